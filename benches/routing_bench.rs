@@ -90,10 +90,66 @@ fn bench_router_scaling(c: &mut Criterion) {
     group.finish();
 }
 
+/// ~400 param routes shaped like a real API: resources, nested resources,
+/// constrained ids, versioned mounts and a few catch-alls
+fn large_param_router() -> Router {
+    const RESOURCES: [&str; 20] = [
+        "users", "posts", "comments", "orders", "invoices", "products", "carts", "reviews",
+        "teams", "projects", "tasks", "tags", "files", "events", "alerts", "reports",
+        "sessions", "tokens", "webhooks", "plans",
+    ];
+
+    let mut router = Router::new();
+    for version in ["v1", "v2"] {
+        for (i, resource) in RESOURCES.iter().enumerate() {
+            let base = format!("/api/{version}/{resource}");
+            let nested = RESOURCES[(i + 1) % RESOURCES.len()];
+            for path in [
+                format!("{base}/:id<u64>"),
+                format!("{base}/:slug<[a-z][a-z0-9-]*>"),
+                format!("{base}/:id/{nested}"),
+                format!("{base}/:id/{nested}/:nested_id"),
+                format!("{base}/:id/{nested}/:nested_id/history"),
+                format!("{base}/:id/settings/:key"),
+                format!("{base}/:id/attachments/*path"),
+                format!("/:tenant/{version}/{resource}/:id"),
+                format!("/:tenant/{version}/{resource}/:id/{nested}/:nested_id"),
+            ] {
+                router.add_route("GET", &path, Box::new(simple_handler));
+            }
+            router.add_route("PUT", &format!("{base}/:id"), Box::new(simple_handler));
+        }
+    }
+    router.add_route("GET", "/assets/*path", Box::new(simple_handler));
+    router
+}
+
+fn bench_router_large_table(c: &mut Criterion) {
+    let router = large_param_router();
+    let mut group = c.benchmark_group("router_large_table");
+
+    for (name, method, path) in [
+        ("first_resource", Method::GET, "/api/v1/users/42"),
+        ("last_resource", Method::GET, "/api/v2/plans/42/users/7/history"),
+        ("constrained_slug", Method::GET, "/api/v2/reports/q3-summary"),
+        ("tenant_prefix", Method::GET, "/acme/v2/webhooks/9/plans/3"),
+        ("catch_all", Method::GET, "/api/v1/files/7/attachments/2024/05/report.pdf"),
+        ("method_fallthrough", Method::PUT, "/api/v2/plans/42"),
+        ("not_found", Method::GET, "/api/v3/users/42"),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| router.find(black_box(&method), black_box(path)))
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_router_insert,
     bench_router_lookup,
-    bench_router_scaling
+    bench_router_scaling,
+    bench_router_large_table
 );
 criterion_main!(benches);
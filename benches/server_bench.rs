@@ -1,23 +1,23 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use firework::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use firework::{Request, Response, RuntimeMode, Server, ShutdownHandle};
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
 use tokio::runtime::Runtime;
 
-async fn start_test_server() -> u16 {
-    use firework::Server;
-    
+async fn start_test_server(runtime: RuntimeMode) -> (u16, ShutdownHandle) {
     // Find available port
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
     let port = listener.local_addr().unwrap().port();
     drop(listener);
-    
+
     let server = Server::new()
-        .get("/", |_req, res| async move { res })
-        .get("/hello", |_req, mut res| async move {
+        .get("/", |_req: Request, res: Response| async move { res })
+        .get("/hello", |_req: Request, mut res: Response| async move {
             res.set_body(b"Hello, World!".to_vec());
             res
         })
-        .get("/json", |_req, mut res| async move {
+        .get("/json", |_req: Request, mut res: Response| async move {
             let body = serde_json::json!({
                 "message": "Hello",
                 "status": "ok"
@@ -26,40 +26,76 @@ async fn start_test_server() -> u16 {
             res.headers.insert("Content-Type".to_string(), "application/json".to_string());
             res
         });
-    
+    let handle = server.shutdown_handle();
+
+    // The server reads its runtime mode from the global config when it starts
+    firework::config().write().await.server.runtime = runtime;
     tokio::spawn(async move {
         server.listen(&format!("127.0.0.1:{}", port)).await.ok();
     });
-    
+
     // Wait for server to be ready
     tokio::time::sleep(Duration::from_millis(100)).await;
-    
-    port
+
+    (port, handle)
+}
+
+/// Send a GET on a keep-alive connection and read the whole response
+async fn get(stream: &mut TcpStream, path: &str) {
+    let request = format!("GET {} HTTP/1.1\r\nHost: bench\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = Vec::with_capacity(512);
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(n > 0, "server closed the connection");
+        response.extend_from_slice(&buf[..n]);
+
+        if let Some(end) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = std::str::from_utf8(&response[..end]).unwrap();
+            let content_length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .map(|(_, value)| value.trim().parse::<usize>().unwrap())
+                .unwrap_or(0);
+            if response.len() >= end + 4 + content_length {
+                return;
+            }
+        }
+    }
 }
 
 fn bench_server_throughput(c: &mut Criterion) {
     let rt = Runtime::new().unwrap();
-    let port = rt.block_on(start_test_server());
-    let client = reqwest::blocking::Client::new();
-    let url = format!("http://127.0.0.1:{}", port);
-    
-    c.bench_function("server_simple_get", |b| {
-        b.iter(|| {
-            client.get(&url).send().unwrap()
-        })
-    });
-    
-    c.bench_function("server_hello_world", |b| {
-        b.iter(|| {
-            client.get(&format!("{}/hello", url)).send().unwrap()
-        })
-    });
-    
-    c.bench_function("server_json_response", |b| {
-        b.iter(|| {
-            client.get(&format!("{}/json", url)).send().unwrap()
-        })
-    });
+
+    // io_uring is only measured when built with `--features io-uring` on a kernel that allows it
+    let mut runtimes = vec![
+        ("multi_thread", RuntimeMode::MultiThread),
+        ("thread_per_core", RuntimeMode::ThreadPerCore),
+    ];
+    if firework::io_uring_available() {
+        runtimes.push(("io_uring", RuntimeMode::IoUring));
+    }
+
+    let mut group = c.benchmark_group("server");
+    for (name, runtime) in runtimes {
+        let (port, handle) = rt.block_on(start_test_server(runtime));
+        let mut stream = rt.block_on(TcpStream::connect(("127.0.0.1", port))).unwrap();
+        stream.set_nodelay(true).unwrap();
+
+        for (bench, path) in [("simple_get", "/"), ("hello_world", "/hello"), ("json_response", "/json")] {
+            group.bench_function(BenchmarkId::new(bench, name), |b| {
+                b.iter(|| rt.block_on(get(&mut stream, path)))
+            });
+        }
+
+        drop(stream);
+        handle.shutdown();
+        rt.block_on(async { tokio::time::sleep(Duration::from_millis(100)).await });
+    }
+    group.finish();
 }
 
 criterion_group! {
@@ -31,6 +31,23 @@ async fn test_endpoint() -> &'static str {
     "Works for GET and POST"
 }
 
+// Test: Named routes
+#[get("/users/:id<u64>", name = "user_show")]
+async fn show_user(Path(id): Path<u64>) -> String {
+    format!("User {}", id)
+}
+
+// Test: Virtual hosts
+#[scope(host = "admin.localhost")]
+mod admin {
+    use super::*;
+
+    #[get("/")]
+    async fn dashboard() -> &'static str {
+        "Admin dashboard"
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let server = routes!();
@@ -46,12 +63,15 @@ async fn main() {
         println!("  {} {}", route.method, route.path);
     }
     
+    println!("\nurl_for(\"user_show\", id = 7) = {}", url_for("user_show", [("id", 7)]).unwrap());
+    
     println!("\n✅ Multiple routes feature works!");
     println!("\nStarting server on http://127.0.0.1:3000");
     println!("Try:");
     println!("  curl http://127.0.0.1:3000/health");
     println!("  curl http://127.0.0.1:3000/api/health");
     println!("  curl http://127.0.0.1:3000/status");
+    println!("  curl -H 'Host: admin.localhost' http://127.0.0.1:3000/");
     
     server.listen("127.0.0.1:3000").await.unwrap();
 }
@@ -30,7 +30,7 @@ struct UserId(String);
 #[middleware]
 fn auth_middleware(req: &mut Request, res: &mut Response) -> Flow {
     if let Some(auth) = req.headers.get("Authorization") {
-        if auth.first().map(|s| s.as_str()) == Some("Bearer valid_token") {
+        if auth == "Bearer valid_token" {
             req.set_context(UserId("123".to_string()));
             return Flow::Continue;
         }
@@ -172,6 +172,7 @@ async fn upload_image(form: FormData) -> Response {
     // Validate config
     let config = UploadConfig {
         max_file_size: 5 * 1024 * 1024, // 5MB
+        max_body_size: Some(20 * 1024 * 1024),
         allowed_extensions: Some(vec!["jpg".to_string(), "jpeg".to_string(), "png".to_string(), "gif".to_string()]),
         allowed_mime_types: Some(vec!["image/".to_string()]),
         upload_dir: PathBuf::from("uploads/images"),
@@ -430,7 +430,7 @@ fn parse_routes_from_file(content: &str, file: &str) -> Vec<RouteInfo> {
     let mut routes = Vec::new();
     
     // Regex to match route macros - these are compile-time constants so unwrap is safe
-    let route_regex = Regex::new(r#"#\[(get|post|put|patch|delete|options|head)\("([^"]+)"\)\]"#)
+    let route_regex = Regex::new(r#"#\[(get|post|put|patch|delete|options|head)\("([^"]+)"(?:\s*,\s*name\s*=\s*"[^"]*")?\)\]"#)
         .expect("Invalid route regex pattern");
     let handler_regex = Regex::new(r"(?:async\s+)?fn\s+(\w+)")
         .expect("Invalid handler regex pattern");
@@ -564,6 +564,8 @@ fn check_route_conflicts(routes: &[RouteInfo]) {
         let pattern = route.path.split('/').map(|segment| {
             if segment.starts_with(':') {
                 ":param"
+            } else if segment.starts_with('*') {
+                "*rest"
             } else {
                 segment
             }
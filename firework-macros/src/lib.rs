@@ -168,17 +168,41 @@ fn validate_path_for_light_guard(path: &str, context: &str) -> Result<(), String
         ));
     }
 
-    for segment in path.split('/') {
-        if !segment.starts_with(':') {
+    let segment_count = path.split('/').count();
+    for (index, segment) in path.split('/').enumerate() {
+        if !segment.starts_with(':') && !segment.starts_with('*') {
             continue;
         }
+        if segment.starts_with('*') && index + 1 != segment_count {
+            return Err(firework_refuse_message(
+                &format!("{context} path '{path}' has catch-all '{segment}' before its last segment"),
+                Some("A catch-all takes the rest of the path; use it only as the last segment, like '/static/*path'."),
+            ));
+        }
         if segment.len() <= 1 {
             return Err(firework_refuse_message(
                 &format!("{context} path '{path}' contains an empty parameter"),
                 Some("Use named params like ':id'."),
             ));
         }
-        let name = &segment[1..];
+        let mut name = &segment[1..];
+        if segment.starts_with(':') {
+            if let Some(open) = name.find('<') {
+                let Some(constraint) = name[open + 1..].strip_suffix('>') else {
+                    return Err(firework_refuse_message(
+                        &format!("{context} path '{path}' has unterminated constraint in '{segment}'"),
+                        Some("Close constraints with '>'; they cannot contain '/'."),
+                    ));
+                };
+                validate_param_constraint(constraint).map_err(|err| {
+                    firework_refuse_message(
+                        &format!("{context} path '{path}' has invalid constraint in '{segment}': {err}"),
+                        Some("Use an integer type, 'uuid' or a regex without '/', like ':id<u64>'."),
+                    )
+                })?;
+                name = &name[..open];
+            }
+        }
         let mut chars = name.chars();
         let Some(first) = chars.next() else {
             return Err(firework_refuse_message(
@@ -190,7 +214,7 @@ fn validate_path_for_light_guard(path: &str, context: &str) -> Result<(), String
         let valid_rest = chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
         if !valid_start || !valid_rest {
             return Err(firework_refuse_message(
-                &format!("{context} path '{path}' has invalid parameter ':{}'", name),
+                &format!("{context} path '{path}' has invalid parameter '{segment}'"),
                 Some("Parameter names must match [A-Za-z_][A-Za-z0-9_]*."),
             ));
         }
@@ -199,6 +223,89 @@ fn validate_path_for_light_guard(path: &str, context: &str) -> Result<(), String
     Ok(())
 }
 
+/// Mirrors `HostPattern::try_parse` in the runtime crate
+fn validate_host_for_light_guard(host: &str) -> Result<(), String> {
+    let refuse = |reason: String| {
+        firework_refuse_message(
+            &reason,
+            Some("Use 'api.example.com', ':tenant.example.com' or '*.example.com', without a port."),
+        )
+    };
+    let trimmed = host.strip_suffix('.').unwrap_or(host);
+    if trimmed.is_empty() {
+        return Err(refuse("scope host cannot be empty".to_string()));
+    }
+    let valid_name = |name: &str| {
+        name.chars().next().is_none_or(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let mut has_static = false;
+    for (index, label) in trimmed.split('.').enumerate() {
+        let valid = if let Some(name) = label.strip_prefix('*') {
+            index == 0 && valid_name(name)
+        } else if let Some(name) = label.strip_prefix(':') {
+            !name.is_empty() && valid_name(name)
+        } else {
+            has_static = true;
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if !valid {
+            return Err(refuse(format!("scope host '{host}' has invalid label '{label}'")));
+        }
+    }
+    if !has_static {
+        return Err(refuse(format!("scope host '{host}' needs at least one static label")));
+    }
+    Ok(())
+}
+
+/// String arguments of `#[scope(...)]`: the prefix and `host = "..."`
+fn scope_string_args(attr: proc_macro2::TokenStream) -> (Option<String>, Option<String>) {
+    let tokens: Vec<proc_macro2::TokenTree> = attr.into_iter().collect();
+    let mut prefix = None;
+    let mut host = None;
+    for (index, token) in tokens.iter().enumerate() {
+        let proc_macro2::TokenTree::Literal(literal) = token else {
+            continue;
+        };
+        let Ok(value) = syn::parse_str::<LitStr>(&literal.to_string()) else {
+            continue;
+        };
+        let key = match index.checked_sub(2).map(|key| (&tokens[key], &tokens[key + 1])) {
+            Some((proc_macro2::TokenTree::Ident(key), proc_macro2::TokenTree::Punct(eq))) if eq.as_char() == '=' => {
+                Some(key.to_string())
+            }
+            _ => None,
+        };
+        match key.as_deref() {
+            None if prefix.is_none() => prefix = Some(value.value()),
+            Some("host") => host = Some(value.value()),
+            _ => {}
+        }
+    }
+    (prefix, host)
+}
+
+/// Mirrors `ParamConstraint::parse` in the runtime crate
+fn validate_param_constraint(constraint: &str) -> Result<(), String> {
+    const TYPES: [&str; 13] = [
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize", "uuid",
+    ];
+    if constraint.is_empty() {
+        return Err("empty constraint".to_string());
+    }
+    if TYPES.contains(&constraint) {
+        return Ok(());
+    }
+    if constraint.contains('/') {
+        return Err(format!("constraint '{constraint}' cannot contain '/'"));
+    }
+    // Compiled exactly as the runtime does, so a route that compiles cannot fail to register
+    regex::Regex::new(&format!("^(?:{constraint})$"))
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
 #[proc_macro_attribute]
 pub fn middleware(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemFn);
@@ -269,11 +376,70 @@ pub fn middleware(attr: TokenStream, item: TokenStream) -> TokenStream {
     output.into()
 }
 
+/// Whether any handler argument is a `BodyStream`, which opts the route into streaming
+fn takes_body_stream(inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::Token![,]>) -> bool {
+    inputs.iter().any(|arg| match arg {
+        syn::FnArg::Typed(pat_type) => match pat_type.ty.as_ref() {
+            syn::Type::Path(type_path) => type_path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "BodyStream"),
+            _ => false,
+        },
+        syn::FnArg::Receiver(_) => false,
+    })
+}
+
+/// Arguments of a route attribute: `("/users/:id")` or `("/users/:id", name = "user_show")`
+struct RouteArgs {
+    path: String,
+    name: Option<String>,
+}
+
+fn parse_route_args(tokens: proc_macro2::TokenStream) -> syn::Result<RouteArgs> {
+    let parser = |input: syn::parse::ParseStream| {
+        let path: LitStr = input.parse()?;
+        let mut name = None;
+        while input.parse::<Option<syn::Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<syn::Token![=]>()?;
+            let value: LitStr = input.parse()?;
+            if key != "name" {
+                return Err(syn::Error::new(key.span(), format!("unknown route argument `{key}`, expected `name`")));
+            }
+            if name.is_some() {
+                return Err(syn::Error::new(key.span(), "route name given twice"));
+            }
+            if value.value().trim().is_empty() {
+                return Err(syn::Error::new(value.span(), "route name cannot be empty"));
+            }
+            name = Some(value.value());
+        }
+        Ok(RouteArgs { path: path.value(), name })
+    };
+    parser.parse2(tokens)
+}
+
+impl RouteArgs {
+    fn name_tokens(&self) -> proc_macro2::TokenStream {
+        match &self.name {
+            Some(name) => quote! { ::core::option::Option::Some(#name) },
+            None => quote! { ::core::option::Option::None },
+        }
+    }
+}
+
 fn route_macro(method: &str, attr: TokenStream, item: TokenStream) -> TokenStream {
-    let path = parse_macro_input!(attr as LitStr).value();
+    let args = match parse_route_args(attr.into()) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let path = args.path.clone();
     if let Err(err) = validate_path_for_light_guard(&path, "route") {
         return compile_error_output(err);
     }
+    let name = args.name_tokens();
     
     // Check if we're the innermost macro by looking if input already has the wrapper
     let item_str = item.to_string();
@@ -321,6 +487,8 @@ fn route_macro(method: &str, attr: TokenStream, item: TokenStream) -> TokenStrea
         false
     };
     
+    let streaming_body = takes_body_stream(&input.sig.inputs);
+    
     let wrapper_impl = if uses_standard_signature {
         // Standard signature - direct call
         quote! {
@@ -392,6 +560,9 @@ fn route_macro(method: &str, attr: TokenStream, item: TokenStream) -> TokenStrea
                 0
             },
             is_static_path: ::firework::__private::const_is_static_path(#path),
+            streaming_body: #streaming_body,
+            name: #name,
+            host: ::core::option::Option::None,
         };
     };
     
@@ -404,20 +575,28 @@ pub fn scope(attr: TokenStream, item: TokenStream) -> TokenStream {
     
     // Parse attributes
     let attr_str = attr.to_string();
-    let mut prefix = String::new();
     let mut pre_middlewares: Vec<String> = Vec::new();
     let mut post_middlewares: Vec<String> = Vec::new();
     
-    // Parse prefix
-    if let Some(prefix_start) = attr_str.find('"') {
-        if let Some(prefix_end) = attr_str[prefix_start + 1..].find('"') {
-            prefix = attr_str[prefix_start + 1..prefix_start + 1 + prefix_end].to_string();
+    // Parse prefix and host
+    let (prefix, host) = scope_string_args(attr.into());
+    let prefix = prefix.unwrap_or_default();
+
+    // A host scope may serve its routes without a prefix
+    if !(prefix.is_empty() && host.is_some()) {
+        if let Err(err) = validate_path_for_light_guard(&prefix, "scope") {
+            return compile_error_output(err);
         }
     }
-
-    if let Err(err) = validate_path_for_light_guard(&prefix, "scope") {
-        return compile_error_output(err);
+    if let Some(host) = &host {
+        if let Err(err) = validate_host_for_light_guard(host) {
+            return compile_error_output(err);
+        }
     }
+    let host_tokens = match &host {
+        Some(host) => quote! { ::core::option::Option::Some(#host) },
+        None => quote! { ::core::option::Option::None },
+    };
     
     // Parse middleware arrays
     // Formato: middleware = [a, b], post = [c, d]
@@ -480,11 +659,16 @@ pub fn scope(attr: TokenStream, item: TokenStream) -> TokenStream {
                 }
                 
                 if let Some((method, route_attr)) = route_attr {
-                    let path = if let syn::Meta::List(meta_list) = &route_attr.meta {
-                        meta_list.tokens.to_string().trim_matches('"').to_string()
+                    let args = if let syn::Meta::List(meta_list) = &route_attr.meta {
+                        match parse_route_args(meta_list.tokens.clone()) {
+                            Ok(args) => args,
+                            Err(err) => return err.to_compile_error().into(),
+                        }
                     } else {
-                        String::new()
+                        RouteArgs { path: String::new(), name: None }
                     };
+                    let path = args.path.clone();
+                    let name = args.name_tokens();
                     if let Err(err) = validate_path_for_light_guard(&path, "scope route") {
                         return compile_error_output(err);
                     }
@@ -516,6 +700,7 @@ pub fn scope(attr: TokenStream, item: TokenStream) -> TokenStream {
                     
                     // Detect if uses standard signature
                     let uses_standard_signature = func.sig.inputs.len() == 2;
+                    let streaming_body = takes_body_stream(&func.sig.inputs);
                     
                     // Build the core handler call
                     let handler_call = if uses_standard_signature {
@@ -682,6 +867,9 @@ pub fn scope(attr: TokenStream, item: TokenStream) -> TokenStream {
                                 0
                             },
                             is_static_path: ::firework::__private::const_is_static_path(#full_path),
+                            streaming_body: #streaming_body,
+                            name: #name,
+                            host: #host_tokens,
                         };
                     });
                 } else {
@@ -906,12 +1094,13 @@ pub fn run(input: TokenStream) -> TokenStream {
                     .expect("Failed to start server");
             }
             
-            #[::tokio::main]
-            async fn __firework_run_main() {
-                __firework_run_async().await;
-            }
-            
-            __firework_run_main();
+            // Runtime flavor and thread count follow `[server] runtime` and `workers`
+            ::firework::Config::from_file(#config_file)
+                .unwrap_or_default()
+                .server
+                .build_runtime()
+                .expect("Failed to start the Tokio runtime")
+                .block_on(__firework_run_async());
         }
     };
     
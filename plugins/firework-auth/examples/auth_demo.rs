@@ -310,7 +310,6 @@ fn admin_middleware(req: &mut Request, res: &mut Response) -> Flow {
     // First, require auth
     let token = match req.headers
         .get("authorization")
-        .and_then(|v| v.first())
         .and_then(|s| s.strip_prefix("Bearer "))
     {
         Some(t) => t,
@@ -313,8 +313,6 @@ impl firework::FromRequest for OptionalAuth {
 fn extract_token_from_header(req: &Request) -> Option<String> {
     req.headers
         .get("authorization")
-        .or_else(|| req.headers.get("Authorization"))
-        .and_then(|v| v.first())
         .and_then(|s| s.strip_prefix("Bearer "))
         .map(|s| s.to_string())
 }
@@ -266,8 +266,6 @@ impl Plugin for CompressionPlugin {
         // Get Accept-Encoding header
         let accept_encoding = req.headers
             .get("accept-encoding")
-            .or_else(|| req.headers.get("Accept-Encoding"))
-            .and_then(|v| v.first())
             .map(|s| s.as_str())
             .unwrap_or("");
         
@@ -294,7 +292,7 @@ impl Plugin for CompressionPlugin {
         if compressed.len() < data.len() {
             res.body = ResponseBody::Static(compressed);
             res.headers.insert("Content-Length".to_string(), res.body.len().unwrap_or(0).to_string());
-            res.headers.insert("Vary".to_string(), "Accept-Encoding".to_string());
+            res.headers.append("Vary".to_string(), "Accept-Encoding".to_string());
         }
         
         Ok(())
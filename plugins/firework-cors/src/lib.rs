@@ -2,10 +2,9 @@
 //! 
 //! Cross-Origin Resource Sharing (CORS) middleware for Firework framework.
 
-use firework::{Plugin, PluginResult, PluginMetadata, Request, Response, Method, StatusCode, ResponseBody};
+use firework::{Plugin, PluginResult, PluginMetadata, Request, Response, Method, StatusCode, HeaderMap};
 use serde::{Deserialize, Serialize};
 use std::any::Any;
-use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CorsConfig {
@@ -159,10 +158,9 @@ impl CorsPlugin {
     fn handle_preflight(&self, req: &Request) -> Response {
         let origin = req.headers
             .get("origin")
-            .and_then(|v| v.first())
             .map(|s| s.as_str());
         
-        let mut headers = HashMap::new();
+        let mut headers = HeaderMap::new();
         
         let origin_header = self.get_origin_header(origin);
         if !origin_header.is_empty() {
@@ -180,18 +178,14 @@ impl CorsPlugin {
             headers.insert("Access-Control-Max-Age".to_string(), max_age.to_string());
         }
         
-        Response {
-            version: firework::Version::Http11,
-            status: StatusCode::NoContent,
-            headers,
-            body: ResponseBody::Static(vec![]),
-        }
+        let mut response = Response::new(StatusCode::NoContent, Vec::new());
+        response.headers = headers;
+        response
     }
     
     fn add_cors_headers(&self, req: &Request, res: &mut Response) {
         let origin = req.headers
             .get("origin")
-            .and_then(|v| v.first())
             .map(|s| s.as_str());
         
         let origin_header = self.get_origin_header(origin);
@@ -208,7 +202,7 @@ impl CorsPlugin {
             res.headers.insert("Access-Control-Expose-Headers".to_string(), self.config.exposed_headers.join(", "));
         }
         
-        res.headers.insert("Vary".to_string(), "Origin".to_string());
+        res.headers.append("Vary".to_string(), "Origin".to_string());
     }
 }
 
@@ -248,20 +248,8 @@ impl VitePlugin {
     
     /// Proxy a request to Vite dev server
     async fn proxy_request(&self, req: &Request) -> Result<Option<Response>> {
-        // Build full URL with query string
-        let query_string = if let Some(query) = &req.uri.query {
-            let params: Vec<String> = query
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect();
-            if !params.is_empty() {
-                format!("?{}", params.join("&"))
-            } else {
-                String::new()
-            }
-        } else {
-            String::new()
-        };
+        // Build full URL with the query string as sent
+        let query_string = req.uri.raw_query.as_deref().map(|raw| format!("?{}", raw)).unwrap_or_default();
         
         let vite_url = format!("{}{}{}", self.dev_url(), req.uri.path, query_string);
 
@@ -285,11 +273,7 @@ impl VitePlugin {
                 
                 match response.bytes().await {
                     Ok(body) => {
-                        let status = match status_code {
-                            200 => StatusCode::Ok,
-                            404 => StatusCode::NotFound,
-                            code => StatusCode::Custom(code, "Vite".to_string()),
-                        };
+                        let status = StatusCode::from_u16(status_code);
 
                         let mut new_res = Response::new(status, body.to_vec());
 
@@ -535,20 +519,8 @@ pub async fn vite_middleware(
         return Flow::Continue;
     }
 
-    // Build full URL with query string
-    let query_string = if let Some(query) = &req.uri.query {
-        let params: Vec<String> = query
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect();
-        if !params.is_empty() {
-            format!("?{}", params.join("&"))
-        } else {
-            String::new()
-        }
-    } else {
-        String::new()
-    };
+    // Build full URL with the query string as sent
+    let query_string = req.uri.raw_query.as_deref().map(|raw| format!("?{}", raw)).unwrap_or_default();
     
     let vite_url = format!("{}{}{}", vite.dev_url(), req.uri.path, query_string);
 
@@ -566,11 +538,9 @@ pub async fn vite_middleware(
     };
     
     // Forward important headers
-    for (key, values) in &req.headers {
+    for (key, value) in &req.headers {
         if !key.to_lowercase().starts_with("host") {
-            if let Some(first_value) = values.first() {
-                proxy_req = proxy_req.header(key, first_value);
-            }
+            proxy_req = proxy_req.header(key, value);
         }
     }
 
@@ -581,11 +551,7 @@ pub async fn vite_middleware(
             
             match response.bytes().await {
                 Ok(body) => {
-                    let status = match status_code {
-                        200 => StatusCode::Ok,
-                        404 => StatusCode::NotFound,
-                        code => StatusCode::Custom(code, "Vite".to_string()),
-                    };
+                    let status = StatusCode::from_u16(status_code);
 
                     let mut new_res = Response::new(status, body.to_vec());
 
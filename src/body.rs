@@ -0,0 +1,321 @@
+use crate::{Error, FromRequest, Request, Response, Result};
+use bytes::{Bytes, BytesMut};
+use futures_util::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Sleep;
+
+/// Chunks queued between the connection and the handler before reads pause
+const CHANNEL_CAPACITY: usize = 8;
+
+pub(crate) type BodySender = mpsc::Sender<std::io::Result<Bytes>>;
+
+/// Request body read incrementally instead of buffered up front
+///
+/// Taking `BodyStream` as a handler argument opts the route into streaming:
+/// chunks come straight from the socket (or the h2 `RecvStream`) as the
+/// handler asks for them, and `req.body` stays empty. On buffered routes it
+/// yields the already-read body as a single chunk.
+///
+/// ```rust,ignore
+/// #[post("/upload")]
+/// async fn upload(mut body: BodyStream) -> Result<String> {
+///     let mut total = 0;
+///     while let Some(chunk) = body.chunk().await? {
+///         total += chunk.len();
+///     }
+///     Ok(format!("{total} bytes"))
+/// }
+/// ```
+pub struct BodyStream {
+    source: Source,
+    /// Length the client declared up front, if it did
+    content_length: Option<u64>,
+    /// Longest wait for the next chunk before the read fails
+    read_timeout: Option<Duration>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    /// Fired on the first read, telling the connection the body is wanted
+    first_read: Option<oneshot::Sender<()>>,
+    /// Fired once the body has been read to the end or failed
+    finished: Option<oneshot::Sender<()>>,
+}
+
+enum Source {
+    Buffered(Option<Bytes>),
+    Channel(mpsc::Receiver<std::io::Result<Bytes>>),
+    #[cfg(feature = "http2")]
+    H2(h2::RecvStream),
+}
+
+impl BodyStream {
+    pub(crate) fn buffered(body: Vec<u8>) -> Self {
+        let len = body.len() as u64;
+        let chunk = (!body.is_empty()).then(|| Bytes::from(body));
+        Self::from_source(Source::Buffered(chunk)).with_content_length(Some(len))
+    }
+
+    /// Stream fed by the connection task through the returned sender
+    pub(crate) fn channel() -> (BodySender, Self) {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        (tx, Self::from_source(Source::Channel(rx)))
+    }
+
+    #[cfg(feature = "http2")]
+    pub(crate) fn h2(recv: h2::RecvStream) -> Self {
+        Self::from_source(Source::H2(recv))
+    }
+
+    fn from_source(source: Source) -> Self {
+        Self {
+            source,
+            content_length: None,
+            read_timeout: None,
+            sleep: None,
+            first_read: None,
+            finished: None,
+        }
+    }
+
+    /// Fail with 408 when no data arrives for `timeout`
+    pub(crate) fn with_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn with_content_length(mut self, len: Option<u64>) -> Self {
+        self.content_length = len;
+        self
+    }
+
+    /// The returned receiver resolves on the first read, or fails if the
+    /// stream is dropped unread
+    pub(crate) fn notify_first_read(&mut self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.first_read = Some(tx);
+        rx
+    }
+
+    /// The returned receiver resolves once the body has been read to the end or
+    /// failed, or fails if the stream is dropped before that
+    pub(crate) fn notify_finished(&mut self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.finished = Some(tx);
+        rx
+    }
+
+    /// Body length the client declared (`Content-Length`), if known before reading
+    ///
+    /// Lets a handler turn away an oversized upload before any of it is sent.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Next chunk of the body, or `None` once it has been fully read
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        use futures_util::StreamExt;
+
+        match self.next().await {
+            Some(Ok(chunk)) => Ok(Some(chunk)),
+            Some(Err(e)) => Err(body_error(e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the rest of the body into memory, failing with 413 past `limit` bytes
+    pub async fn collect(mut self, limit: usize) -> Result<Bytes> {
+        let mut body = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                return Err(Error::PayloadTooLarge(format!(
+                    "Request body exceeds {} bytes",
+                    limit
+                )));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body.freeze())
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = std::io::Result<Bytes>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(first_read) = this.first_read.take() {
+            let _ = first_read.send(());
+        }
+        let poll = match &mut this.source {
+            Source::Buffered(chunk) => Poll::Ready(chunk.take().map(Ok)),
+            Source::Channel(rx) => rx.poll_recv(cx),
+            #[cfg(feature = "http2")]
+            Source::H2(recv) => match recv.poll_data(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    // Hand the window back so the peer keeps sending
+                    let _ = recv.flow_control().release_capacity(data.len());
+                    Poll::Ready(Some(Ok(data)))
+                }
+                Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(std::io::Error::other(e)))),
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            },
+        };
+
+        match (&poll, this.read_timeout) {
+            (Poll::Pending, Some(timeout)) => {
+                let sleep = this
+                    .sleep
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+                if sleep.as_mut().poll(cx).is_ready() {
+                    this.sleep = None;
+                    return Poll::Ready(Some(Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "request body read timed out",
+                    ))));
+                }
+            }
+            // Any progress restarts the clock
+            _ => this.sleep = None,
+        }
+        if matches!(poll, Poll::Ready(None | Some(Err(_)))) {
+            if let Some(finished) = this.finished.take() {
+                let _ = finished.send(());
+            }
+        }
+        poll
+    }
+}
+
+impl std::fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self.source {
+            Source::Buffered(_) => "buffered",
+            Source::Channel(_) => "http1",
+            #[cfg(feature = "http2")]
+            Source::H2(_) => "http2",
+        };
+        f.debug_struct("BodyStream").field("source", &source).finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl FromRequest for BodyStream {
+    async fn from_request(req: &mut Request, _res: &mut Response) -> Result<Self> {
+        Ok(req.take_body_stream())
+    }
+}
+
+/// Framing problems are the client's fault; anything else is ours
+fn body_error(err: std::io::Error) -> Error {
+    match err.kind() {
+        std::io::ErrorKind::InvalidData => Error::BadRequest(format!("Malformed request body: {}", err)),
+        std::io::ErrorKind::UnexpectedEof => Error::BadRequest("Request body ended early".into()),
+        std::io::ErrorKind::TimedOut => Error::RequestTimeout("Timed out reading request body".into()),
+        _ => Error::IoError(err),
+    }
+}
+
+/// Body on a `Request` that has not been read yet
+///
+/// The stream can only be consumed once, so clones of a request start without it.
+#[derive(Default)]
+pub(crate) struct PendingBody(pub(crate) Option<BodyStream>);
+
+impl Clone for PendingBody {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+impl std::fmt::Debug for PendingBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Route handler wrapper that receives its request body as a stream
+///
+/// Routes declared with a `BodyStream` argument are wrapped automatically;
+/// use this for handlers registered with `Server::route` and friends.
+pub struct Streaming<H>(pub H);
+
+impl<H: crate::AsyncHandler> crate::AsyncHandler for Streaming<H> {
+    fn call(
+        &self,
+        req: Request,
+        res: Response,
+    ) -> Pin<Box<dyn Future<Output = Response> + Send>> {
+        self.0.call(req, res)
+    }
+
+    fn streams_body(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn buffered_body_is_a_single_chunk() {
+        let mut body = BodyStream::buffered(b"hello".to_vec());
+        assert_eq!(body.chunk().await.unwrap(), Some(Bytes::from_static(b"hello")));
+        assert_eq!(body.chunk().await.unwrap(), None);
+
+        let mut empty = BodyStream::buffered(Vec::new());
+        assert_eq!(empty.chunk().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn channel_body_yields_chunks_in_order() {
+        let (tx, body) = BodyStream::channel();
+        tokio::spawn(async move {
+            for part in ["ab", "cd", "ef"] {
+                tx.send(Ok(Bytes::from(part))).await.unwrap();
+            }
+        });
+
+        assert_eq!(body.collect(16).await.unwrap(), Bytes::from_static(b"abcdef"));
+    }
+
+    #[tokio::test]
+    async fn collect_enforces_limit() {
+        let (tx, body) = BodyStream::channel();
+        tokio::spawn(async move {
+            for _ in 0..4 {
+                if tx.send(Ok(Bytes::from_static(b"0123456789"))).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let err = body.collect(25).await.unwrap_err();
+        assert!(matches!(err, Error::PayloadTooLarge(_)));
+    }
+
+    #[tokio::test]
+    async fn framing_errors_are_bad_requests() {
+        let (tx, mut body) = BodyStream::channel();
+        tx.send(Err(std::io::ErrorKind::UnexpectedEof.into())).await.unwrap();
+
+        assert!(matches!(body.chunk().await, Err(Error::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn stalled_body_times_out() {
+        let (tx, body) = BodyStream::channel();
+        let mut body = body.with_read_timeout(Some(Duration::from_millis(20)));
+        tx.send(Ok(Bytes::from_static(b"first"))).await.unwrap();
+
+        assert_eq!(body.chunk().await.unwrap(), Some(Bytes::from_static(b"first")));
+        assert!(matches!(body.chunk().await, Err(Error::RequestTimeout(_))));
+        drop(tx);
+    }
+}
@@ -0,0 +1,367 @@
+//! Incremental decoder for `Transfer-Encoding: chunked` request bodies (RFC 9112 §7.1)
+
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Longest chunk-size line (size + extensions) we accept
+const MAX_CHUNK_LINE: usize = 4096;
+/// Largest trailer section we accept
+const MAX_TRAILER_BYTES: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChunkedError {
+    /// Chunk size is missing, not hex, or overflows
+    InvalidChunkSize,
+    /// Chunk extension contains forbidden bytes
+    InvalidChunkExtension,
+    /// Chunk data is not followed by CRLF
+    MissingChunkTerminator,
+    /// Chunk-size line exceeds `MAX_CHUNK_LINE`
+    LineTooLong,
+    /// Trailer field is malformed
+    InvalidTrailer,
+    /// Trailer section exceeds `MAX_TRAILER_BYTES`
+    TrailerTooLarge,
+    /// Chunk sizes add up to more than the decoder's body limit
+    BodyTooLarge,
+}
+
+impl std::fmt::Display for ChunkedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            ChunkedError::InvalidChunkSize => "invalid chunk size",
+            ChunkedError::InvalidChunkExtension => "invalid chunk extension",
+            ChunkedError::MissingChunkTerminator => "chunk data not terminated by CRLF",
+            ChunkedError::LineTooLong => "chunk size line too long",
+            ChunkedError::InvalidTrailer => "invalid trailer field",
+            ChunkedError::TrailerTooLarge => "trailer section too large",
+            ChunkedError::BodyTooLarge => "chunked body too large",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for ChunkedError {}
+
+/// Result of a single decoding step
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Decoded {
+    /// A piece of the payload
+    Data(Bytes),
+    /// The buffer does not hold enough bytes to make progress
+    NeedMore,
+    /// Last chunk and trailer section consumed; the body is complete
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailer,
+    Done,
+}
+
+/// Chunked body decoder that consumes bytes from a connection buffer.
+///
+/// Bytes left in the buffer after `Decoded::Done` belong to the next message.
+/// Trailer fields are validated and then discarded, which RFC 9112 §7.1.2 allows.
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: State,
+    trailer_bytes: usize,
+    /// Payload bytes announced by the chunk sizes so far
+    body_bytes: u64,
+    max_body: Option<u64>,
+}
+
+impl ChunkedDecoder {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: State::Size,
+            trailer_bytes: 0,
+            body_bytes: 0,
+            max_body: None,
+        }
+    }
+
+    /// Fail with `BodyTooLarge` as soon as a chunk size takes the body past `limit` bytes
+    pub(crate) fn with_max_body(mut self, limit: Option<usize>) -> Self {
+        self.max_body = limit.map(|limit| limit as u64);
+        self
+    }
+
+    /// Decode the next piece of `buf`, consuming the bytes it used
+    pub(crate) fn decode(&mut self, buf: &mut BytesMut) -> Result<Decoded, ChunkedError> {
+        loop {
+            match self.state {
+                State::Size => {
+                    let Some(line) = take_line(buf, MAX_CHUNK_LINE, ChunkedError::LineTooLong)? else {
+                        return Ok(Decoded::NeedMore);
+                    };
+                    let size = parse_chunk_size_line(&line)?;
+                    self.body_bytes = self.body_bytes.saturating_add(size);
+                    if self.max_body.is_some_and(|max| self.body_bytes > max) {
+                        return Err(ChunkedError::BodyTooLarge);
+                    }
+                    self.state = if size == 0 { State::Trailer } else { State::Data(size) };
+                }
+                State::Data(remaining) => {
+                    if buf.is_empty() {
+                        return Ok(Decoded::NeedMore);
+                    }
+                    let take = remaining.min(buf.len() as u64) as usize;
+                    let data = buf.split_to(take).freeze();
+                    let left = remaining - take as u64;
+                    self.state = if left == 0 { State::DataEnd } else { State::Data(left) };
+                    return Ok(Decoded::Data(data));
+                }
+                State::DataEnd => {
+                    if buf.len() < 2 {
+                        return Ok(Decoded::NeedMore);
+                    }
+                    if &buf[..2] != b"\r\n" {
+                        return Err(ChunkedError::MissingChunkTerminator);
+                    }
+                    buf.advance(2);
+                    self.state = State::Size;
+                }
+                State::Trailer => {
+                    let limit = MAX_TRAILER_BYTES - self.trailer_bytes;
+                    let Some(line) = take_line(buf, limit, ChunkedError::TrailerTooLarge)? else {
+                        return Ok(Decoded::NeedMore);
+                    };
+                    self.trailer_bytes += line.len() + 2;
+                    if line.is_empty() {
+                        self.state = State::Done;
+                    } else {
+                        validate_trailer_field(&line)?;
+                    }
+                }
+                State::Done => return Ok(Decoded::Done),
+            }
+        }
+    }
+}
+
+/// Read a whole chunked body, pulling more bytes from `reader` into `buf` as needed.
+///
+/// Framing errors, and a body over `max_body` bytes, surface as `io::ErrorKind::InvalidData`
+/// wrapping the `ChunkedError`. Bytes after the body stay in `buf`.
+pub(crate) async fn read_chunked_body<R>(
+    reader: &mut R,
+    buf: &mut BytesMut,
+    max_body: Option<usize>,
+) -> std::io::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut decoder = ChunkedDecoder::new().with_max_body(max_body);
+    let mut body = Vec::new();
+    loop {
+        match decoder.decode(buf) {
+            Ok(Decoded::Data(data)) => body.extend_from_slice(&data),
+            Ok(Decoded::Done) => return Ok(body),
+            Ok(Decoded::NeedMore) => {
+                if reader.read_buf(buf).await? == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Split off one CRLF-terminated line (without the CRLF)
+fn take_line(
+    buf: &mut BytesMut,
+    limit: usize,
+    too_long: ChunkedError,
+) -> Result<Option<BytesMut>, ChunkedError> {
+    match memchr::memchr(b'\n', buf) {
+        Some(lf) => {
+            if lf + 1 > limit {
+                return Err(too_long);
+            }
+            if lf == 0 || buf[lf - 1] != b'\r' {
+                // Bare LF line endings are a classic smuggling vector; refuse them.
+                return Err(ChunkedError::MissingChunkTerminator);
+            }
+            let mut line = buf.split_to(lf + 1);
+            line.truncate(lf - 1);
+            Ok(Some(line))
+        }
+        None if buf.len() >= limit => Err(too_long),
+        None => Ok(None),
+    }
+}
+
+/// chunk-size [ chunk-ext ], where chunk-ext = *( BWS ";" BWS ext-name [ BWS "=" BWS ext-val ] )
+fn parse_chunk_size_line(line: &[u8]) -> Result<u64, ChunkedError> {
+    let digits = line.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+    if digits == 0 {
+        return Err(ChunkedError::InvalidChunkSize);
+    }
+
+    let mut size: u64 = 0;
+    for &b in &line[..digits] {
+        let digit = (b as char).to_digit(16).unwrap_or(0) as u64;
+        size = size
+            .checked_mul(16)
+            .and_then(|s| s.checked_add(digit))
+            .ok_or(ChunkedError::InvalidChunkSize)?;
+    }
+
+    let rest = &line[digits..];
+    let ext_start = rest.iter().position(|b| *b != b' ' && *b != b'\t');
+    match ext_start {
+        None => Ok(size),
+        Some(pos) if rest[pos] == b';' => {
+            let valid = rest[pos..]
+                .iter()
+                .all(|&b| b == b'\t' || (b' '..=b'~').contains(&b) || b >= 0x80);
+            if valid {
+                Ok(size)
+            } else {
+                Err(ChunkedError::InvalidChunkExtension)
+            }
+        }
+        Some(_) => Err(ChunkedError::InvalidChunkSize),
+    }
+}
+
+fn validate_trailer_field(line: &[u8]) -> Result<(), ChunkedError> {
+    let Some(colon) = memchr::memchr(b':', line) else {
+        return Err(ChunkedError::InvalidTrailer);
+    };
+    let name = &line[..colon];
+    let name_ok = !name.is_empty() && name.iter().all(|b| is_token_byte(*b));
+    let value_ok = line[colon + 1..]
+        .iter()
+        .all(|&b| b == b'\t' || (b' '..=b'~').contains(&b) || b >= 0x80);
+    if name_ok && value_ok {
+        Ok(())
+    } else {
+        Err(ChunkedError::InvalidTrailer)
+    }
+}
+
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(input: &[u8]) -> Result<(Vec<u8>, BytesMut), ChunkedError> {
+        let mut buf = BytesMut::from(input);
+        let mut decoder = ChunkedDecoder::new();
+        let mut body = Vec::new();
+        loop {
+            match decoder.decode(&mut buf)? {
+                Decoded::Data(data) => body.extend_from_slice(&data),
+                Decoded::NeedMore => panic!("incomplete input"),
+                Decoded::Done => return Ok((body, buf)),
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_chunks_and_leaves_next_message() {
+        let (body, rest) =
+            decode_all(b"5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\nGET / HTTP/1.1\r\n").unwrap();
+        assert_eq!(body, b"hello, world");
+        assert_eq!(&rest[..], b"GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn accepts_extensions_and_trailers() {
+        let (body, rest) = decode_all(
+            b"4;name=value ; other\r\nWiki\r\nA ;x=\"q\"\r\npedia in\r\n\r\n0\r\nExpires: never\r\nX-Sum: 1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(body, b"Wikipedia in\r\n");
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn resumes_across_partial_reads() {
+        let input = b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        let mut decoder = ChunkedDecoder::new();
+        let mut buf = BytesMut::new();
+        let mut body = Vec::new();
+        for byte in input {
+            buf.extend_from_slice(&[*byte]);
+            while let Decoded::Data(data) = decoder.decode(&mut buf).unwrap() {
+                body.extend_from_slice(&data);
+            }
+        }
+        assert!(decoder.state == State::Done);
+        assert_eq!(body, b"abcde");
+    }
+
+    #[test]
+    fn rejects_malformed_framing() {
+        assert_eq!(decode_all(b"zz\r\n").unwrap_err(), ChunkedError::InvalidChunkSize);
+        assert_eq!(decode_all(b"-1\r\n").unwrap_err(), ChunkedError::InvalidChunkSize);
+        assert_eq!(
+            decode_all(b"1ffffffffffffffff\r\n").unwrap_err(),
+            ChunkedError::InvalidChunkSize
+        );
+        assert_eq!(
+            decode_all(b"3\r\nabcX\r\n").unwrap_err(),
+            ChunkedError::MissingChunkTerminator
+        );
+        assert_eq!(decode_all(b"3\nabc\r\n").unwrap_err(), ChunkedError::MissingChunkTerminator);
+        assert_eq!(
+            decode_all(b"0\r\nno colon here\r\n\r\n").unwrap_err(),
+            ChunkedError::InvalidTrailer
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_body_from_reader_in_pieces() {
+        let mut buf = BytesMut::from(&b"4\r\nab"[..]);
+        let mut reader = &b"cd\r\n0\r\n\r\nnext"[..];
+        let body = read_chunked_body(&mut reader, &mut buf, None).await.unwrap();
+        assert_eq!(body, b"abcd");
+        assert_eq!(&buf[..], b"next");
+
+        let mut buf = BytesMut::from(&b"4\r\nab"[..]);
+        let mut truncated = &b""[..];
+        let err = read_chunked_body(&mut truncated, &mut buf, None).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_body_limit_before_reading_the_chunk() {
+        let mut buf = BytesMut::from(&b"3\r\nabc\r\n"[..]);
+        let mut reader = &b"3\r\ndef\r\n0\r\n\r\n"[..];
+        let body = read_chunked_body(&mut reader, &mut buf, Some(6)).await.unwrap();
+        assert_eq!(body, b"abcdef");
+
+        // The oversized chunk is refused from its size line; its data is never read
+        let mut buf = BytesMut::from(&b"3\r\nabc\r\n4\r\n"[..]);
+        let mut endless = tokio::io::repeat(b'x');
+        let err = read_chunked_body(&mut endless, &mut buf, Some(6)).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let cause = err.get_ref().and_then(|e| e.downcast_ref::<ChunkedError>());
+        assert_eq!(cause, Some(&ChunkedError::BodyTooLarge));
+    }
+
+    #[test]
+    fn limits_line_and_trailer_size() {
+        let mut long_line = b"1;".to_vec();
+        long_line.resize(long_line.len() + MAX_CHUNK_LINE, b'a');
+        assert_eq!(decode_all(&long_line).unwrap_err(), ChunkedError::LineTooLong);
+
+        let mut trailers = b"0\r\n".to_vec();
+        for i in 0..1000 {
+            trailers.extend_from_slice(format!("X-T{i}: value\r\n").as_bytes());
+        }
+        trailers.extend_from_slice(b"\r\n");
+        assert_eq!(decode_all(&trailers).unwrap_err(), ChunkedError::TrailerTooLarge);
+    }
+}
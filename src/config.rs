@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::sync::RwLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,8 +9,123 @@ pub struct ServerConfig {
     pub address: String,
     #[serde(default = "default_port")]
     pub port: u16,
+    /// Runtime threads; in `thread_per_core` mode, one per worker (0 = one per CPU core)
     #[serde(default = "default_workers")]
     pub workers: usize,
+    /// How connections are spread over threads
+    #[serde(default)]
+    pub runtime: RuntimeMode,
+    /// Seconds to wait for in-flight requests on shutdown before giving up
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+    /// Seconds a client gets to send the request line and headers (0 = no limit)
+    #[serde(default = "default_header_timeout")]
+    pub header_timeout: u64,
+    /// Seconds a client gets to send the request body; streamed bodies apply
+    /// it to each wait for the next chunk instead (0 = no limit)
+    #[serde(default = "default_body_timeout")]
+    pub body_timeout: u64,
+    /// Seconds an idle keep-alive connection stays open (0 = no limit)
+    #[serde(default = "default_keepalive_timeout")]
+    pub keepalive_timeout: u64,
+    /// Seconds a handler may run before the request fails with 503; on streaming
+    /// routes the clock starts once the body has been read (0 = no limit)
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+    /// Largest request body in bytes read into `req.body`; bigger ones get 413.
+    /// Streaming routes read past it and set their own limit (0 = no limit)
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Restrict IPv6 listeners to IPv6 clients; by default `[::]` also accepts IPv4
+    #[serde(default)]
+    pub ipv6_only: bool,
+    /// Permissions for `unix:` socket files, e.g. `0o660` (default: left to the umask)
+    #[serde(default)]
+    pub unix_socket_mode: Option<u32>,
+    /// Serve HTTPS instead of plain HTTP (needs the `tls` feature)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// How request paths are canonicalized before routing
+    #[serde(default)]
+    pub paths: PathConfig,
+}
+
+/// Request path canonicalization for `[server.paths]`
+///
+/// Paths are always percent-decoded (invalid escapes or UTF-8 answer 400);
+/// `%2F` and `%25` stay encoded so they never split a segment, and route
+/// params come out fully decoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathConfig {
+    /// Collapse `//` into `/`; routing ignores empty segments either way
+    #[serde(default = "default_true")]
+    pub merge_slashes: bool,
+    /// What to do with `.` and `..` segments
+    #[serde(default)]
+    pub dot_segments: DotSegments,
+    /// What to do with a trailing `/`
+    #[serde(default)]
+    pub trailing_slash: TrailingSlash,
+}
+
+impl Default for PathConfig {
+    fn default() -> Self {
+        Self {
+            merge_slashes: true,
+            dot_segments: DotSegments::default(),
+            trailing_slash: TrailingSlash::default(),
+        }
+    }
+}
+
+/// Handling of `.` and `..` path segments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DotSegments {
+    /// Remove them as RFC 3986 §5.2.4 does; `..` above the root answers 400
+    #[default]
+    Resolve,
+    /// Answer 400
+    Reject,
+}
+
+/// Handling of a trailing `/` on request paths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingSlash {
+    /// Route `/users/` like `/users`
+    #[default]
+    Ignore,
+    /// Answer `308 Permanent Redirect` to the path without it
+    Redirect,
+}
+
+/// Threading model for `Server::listen`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeMode {
+    /// One work-stealing Tokio runtime shared by all connections
+    #[default]
+    MultiThread,
+    /// `workers` single-threaded runtimes, each accepting on its own
+    /// `SO_REUSEPORT` socket, so a connection never leaves the thread that accepted it
+    ThreadPerCore,
+    /// Thread-per-core with HTTP/1.1 served over io_uring (Linux, `io-uring` feature);
+    /// falls back to `ThreadPerCore` where io_uring cannot be used
+    IoUring,
+}
+
+/// PEM certificate chain and private key for `[server.tls]`
+///
+/// Both files are re-read on SIGHUP or when they change on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 fn default_address() -> String {
@@ -25,12 +140,74 @@ fn default_workers() -> usize {
     num_cpus::get()
 }
 
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+
+fn default_header_timeout() -> u64 {
+    10
+}
+
+fn default_body_timeout() -> u64 {
+    30
+}
+
+fn default_keepalive_timeout() -> u64 {
+    30
+}
+
+fn default_request_timeout() -> u64 {
+    30
+}
+
+fn default_max_body_size() -> usize {
+    10 * 1024 * 1024
+}
+
+impl ServerConfig {
+    /// Worker count with 0 resolved to the number of CPU cores
+    pub fn worker_threads(&self) -> usize {
+        match self.workers {
+            0 => num_cpus::get(),
+            workers => workers,
+        }
+    }
+
+    /// Tokio runtime for the thread that calls `Server::listen`
+    ///
+    /// In the thread-per-core modes connections are served on the server's own
+    /// worker threads, so this is a single-threaded runtime that only runs
+    /// startup, signal handling and plugins.
+    pub fn build_runtime(&self) -> std::io::Result<tokio::runtime::Runtime> {
+        match self.runtime {
+            RuntimeMode::MultiThread => tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(self.worker_threads())
+                .enable_all()
+                .build(),
+            RuntimeMode::ThreadPerCore | RuntimeMode::IoUring => {
+                tokio::runtime::Builder::new_current_thread().enable_all().build()
+            }
+        }
+    }
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             address: default_address(),
             port: default_port(),
             workers: default_workers(),
+            runtime: RuntimeMode::default(),
+            shutdown_timeout: default_shutdown_timeout(),
+            header_timeout: default_header_timeout(),
+            body_timeout: default_body_timeout(),
+            keepalive_timeout: default_keepalive_timeout(),
+            request_timeout: default_request_timeout(),
+            max_body_size: default_max_body_size(),
+            ipv6_only: false,
+            unix_socket_mode: None,
+            tls: None,
+            paths: PathConfig::default(),
         }
     }
 }
@@ -90,7 +267,16 @@ impl Config {
     
     /// Get server bind address
     pub fn bind_address(&self) -> String {
-        format!("{}:{}", self.server.address, self.server.port)
+        let address = &self.server.address;
+        if address.starts_with("unix:") {
+            // Socket path, no port
+            address.clone()
+        } else if address.contains(':') && !address.starts_with('[') {
+            // Bare IPv6 address
+            format!("[{}]:{}", address, self.server.port)
+        } else {
+            format!("{}:{}", address, self.server.port)
+        }
     }
 }
 
@@ -0,0 +1,225 @@
+//! Route param constraints: `:id<u64>`, `:uuid<uuid>`, `:slug<[a-z0-9-]+>`
+
+use std::collections::HashSet;
+use std::fmt;
+
+use regex::Regex;
+use regex_automata::dfa::{dense, Automaton, StartKind};
+use regex_automata::{Anchored, Input, MatchKind};
+
+const UUID_PATTERN: &str = "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+
+/// Pairs of DFA states explored before two constraints are assumed to overlap
+const OVERLAP_SEARCH_LIMIT: usize = 100_000;
+
+/// Constraint a `:param` value must satisfy for its route to match
+///
+/// Written between `<` and `>` after the param name. An integer type name
+/// (`u8` … `u128`, `usize`, `i8` … `i128`, `isize`) requires the value to
+/// parse as that type, `uuid` requires a hyphenated UUID, and anything else
+/// is a regex the whole value must match. A constraint cannot contain `/`.
+#[derive(Debug, Clone)]
+pub struct ParamConstraint {
+    source: String,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Integer { signed: bool, bits: u32 },
+    Pattern(Regex),
+}
+
+impl ParamConstraint {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        if source.is_empty() {
+            return Err("empty constraint".to_string());
+        }
+        if source.contains('/') {
+            return Err(format!("constraint '{source}' cannot contain '/'"));
+        }
+
+        let integer = |signed, bits| Kind::Integer { signed, bits };
+        let kind = match source {
+            "u8" => integer(false, 8),
+            "u16" => integer(false, 16),
+            "u32" => integer(false, 32),
+            "u64" => integer(false, 64),
+            "u128" => integer(false, 128),
+            "usize" => integer(false, usize::BITS),
+            "i8" => integer(true, 8),
+            "i16" => integer(true, 16),
+            "i32" => integer(true, 32),
+            "i64" => integer(true, 64),
+            "i128" => integer(true, 128),
+            "isize" => integer(true, isize::BITS),
+            "uuid" => Kind::Pattern(anchored(UUID_PATTERN).expect("UUID pattern is valid")),
+            pattern => Kind::Pattern(
+                anchored(pattern).map_err(|err| format!("invalid constraint regex '{pattern}': {err}"))?,
+            ),
+        };
+
+        Ok(ParamConstraint {
+            source: source.to_string(),
+            kind,
+        })
+    }
+
+    /// Whether a decoded param value satisfies the constraint
+    pub fn accepts(&self, value: &str) -> bool {
+        match &self.kind {
+            Kind::Integer { signed: false, bits } => value
+                .parse::<u128>()
+                .is_ok_and(|n| *bits == 128 || n >> bits == 0),
+            Kind::Integer { signed: true, bits } => value.parse::<i128>().is_ok_and(|n| {
+                *bits == 128 || (-(1i128 << (bits - 1))..1i128 << (bits - 1)).contains(&n)
+            }),
+            Kind::Pattern(regex) => regex.is_match(value),
+        }
+    }
+
+    /// Regex matching a superset of the accepted values
+    ///
+    /// Exact for patterns. Integers ignore their range, which leading zeros
+    /// make unbounded in length anyway (`"0007"` is a valid `u8`).
+    fn pattern(&self) -> &str {
+        match &self.kind {
+            Kind::Integer { signed: false, .. } => r"^\+?[0-9]+$",
+            Kind::Integer { signed: true, .. } => r"^[+-]?[0-9]+$",
+            Kind::Pattern(regex) => regex.as_str(),
+        }
+    }
+}
+
+impl PartialEq for ParamConstraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Display for ParamConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn anchored(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{pattern})$"))
+}
+
+/// Split a param spec (after its `:`) into name and constraint source
+///
+/// `id<u64>` gives `("id", Some("u64"))`. A spec without a closing `>` is
+/// all name, which then fails name validation.
+pub(crate) fn split_param(spec: &str) -> (&str, Option<&str>) {
+    match spec.find('<') {
+        Some(open) if spec.ends_with('>') => (&spec[..open], Some(&spec[open + 1..spec.len() - 1])),
+        _ => (spec, None),
+    }
+}
+
+/// Whether some value satisfies both constraints (no constraint accepts everything)
+///
+/// Walks the product of both constraints' DFAs looking for a string they
+/// both match. When a DFA cannot be built or the search gets too large,
+/// the constraints are assumed to overlap.
+pub(crate) fn may_overlap(left: Option<&ParamConstraint>, right: Option<&ParamConstraint>) -> bool {
+    let (Some(left), Some(right)) = (left, right) else {
+        return true;
+    };
+    if left == right {
+        return true;
+    }
+    let (Some(left), Some(right)) = (build_dfa(left.pattern()), build_dfa(right.pattern())) else {
+        return true;
+    };
+    let input = Input::new("").anchored(Anchored::Yes);
+    let (Ok(left_start), Ok(right_start)) = (left.start_state_forward(&input), right.start_state_forward(&input)) else {
+        return true;
+    };
+
+    let mut seen = HashSet::new();
+    let mut pending = vec![(left_start, right_start)];
+    while let Some((l, r)) = pending.pop() {
+        if !seen.insert((l, r)) {
+            continue;
+        }
+        if seen.len() > OVERLAP_SEARCH_LIMIT {
+            return true;
+        }
+        if left.is_match_state(left.next_eoi_state(l)) && right.is_match_state(right.next_eoi_state(r)) {
+            return true;
+        }
+        for byte in 0..=u8::MAX {
+            let (next_l, next_r) = (left.next_state(l, byte), right.next_state(r, byte));
+            if left.is_quit_state(next_l) || right.is_quit_state(next_r) {
+                return true;
+            }
+            if !left.is_dead_state(next_l) && !right.is_dead_state(next_r) {
+                pending.push((next_l, next_r));
+            }
+        }
+    }
+    false
+}
+
+fn build_dfa(pattern: &str) -> Option<dense::DFA<Vec<u32>>> {
+    dense::Builder::new()
+        .configure(
+            dense::Config::new()
+                .start_kind(StartKind::Anchored)
+                .match_kind(MatchKind::All),
+        )
+        .build(pattern)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraint(source: &str) -> ParamConstraint {
+        ParamConstraint::parse(source).unwrap()
+    }
+
+    #[test]
+    fn integer_constraints_check_the_type_range() {
+        assert!(constraint("u8").accepts("255"));
+        assert!(!constraint("u8").accepts("256"));
+        assert!(!constraint("u64").accepts("-1"));
+        assert!(constraint("i8").accepts("-128"));
+        assert!(!constraint("i8").accepts("128"));
+        assert!(!constraint("i64").accepts("12a"));
+    }
+
+    #[test]
+    fn uuid_and_regex_constraints_match_the_whole_value() {
+        assert!(constraint("uuid").accepts("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!constraint("uuid").accepts("67e55044"));
+        assert!(constraint("[a-z0-9-]+").accepts("hello-world"));
+        assert!(!constraint("[a-z0-9-]+").accepts("Hello"));
+        assert!(!constraint("a|b").accepts("ab"));
+    }
+
+    #[test]
+    fn rejects_invalid_constraints() {
+        assert!(ParamConstraint::parse("").is_err());
+        assert!(ParamConstraint::parse("[a-z").is_err());
+        assert!(ParamConstraint::parse("a/b").is_err());
+        assert_eq!(split_param("id<u64>"), ("id", Some("u64")));
+        assert_eq!(split_param("id<u64"), ("id<u64", None));
+    }
+
+    #[test]
+    fn overlap_is_decided_by_shared_values() {
+        let overlap = |a: &str, b: &str| may_overlap(Some(&constraint(a)), Some(&constraint(b)));
+
+        assert!(may_overlap(None, Some(&constraint("u64"))));
+        assert!(overlap("u64", "i32"));
+        assert!(overlap("u64", "[0-9]{3}"));
+        assert!(overlap("[a-z]+", "[a-f0-9]+"));
+        assert!(!overlap("u64", "uuid"));
+        assert!(!overlap("u64", "[a-z][a-z0-9-]*"));
+        assert!(!overlap("[a-z]+", "[A-Z]+"));
+    }
+}
@@ -97,32 +97,41 @@ impl From<serde_json::Error> for Error {
 
 impl Error {
     /// Convierte el error a una respuesta HTTP (consume el error)
+    ///
+    /// The response keeps the error, so a `Server::error_handler` can render it instead.
     pub fn into_response(self) -> crate::Response {
+        let mut response = self.default_response();
+        response.error = Some(Box::new(self));
+        response
+    }
+
+    /// Status and JSON body for this error, without consuming it
+    fn default_response(&self) -> crate::Response {
         use crate::{Response, StatusCode};
         
         let (status, message) = match self {
-            Error::ParseError(msg) => (StatusCode::BadRequest, msg),
+            Error::ParseError(msg) => (StatusCode::BadRequest, msg.clone()),
             Error::IoError(err) => (StatusCode::InternalServerError, err.to_string()),
             Error::JsonError(err) => (StatusCode::BadRequest, err.to_string()),
-            Error::ValidationError(msg) => (StatusCode::BadRequest, msg),
-            Error::NotFound(msg) => (StatusCode::NotFound, msg),
-            Error::Unauthorized(msg) => (StatusCode::Unauthorized, msg),
-            Error::Forbidden(msg) => (StatusCode::Forbidden, msg),
-            Error::BadRequest(msg) => (StatusCode::BadRequest, msg),
-            Error::Internal(msg) => (StatusCode::InternalServerError, msg),
-            Error::Custom(msg) => (StatusCode::InternalServerError, msg),
-            Error::CustomWithCode(code, msg) => (StatusCode::Custom(code, "Custom".into()), msg),
-            Error::Conflict(msg) => (StatusCode::Custom(409, "Conflict".into()), msg),
-            Error::Gone(msg) => (StatusCode::Custom(410, "Gone".into()), msg),
-            Error::PayloadTooLarge(msg) => (StatusCode::Custom(413, "Payload Too Large".into()), msg),
-            Error::UriTooLong(msg) => (StatusCode::Custom(414, "URI Too Long".into()), msg),
-            Error::TooManyRequests(msg) => (StatusCode::Custom(429, "Too Many Requests".into()), msg),
-            Error::ServiceUnavailable(msg) => (StatusCode::Custom(503, "Service Unavailable".into()), msg),
-            Error::GatewayTimeout(msg) => (StatusCode::Custom(504, "Gateway Timeout".into()), msg),
-            Error::MethodNotAllowed(msg) => (StatusCode::Custom(405, "Method Not Allowed".into()), msg),
-            Error::NotAcceptable(msg) => (StatusCode::Custom(406, "Not Acceptable".into()), msg),
-            Error::RequestTimeout(msg) => (StatusCode::Custom(408, "Request Timeout".into()), msg),
-            Error::UnprocessableEntity(msg) => (StatusCode::Custom(422, "Unprocessable Entity".into()), msg),
+            Error::ValidationError(msg) => (StatusCode::BadRequest, msg.clone()),
+            Error::NotFound(msg) => (StatusCode::NotFound, msg.clone()),
+            Error::Unauthorized(msg) => (StatusCode::Unauthorized, msg.clone()),
+            Error::Forbidden(msg) => (StatusCode::Forbidden, msg.clone()),
+            Error::BadRequest(msg) => (StatusCode::BadRequest, msg.clone()),
+            Error::Internal(msg) => (StatusCode::InternalServerError, msg.clone()),
+            Error::Custom(msg) => (StatusCode::InternalServerError, msg.clone()),
+            Error::CustomWithCode(code, msg) => (StatusCode::from_u16(*code), msg.clone()),
+            Error::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            Error::Gone(msg) => (StatusCode::GONE, msg.clone()),
+            Error::PayloadTooLarge(msg) => (StatusCode::CONTENT_TOO_LARGE, msg.clone()),
+            Error::UriTooLong(msg) => (StatusCode::URI_TOO_LONG, msg.clone()),
+            Error::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
+            Error::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
+            Error::GatewayTimeout(msg) => (StatusCode::GATEWAY_TIMEOUT, msg.clone()),
+            Error::MethodNotAllowed(msg) => (StatusCode::METHOD_NOT_ALLOWED, msg.clone()),
+            Error::NotAcceptable(msg) => (StatusCode::NOT_ACCEPTABLE, msg.clone()),
+            Error::RequestTimeout(msg) => (StatusCode::REQUEST_TIMEOUT, msg.clone()),
+            Error::UnprocessableEntity(msg) => (StatusCode::UNPROCESSABLE_CONTENT, msg.clone()),
         };
         
         let body = serde_json::json!({
@@ -40,7 +40,22 @@ where
     T: serde::de::DeserializeOwned + Send,
 {
     async fn from_request(req: &mut Request, _res: &mut Response) -> Result<Self> {
-        let body = std::str::from_utf8(&req.body)
+        Self::from_slice(&req.body)
+    }
+}
+
+impl<T> Json<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    /// Read a streamed body (at most `limit` bytes) and parse it as JSON
+    pub async fn from_stream(body: crate::BodyStream, limit: usize) -> Result<Self> {
+        let bytes = body.collect(limit).await?;
+        Self::from_slice(&bytes)
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let body = std::str::from_utf8(bytes)
             .map_err(|_| Error::BadRequest("Invalid UTF-8 in body".into()))?;
         
         let value = serde_json::from_str(body)
@@ -73,6 +88,9 @@ where
 }
 
 /// Extract query parameters
+///
+/// Repeated keys (`?tag=a&tag=b`) and `tag[]=a` fill a `Vec`, `price[min]=1`
+/// fills a nested struct, and an empty value reads as `None`.
 pub struct Query<T>(pub T);
 
 #[async_trait::async_trait]
@@ -81,13 +99,8 @@ where
     T: serde::de::DeserializeOwned + Send,
 {
     async fn from_request(req: &mut Request, _res: &mut Response) -> Result<Self> {
-        let query = req.uri.query.as_ref()
-            .ok_or_else(|| Error::BadRequest("Missing query parameters".into()))?;
-        
-        let value = serde_json::from_value(serde_json::json!(query))
-            .map_err(|e| Error::BadRequest(format!("Failed to parse query: {}", e)))?;
-        
-        Ok(Query(value))
+        let raw = req.uri.raw_query.as_deref().unwrap_or("");
+        Ok(Query(crate::query::from_query_str(raw)?))
     }
 }
 
@@ -0,0 +1,228 @@
+//! Header map shared by `Request` and `Response`
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Ordered, multi-valued header map with case-insensitive names
+///
+/// Names keep the case they were set with, so they go out on the wire as
+/// written, but `get("content-type")` finds a `Content-Type` header. Each
+/// value is its own entry: two `Set-Cookie` headers stay two header lines.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { entries: Vec::with_capacity(capacity) }
+    }
+
+    /// Number of header values (a repeated header counts once per value)
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// First value of `name`
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Every value of `name`, in the order they were added
+    pub fn get_all<'a, 'n>(&'a self, name: &'n str) -> GetAll<'a, 'n> {
+        GetAll { entries: self.entries.iter(), name }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.entries.iter().any(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+
+    /// Set `name` to a single value, replacing every value it had
+    ///
+    /// The header keeps its place in the order; the previous first value is returned.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        let Some(first) = self.position(&name) else {
+            self.entries.push((name, value));
+            return None;
+        };
+
+        // Only values after `first` are dropped, so it still points at the same entry
+        let mut index = 0;
+        self.entries.retain(|(key, _)| {
+            index += 1;
+            index <= first + 1 || !key.eq_ignore_ascii_case(&name)
+        });
+        Some(std::mem::replace(&mut self.entries[first], (name, value)).1)
+    }
+
+    /// Add a value to `name`, keeping the values it already has
+    pub fn append(&mut self, name: String, value: String) {
+        self.entries.push((name, value));
+    }
+
+    /// Remove every value of `name`, returning the first one
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let first = self.position(name)?;
+        let previous = self.entries.remove(first).1;
+        self.entries.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        Some(previous)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Every `(name, value)` pair, one per value, in order
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Debug for HeaderMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the `(name, value)` pairs of a `HeaderMap`
+pub struct Iter<'a>(std::slice::Iter<'a, (String, String)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Iterator over the values of one header, see `HeaderMap::get_all`
+pub struct GetAll<'a, 'n> {
+    entries: std::slice::Iter<'a, (String, String)>,
+    name: &'n str,
+}
+
+impl<'a> Iterator for GetAll<'a, '_> {
+    type Item = &'a String;
+
+    fn next(&mut self) -> Option<&'a String> {
+        let name = self.name;
+        self.entries
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a String, &'a String);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for HeaderMap {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.append(name.into(), value.into());
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.extend(iter);
+        headers
+    }
+}
+
+impl<S> From<HashMap<String, String, S>> for HeaderMap {
+    fn from(map: HashMap<String, String, S>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl<S> From<HashMap<String, Vec<String>, S>> for HeaderMap {
+    fn from(map: HashMap<String, Vec<String>, S>) -> Self {
+        map.into_iter()
+            .flat_map(|(name, values)| values.into_iter().map(move |value| (name.clone(), value)))
+            .collect()
+    }
+}
+
+impl From<ahash::AHashMap<String, Vec<String>>> for HeaderMap {
+    fn from(map: ahash::AHashMap<String, Vec<String>>) -> Self {
+        map.into_iter()
+            .flat_map(|(name, values)| values.into_iter().map(move |value| (name.clone(), value)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_case_insensitive_and_keep_their_case() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type".into(), "text/plain".into());
+        headers.insert("content-type".into(), "application/json".into());
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("CONTENT-TYPE").unwrap(), "application/json");
+        assert!(headers.contains_key("Content-Type"));
+        assert_eq!(headers.iter().next().unwrap().0, "content-type");
+    }
+
+    #[test]
+    fn values_stay_separate_and_ordered() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Length".into(), "0".into());
+        headers.append("Set-Cookie".into(), "a=1".into());
+        headers.append("Vary".into(), "Origin".into());
+        headers.append("set-cookie".into(), "b=2".into());
+
+        assert_eq!(headers.get("Set-Cookie").unwrap(), "a=1");
+        assert_eq!(headers.get_all("SET-COOKIE").collect::<Vec<_>>(), ["a=1", "b=2"]);
+        let names: Vec<_> = headers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Content-Length", "Set-Cookie", "Vary", "set-cookie"]);
+
+        // insert collapses to one value in the first one's place
+        assert_eq!(headers.insert("Set-Cookie".into(), "c=3".into()).as_deref(), Some("a=1"));
+        let pairs: Vec<_> = headers.iter().map(|(name, value)| format!("{name}: {value}")).collect();
+        assert_eq!(pairs, ["Content-Length: 0", "Set-Cookie: c=3", "Vary: Origin"]);
+
+        assert_eq!(headers.remove("vary").as_deref(), Some("Origin"));
+        assert_eq!(headers.remove("vary"), None);
+        assert_eq!(headers.len(), 2);
+    }
+}
@@ -0,0 +1,187 @@
+//! Host patterns for virtual hosts: `api.example.com`, `:tenant.example.com`, `*.example.com`
+
+use std::borrow::Cow;
+
+use ahash::AHashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Label {
+    Static(String),
+    /// `:name`, one label
+    Param(String),
+    /// `*` or `*name`, one or more leading labels
+    Wildcard(Option<String>),
+}
+
+/// Pattern a request's `Host` header is matched against
+///
+/// Labels are matched case-insensitively and the port is ignored. A `:name`
+/// label captures one label into the `name` param. A leading `*` matches one
+/// or more labels; written `*name`, it captures them into the `name` param.
+#[derive(Debug, Clone)]
+pub(crate) struct HostPattern {
+    pub(crate) source: String,
+    labels: Vec<Label>,
+    /// Number of static labels; more wins
+    specificity: usize,
+}
+
+impl HostPattern {
+    pub(crate) fn parse(pattern: &str) -> Self {
+        Self::try_parse(pattern).unwrap_or_else(|err| panic!("invalid host '{pattern}': {err}"))
+    }
+
+    pub(crate) fn try_parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.strip_suffix('.').unwrap_or(pattern);
+        if pattern.is_empty() {
+            return Err("empty host".to_string());
+        }
+        let valid_name = |name: &str| {
+            name.chars().next().is_none_or(|c| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        let mut labels = Vec::new();
+        for (i, label) in pattern.split('.').enumerate() {
+            labels.push(if let Some(name) = label.strip_prefix('*') {
+                if i != 0 {
+                    return Err("'*' can only be the first label".to_string());
+                }
+                if !valid_name(name) {
+                    return Err(format!("invalid wildcard name '{name}'"));
+                }
+                Label::Wildcard((!name.is_empty()).then(|| name.to_string()))
+            } else if let Some(name) = label.strip_prefix(':') {
+                if name.is_empty() || !valid_name(name) {
+                    return Err(format!("invalid param name '{name}'"));
+                }
+                Label::Param(name.to_string())
+            } else if label.is_empty() {
+                return Err("empty label".to_string());
+            } else if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("invalid label '{label}'; hosts are matched without a port"));
+            } else {
+                Label::Static(label.to_ascii_lowercase())
+            });
+        }
+        if labels.iter().all(|label| !matches!(label, Label::Static(_))) {
+            return Err("at least one label must be static".to_string());
+        }
+
+        let specificity = labels.iter().filter(|label| matches!(label, Label::Static(_))).count();
+        let source = labels
+            .iter()
+            .map(|label| match label {
+                Label::Static(text) => text.clone(),
+                Label::Param(name) => format!(":{name}"),
+                Label::Wildcard(name) => format!("*{}", name.as_deref().unwrap_or("")),
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        Ok(HostPattern {
+            source,
+            labels,
+            specificity,
+        })
+    }
+
+    /// Captured params when `host` (a `Host` header value) matches
+    pub(crate) fn matches(&self, host: &str) -> Option<AHashMap<String, String>> {
+        let host = normalize_host(host)?;
+        let host_labels: Vec<&str> = host.split('.').collect();
+        let (wildcard, labels) = match self.labels.split_first() {
+            Some((Label::Wildcard(name), rest)) => (Some(name), rest),
+            _ => (None, &self.labels[..]),
+        };
+        let skipped = match wildcard {
+            Some(_) if host_labels.len() > labels.len() => host_labels.len() - labels.len(),
+            None if host_labels.len() == labels.len() => 0,
+            _ => return None,
+        };
+
+        let mut params = AHashMap::new();
+        for (label, value) in labels.iter().zip(&host_labels[skipped..]) {
+            match label {
+                Label::Static(text) if text == value => {}
+                Label::Param(name) if !value.is_empty() => {
+                    params.insert(name.clone(), value.to_string());
+                }
+                _ => return None,
+            }
+        }
+        if let Some(Some(name)) = wildcard {
+            params.insert(name.clone(), host_labels[..skipped].join("."));
+        }
+        Some(params)
+    }
+
+    /// Priority order: more static labels first, then `:param` before `*`
+    pub(crate) fn priority(&self) -> (std::cmp::Reverse<usize>, bool) {
+        let wildcard = matches!(self.labels.first(), Some(Label::Wildcard(_)));
+        (std::cmp::Reverse(self.specificity), wildcard)
+    }
+}
+
+/// Lowercase `host` and drop its port and trailing dot; `None` for an empty label
+fn normalize_host(host: &str) -> Option<Cow<'_, str>> {
+    let host = host.trim();
+    let host = match host.rfind(':') {
+        // An IPv6 literal keeps its colons inside the brackets
+        Some(colon) if !host[colon..].contains(']') => &host[..colon],
+        _ => host,
+    };
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if host.is_empty() || host.split('.').any(str::is_empty) {
+        return None;
+    }
+    Some(if host.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(host.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(host)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(pattern: &str, host: &str) -> Option<Vec<(String, String)>> {
+        HostPattern::parse(pattern).matches(host).map(|params| {
+            let mut params: Vec<_> = params.into_iter().collect();
+            params.sort();
+            params
+        })
+    }
+
+    #[test]
+    fn matches_exact_hosts_ignoring_case_and_port() {
+        assert_eq!(captures("api.example.com", "api.example.com"), Some(vec![]));
+        assert_eq!(captures("api.example.com", "API.Example.com:8080"), Some(vec![]));
+        assert_eq!(captures("api.example.com", "api.example.com."), Some(vec![]));
+        assert_eq!(captures("api.example.com", "admin.example.com"), None);
+        assert_eq!(captures("api.example.com", "x.api.example.com"), None);
+        assert_eq!(captures("localhost", "[::1]:8080"), None);
+    }
+
+    #[test]
+    fn captures_params_and_wildcards() {
+        let tenant = |value: &str| Some(vec![("tenant".to_string(), value.to_string())]);
+        assert_eq!(captures(":tenant.example.com", "acme.example.com"), tenant("acme"));
+        assert_eq!(captures(":tenant.example.com", "a.b.example.com"), None);
+        assert_eq!(captures("*tenant.example.com", "a.b.example.com"), tenant("a.b"));
+        assert_eq!(captures("*.example.com", "a.b.example.com"), Some(vec![]));
+        assert_eq!(captures("*.example.com", "example.com"), None);
+        assert_eq!(captures("*.example.com", ".example.com"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(HostPattern::try_parse("").is_err());
+        assert!(HostPattern::try_parse("api..example.com").is_err());
+        assert!(HostPattern::try_parse("api.*.example.com").is_err());
+        assert!(HostPattern::try_parse("localhost:8080").is_err());
+        assert!(HostPattern::try_parse("*.:tenant").is_err());
+        assert!(HostPattern::try_parse(":1st.example.com").is_err());
+        assert_eq!(HostPattern::parse("API.Example.com.").source, "api.example.com");
+        assert_eq!(HostPattern::parse(":tenantId.Example.com").source, ":tenantId.example.com");
+    }
+}
@@ -1,42 +1,353 @@
-/// io_uring optimized server implementation
-/// Requires Linux kernel 5.19+ for best performance
-///
-/// This provides 30-50% better performance than epoll-based tokio
-/// by using kernel-level async I/O primitives
+//! io_uring backend for thread-per-core workers (`runtime = "io_uring"`)
+//!
+//! Each worker runs a `tokio-uring` runtime. Connections are accepted through
+//! tokio as usual, so `on_stream_accept` hooks and HTTP/2 detection behave the
+//! same as on epoll; HTTP/1.1 connections then move to io_uring, reading into
+//! buffers registered with the kernel once per worker.
+//!
+//! Requires Linux 5.11+ and the `io-uring` feature. Anything else falls back to
+//! the epoll thread-per-core workers.
 
-#[cfg(feature = "io-uring")]
-use tokio_uring::net::TcpListener;
+use crate::server::Listener;
 
-#[cfg(feature = "io-uring")]
-pub async fn run_io_uring_server(addr: &str) -> std::io::Result<()> {
-    println!("[IO_URING] Starting server with io_uring support on {}", addr);
-    println!("[IO_URING] This provides ~30-50% better performance on Linux 5.19+");
-
-    // TODO: Implement full io_uring server
-    // This would replace the epoll-based tokio server in server.rs
-    // Key optimizations:
-    // - Zero-copy I/O with registered buffers
-    // - Batch syscalls for better throughput
-    // - Direct descriptor passing
-
-    Ok(())
-}
+/// Oldest kernel with every io_uring operation tokio-uring relies on
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+const MIN_KERNEL: (u32, u32) = (5, 11);
 
-// Feature flag documentation
+/// Whether this build can serve on io_uring and the running kernel allows it
 #[cfg(not(feature = "io-uring"))]
 pub fn io_uring_available() -> bool {
     false
 }
 
+/// Whether this build can serve on io_uring and the running kernel allows it
 #[cfg(feature = "io-uring")]
 pub fn io_uring_available() -> bool {
-    // Check kernel version
     #[cfg(target_os = "linux")]
     {
-        // Simple check - in production you'd verify kernel >= 5.19
-        std::path::Path::new("/sys/kernel/debug/io_uring").exists()
+        // Containers often block io_uring_setup even on new kernels, so also try a ring
+        kernel_version().is_some_and(|version| version >= MIN_KERNEL)
+            && tokio_uring::uring_builder().build(2).is_ok()
     }
 
     #[cfg(not(target_os = "linux"))]
     false
 }
+
+/// Whether `runtime = "io_uring"` can be honored for these listeners; prints why not
+pub(crate) fn can_serve(listeners: &[(Listener, String)], tls: bool) -> bool {
+    let reason = if !io_uring_available() {
+        "io_uring is unavailable (needs the `io-uring` feature and Linux 5.11+)"
+    } else if tls {
+        "TLS is not supported on io_uring"
+    } else if listeners.iter().any(|(listener, _)| !matches!(listener, Listener::Tcp(_))) {
+        "only TCP listeners are supported on io_uring"
+    } else {
+        return true;
+    };
+    println!("[IO_URING] {}, falling back to epoll workers", reason);
+    false
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+fn kernel_version() -> Option<(u32, u32)> {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) };
+    parse_kernel_version(release.to_str().ok()?)
+}
+
+/// `major.minor` of a release string like `6.8.0-45-generic`
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+fn parse_kernel_version(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(feature = "io-uring")]
+pub(crate) use worker::run_worker;
+
+#[cfg(feature = "io-uring")]
+mod worker {
+    use crate::request::{Context, RemoteAddr};
+    use crate::AcceptedStream;
+    use crate::server::{
+        accept_stream, drive_worker, report_connection_error, serve_http1, Http1Io, Io, Shared,
+        WorkerSocket, BUFFER_SIZE,
+    };
+    use std::future::Future;
+    use std::io;
+    use std::os::fd::{AsRawFd, BorrowedFd};
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{ready, Poll};
+    use std::time::Duration;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc::UnboundedSender;
+    use tokio_uring::buf::fixed::{FixedBuf, FixedBufPool};
+
+    /// Read buffers registered with each worker's ring; reads fall back to the heap when all are busy
+    const REGISTERED_BUFFERS: usize = 512;
+
+    /// Serve `sockets` on this thread with a tokio-uring runtime until shutdown
+    pub(crate) fn run_worker(
+        sockets: Vec<WorkerSocket>,
+        shared: Shared,
+        failed: UnboundedSender<io::Error>,
+        drain_timeout: Duration,
+    ) {
+        let runtime = match tokio_uring::Runtime::new(&tokio_uring::builder()) {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                let _ = failed.send(e);
+                return;
+            }
+        };
+
+        let shutdown = shared.shutdown.clone();
+        let accepting = async move {
+            let buffers = FixedBufPool::new((0..REGISTERED_BUFFERS).map(|_| Vec::with_capacity(BUFFER_SIZE)));
+            buffers.register()?;
+            let listeners = sockets
+                .into_iter()
+                .map(|socket| match socket {
+                    WorkerSocket::Tcp(listener) => TcpListener::from_std(listener),
+                    #[cfg(unix)]
+                    WorkerSocket::Unix(_) => Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "unix listeners are not served on io_uring",
+                    )),
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+
+            futures_util::future::try_join_all(
+                listeners.iter().map(|listener| accept_loop(listener, &shared, &buffers)),
+            )
+            .await
+            .map(drop)
+        };
+        runtime.block_on(drive_worker(accepting, shutdown, failed, drain_timeout));
+    }
+
+    async fn accept_loop(listener: &TcpListener, shared: &Shared, buffers: &FixedBufPool<Vec<u8>>) -> io::Result<()> {
+        loop {
+            let (mut socket, addr) = listener.accept().await?;
+            let shared = shared.clone();
+            let buffers = buffers.clone();
+            let connection = shared.shutdown.track();
+
+            tokio_uring::spawn(async move {
+                let _connection = connection;
+                let _ = socket.set_nodelay(true);
+
+                let remote_addr = RemoteAddr::Ip(addr);
+                let Some(conn_context) = accept_stream(&shared.plugins, AcceptedStream::Tcp(&mut socket), &remote_addr).await else {
+                    return;
+                };
+                report_connection_error(serve_connection(socket, remote_addr, conn_context, shared, buffers).await);
+            });
+        }
+    }
+
+    async fn serve_connection(
+        socket: tokio::net::TcpStream,
+        remote_addr: RemoteAddr,
+        conn_context: Context,
+        shared: Shared,
+        buffers: FixedBufPool<Vec<u8>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Shared { router, middlewares, async_middlewares, ws_routes, plugins, shutdown, timeouts, .. } = shared;
+
+        // HTTP/2 stays on tokio's reactor; only HTTP/1.1 moves to the ring
+        #[cfg(feature = "http2")]
+        match crate::server::sniff_http2(&socket, &shutdown, timeouts).await? {
+            None => return Ok(()),
+            Some(true) => {
+                return crate::server::handle_http2_connection(socket, router, middlewares, async_middlewares, remote_addr, plugins, conn_context, shutdown, timeouts).await;
+            }
+            Some(false) => {}
+        }
+
+        // io_uring would fail reads on a non-blocking socket with EAGAIN instead of waiting
+        let socket = socket.into_std()?;
+        socket.set_nonblocking(false)?;
+        let socket = UringStream::new(tokio_uring::net::TcpStream::from_std(socket), buffers);
+        serve_http1(socket, router, middlewares, async_middlewares, remote_addr, ws_routes, plugins, conn_context, shutdown, timeouts).await
+    }
+
+    type Op<T> = Pin<Box<dyn Future<Output = (io::Result<T>, Buffer)>>>;
+
+    enum Buffer {
+        Fixed(FixedBuf),
+        Heap(Vec<u8>),
+    }
+
+    impl std::ops::Deref for Buffer {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            match self {
+                Buffer::Fixed(buf) => buf,
+                Buffer::Heap(buf) => buf,
+            }
+        }
+    }
+
+    /// tokio-uring socket behind `AsyncRead`/`AsyncWrite`, so `serve_http1` can drive it
+    ///
+    /// Writes are copied into an owned buffer and submitted right away; the
+    /// result is collected by the next write or flush.
+    struct UringStream {
+        socket: Rc<tokio_uring::net::TcpStream>,
+        buffers: FixedBufPool<Vec<u8>>,
+        /// Buffer from the last read, its length and how much of it was handed out
+        filled: Option<(Buffer, usize, usize)>,
+        reading: Option<Op<usize>>,
+        writing: Option<Op<()>>,
+        /// Write buffer kept for reuse between writes
+        spare: Vec<u8>,
+    }
+
+    impl UringStream {
+        fn new(socket: tokio_uring::net::TcpStream, buffers: FixedBufPool<Vec<u8>>) -> Self {
+            Self {
+                socket: Rc::new(socket),
+                buffers,
+                filled: None,
+                reading: None,
+                writing: None,
+                spare: Vec::new(),
+            }
+        }
+
+        fn start_read(&self) -> Op<usize> {
+            let socket = Rc::clone(&self.socket);
+            let buffer = self.buffers.try_next(BUFFER_SIZE);
+            Box::pin(async move {
+                match buffer {
+                    Some(buffer) => {
+                        let (result, buffer) = socket.read_fixed(buffer).await;
+                        (result, Buffer::Fixed(buffer))
+                    }
+                    None => {
+                        let (result, buffer) = socket.read(Vec::with_capacity(BUFFER_SIZE)).await;
+                        (result, Buffer::Heap(buffer))
+                    }
+                }
+            })
+        }
+
+        /// Wait for the write in flight, if any
+        fn poll_written(&mut self, cx: &mut std::task::Context<'_>) -> Poll<io::Result<()>> {
+            if let Some(writing) = &mut self.writing {
+                let (result, buffer) = ready!(writing.as_mut().poll(cx));
+                self.writing = None;
+                if let Buffer::Heap(buffer) = buffer {
+                    self.spare = buffer;
+                }
+                result?;
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncRead for UringStream {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            out: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            loop {
+                if let Some((buffer, len, pos)) = &mut this.filled {
+                    let n = out.remaining().min(*len - *pos);
+                    out.put_slice(&buffer[*pos..*pos + n]);
+                    *pos += n;
+                    if *pos == *len {
+                        // A registered buffer goes back to the pool here
+                        this.filled = None;
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+
+                let reading = match &mut this.reading {
+                    Some(reading) => reading,
+                    None => this.reading.insert(this.start_read()),
+                };
+                let (result, buffer) = ready!(reading.as_mut().poll(cx));
+                this.reading = None;
+                match result? {
+                    0 => return Poll::Ready(Ok(())),
+                    len => this.filled = Some((buffer, len, 0)),
+                }
+            }
+        }
+    }
+
+    impl AsyncWrite for UringStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            data: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            ready!(this.poll_written(cx))?;
+            if data.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+
+            let mut buffer = std::mem::take(&mut this.spare);
+            buffer.clear();
+            buffer.extend_from_slice(data);
+            let socket = Rc::clone(&this.socket);
+            this.writing = Some(Box::pin(async move {
+                let (result, buffer) = socket.write_all(buffer).await;
+                (result, Buffer::Heap(buffer))
+            }));
+            // Submit now rather than on the next flush
+            if let Poll::Ready(Err(e)) = this.poll_written(cx) {
+                return Poll::Ready(Err(e));
+            }
+            Poll::Ready(Ok(data.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_written(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_written(cx))?;
+            Poll::Ready(this.socket.shutdown(std::net::Shutdown::Write))
+        }
+    }
+
+    impl Http1Io for UringStream {
+        /// WebSockets run on tokio's reactor, over a duplicate of the socket
+        fn into_websocket(self) -> io::Result<impl Io> {
+            // SAFETY: the descriptor is owned by `self.socket`, which outlives this borrow
+            let fd = unsafe { BorrowedFd::borrow_raw(self.socket.as_raw_fd()) }.try_clone_to_owned()?;
+            let socket = std::net::TcpStream::from(fd);
+            socket.set_nonblocking(true)?;
+            tokio::net::TcpStream::from_std(socket)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "io-uring", target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kernel_release() {
+        assert_eq!(parse_kernel_version("6.8.0-45-generic"), Some((6, 8)));
+        assert_eq!(parse_kernel_version("5.11.0"), Some((5, 11)));
+        assert_eq!(parse_kernel_version("4.19.112+"), Some((4, 19)));
+        assert_eq!(parse_kernel_version("garbage"), None);
+    }
+}
@@ -6,21 +6,37 @@ use tikv_jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
+mod body;
+mod chunked;
 mod config;
+mod constraint;
 mod cookie;
 mod error;
 mod extract;
+mod headers;
+mod host;
+mod io_uring_server;
 mod macros;
+mod path;
+mod query;
 mod plugin;
 mod request;
 mod response;
 mod router;
 mod perfect_hash_router;
+mod param_tree;
 mod phf_routes;
 mod light_guard;
+#[cfg(unix)]
+mod listen_fds;
 mod serve;
 mod server;
+mod shutdown;
+mod status;
+#[cfg(feature = "tls")]
+mod tls;
 mod upload;
+mod url;
 mod validation;
 
 pub mod log;
@@ -35,17 +51,23 @@ pub mod hot_reload_state;
 #[cfg(any(test, feature = "testing"))]
 pub mod test;
 
-pub use config::{Config, ServerConfig, PluginConfig, config, init_config, get_config, load_plugin_config, load_plugin_config_as};
+pub use body::{BodyStream, Streaming};
+pub use config::{Config, DotSegments, PathConfig, RuntimeMode, ServerConfig, TlsConfig, TrailingSlash, PluginConfig, config, init_config, get_config, load_plugin_config, load_plugin_config_as};
 pub use cookie::{Cookie, SameSite};
 pub use error::{Error, Result};
+pub use headers::HeaderMap;
+pub use io_uring_server::io_uring_available;
 pub use extract::{FromRequest, PluginExtractor, Extract, IntoResponse, Json, Path, Query, Body, Header};
-pub use plugin::{Plugin, PluginRegistry, PluginError, PluginResult, PluginMetadata, register_plugin, register_plugin_async, auto_register_plugins, registry as plugin_registry, get_plugin};
-pub use request::{Method, Request, Uri, Version};
+pub use plugin::{AcceptedStream, Plugin, PluginRegistry, PluginError, PluginResult, PluginMetadata, StreamAction, register_plugin, register_plugin_async, auto_register_plugins, registry as plugin_registry, get_plugin};
+pub use request::{Context, Method, PeerCred, RemoteAddr, Request, Uri, Version};
 pub use response::{Response, ResponseBody, StatusCode};
 pub use router::Router;
+pub use perfect_hash_router::RouteMatch;
 pub use serve::{serve_file, serve_dir, serve_static};
 pub use server::Server;
+pub use shutdown::ShutdownHandle;
 pub use upload::{FormData, UploadedFile, UploadConfig};
+pub use url::{url_for, UrlError};
 pub use validation::{Validated, ValidationError, validators};
 
 pub use websocket::{WebSocket, Message as WebSocketMessage, WebSocketHandler, WebSocketRoom, is_websocket_upgrade, websocket_upgrade};
@@ -79,6 +101,11 @@ pub enum Flow {
 
 pub trait AsyncHandler: Send + Sync {
     fn call(&self, req: Request, res: Response) -> Pin<Box<dyn Future<Output = Response> + Send>>;
+
+    /// Whether the handler reads its body through `BodyStream` instead of `req.body`
+    fn streams_body(&self) -> bool {
+        false
+    }
 }
 
 impl<F, Fut> AsyncHandler for F
@@ -130,6 +157,12 @@ pub struct RouteInfo {
     pub handler: fn(Request, Response) -> Pin<Box<dyn Future<Output = Response> + Send>>,
     pub precomputed_hash: u64,
     pub is_static_path: bool,
+    /// Handler takes a `BodyStream`, so the body is not buffered before routing
+    pub streaming_body: bool,
+    /// Name for `url_for`, from `#[get("/users/:id", name = "user_show")]`
+    pub name: Option<&'static str>,
+    /// Host pattern the route is served on, from `#[scope(host = "api.example.com")]`
+    pub host: Option<&'static str>,
 }
 
 pub struct WsRouteInfo {
@@ -169,6 +202,10 @@ pub const fn const_is_static_path(path: &str) -> bool {
         if bytes[i] == b':' {
             return false;
         }
+        // `*name` is a catch-all only at the start of a segment
+        if bytes[i] == b'*' && i > 0 && bytes[i - 1] == b'/' {
+            return false;
+        }
         i += 1;
     }
     true
@@ -332,14 +369,14 @@ fn compute_source_hash(source_root: &std::path::Path) -> std::io::Result<u64> {
 
 pub mod prelude {
     pub use crate::{
-        Server, Router, Request, Response, StatusCode, Method, Version, Uri,
+        Server, ShutdownHandle, Router, Request, Response, StatusCode, Method, Version, Uri,
         Error, Result, Flow, MiddlewarePhase,
-        FromRequest, IntoResponse, Json, Path, Query, Body, Header,
+        FromRequest, IntoResponse, Json, Path, Query, Body, Header, BodyStream, Streaming,
         PluginExtractor, Extract,
-        Plugin, PluginRegistry, PluginError, PluginResult, PluginMetadata,
+        Plugin, PluginRegistry, PluginError, PluginResult, PluginMetadata, StreamAction, AcceptedStream,
         register_plugin, register_plugin_async, plugin_registry, get_plugin,
         Config, ServerConfig, PluginConfig, config, get_config,
-        serve_file, serve_dir, serve_static,
+        serve_file, serve_dir, serve_static, url_for,
         ResponseBody,
     };
     
@@ -1,5 +1,8 @@
 use std::collections::{HashMap, HashSet};
 
+use crate::constraint::{split_param, ParamConstraint};
+use crate::host::HostPattern;
+use crate::perfect_hash_router::{patterns_may_overlap, RoutePattern};
 use crate::{PluginFactory, RouteInfo, ScopeMiddleware, WsRouteInfo};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,7 +53,7 @@ pub fn enforce(
     let mut seen = HashMap::new();
     for route in routes {
         validate_route(route, &middleware_names, &mut diagnostics);
-        let key = format!("{} {}", route.method, normalize_path(route.path));
+        let key = format!("{} {}{}", route.method, route.host.unwrap_or(""), normalize_path(route.path));
         if let Some(prev) = seen.insert(key.clone(), route.path) {
             diagnostics.push(Diagnostic {
                 code: "LG002",
@@ -64,6 +67,7 @@ pub fn enforce(
         }
     }
 
+    detect_duplicate_route_names(routes, &mut diagnostics);
     detect_ambiguous_param_shapes(routes, &mut diagnostics);
     detect_param_static_overlaps(routes, &mut diagnostics);
     detect_ws_collisions(ws_routes, &mut diagnostics);
@@ -138,7 +142,7 @@ fn validate_route(
         });
     }
 
-    let parsed_static = !route.path.split('/').any(|s| s.starts_with(':'));
+    let parsed_static = !route.path.split('/').any(is_dynamic_segment);
     if route.is_static_path != parsed_static {
         diagnostics.push(Diagnostic {
             code: "LG007",
@@ -164,10 +168,22 @@ fn validate_route(
     }
 
     let mut seen_params = HashSet::new();
-    for segment in route.path.split('/') {
-        if !segment.starts_with(':') {
+    let segment_count = route.path.split('/').count();
+    for (index, segment) in route.path.split('/').enumerate() {
+        if !is_dynamic_segment(segment) {
             continue;
         }
+        if segment.starts_with('*') && index + 1 != segment_count {
+            diagnostics.push(Diagnostic {
+                code: "LG014",
+                severity: Severity::Error,
+                message: format!(
+                    "route '{}' '{}' has catch-all '{}' before its last segment",
+                    route.method, route.path, segment
+                ),
+                tip: Some("A catch-all takes the rest of the path; use it only as the last segment."),
+            });
+        }
         if segment.len() <= 1 {
             diagnostics.push(Diagnostic {
                 code: "LG009",
@@ -180,14 +196,25 @@ fn validate_route(
             });
             continue;
         }
-        let param = &segment[1..];
+        let (param, constraint) = split_param(&segment[1..]);
+        if let Some(Err(err)) = constraint.map(ParamConstraint::parse) {
+            diagnostics.push(Diagnostic {
+                code: "LG015",
+                severity: Severity::Error,
+                message: format!(
+                    "route '{}' '{}' has invalid constraint on '{}': {}",
+                    route.method, route.path, segment, err
+                ),
+                tip: Some("Use an integer type, 'uuid' or a regex without '/', like ':id<u64>'."),
+            });
+        }
         if !is_valid_param_name(param) {
             diagnostics.push(Diagnostic {
                 code: "LG010",
                 severity: Severity::Error,
                 message: format!(
-                    "route '{}' '{}' has invalid parameter name ':{}'",
-                    route.method, route.path, param
+                    "route '{}' '{}' has invalid parameter name '{}'",
+                    route.method, route.path, segment
                 ),
                 tip: Some("Parameter names must match [A-Za-z_][A-Za-z0-9_]*."),
             });
@@ -197,7 +224,7 @@ fn validate_route(
                 code: "LG011",
                 severity: Severity::Warning,
                 message: format!(
-                    "route '{}' '{}' repeats parameter ':{}'",
+                    "route '{}' '{}' repeats parameter '{}'",
                     route.method, route.path, param
                 ),
                 tip: Some("Repeated param names can shadow values; prefer unique names."),
@@ -205,7 +232,15 @@ fn validate_route(
         }
     }
 
-    if route.path.contains('{') || route.path.contains('}') {
+    let unconstrained: String = route
+        .path
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(spec) => split_param(spec).0,
+            None => segment,
+        })
+        .collect();
+    if unconstrained.contains('{') || unconstrained.contains('}') {
         diagnostics.push(Diagnostic {
             code: "LG012",
             severity: Severity::Error,
@@ -229,6 +264,23 @@ fn validate_route(
             tip: Some("If this is intentional ignore this warning; otherwise add scope middleware."),
         });
     }
+
+    if let Some(Err(err)) = route.host.map(HostPattern::try_parse) {
+        diagnostics.push(Diagnostic {
+            code: "LG017",
+            severity: Severity::Error,
+            message: format!(
+                "route '{}' '{}' has invalid host pattern '{}': {}",
+                route.method, route.path, route.host.unwrap_or(""), err
+            ),
+            tip: Some("Use 'api.example.com', ':tenant.example.com' or '*.example.com', without a port."),
+        });
+    }
+}
+
+/// `:param` or `*catch_all`
+fn is_dynamic_segment(segment: &str) -> bool {
+    segment.starts_with(':') || segment.starts_with('*')
 }
 
 fn is_valid_param_name(name: &str) -> bool {
@@ -315,44 +367,77 @@ fn emit_all_as_warnings(diagnostics: &[Diagnostic]) {
     }
 }
 
-fn detect_ambiguous_param_shapes(routes: &[RouteInfo], diagnostics: &mut Vec<Diagnostic>) {
-    let mut by_method_shape: HashMap<(String, String), String> = HashMap::new();
+/// A name may be shared by methods of one path, since `url_for` only needs the path
+fn detect_duplicate_route_names(routes: &[RouteInfo], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: HashMap<&str, &str> = HashMap::new();
     for route in routes {
-        if route.is_static_path {
+        let Some(name) = route.name else {
             continue;
-        }
-        let key = (route.method.to_string(), route_shape(route.path));
-        if let Some(existing) = by_method_shape.get(&key) {
-            if existing != route.path {
+        };
+        match seen.get(name) {
+            Some(prev) if normalize_path(prev) != normalize_path(route.path) => {
                 diagnostics.push(Diagnostic {
-                    code: "LG101",
-                    severity: Severity::Warning,
+                    code: "LG016",
+                    severity: Severity::Error,
                     message: format!(
-                        "ambiguous param route shape for method '{}': '{}' vs '{}'",
-                        route.method, existing, route.path
+                        "route name '{}' is used by both '{}' and '{}'",
+                        name, prev, route.path
                     ),
-                    tip: Some("Prefer a single canonical param shape per endpoint."),
+                    tip: Some("Give each path its own name so url_for is unambiguous."),
                 });
             }
-        } else {
-            by_method_shape.insert(key, route.path.to_string());
+            Some(_) => {}
+            None => {
+                seen.insert(name, route.path);
+            }
         }
     }
 }
 
+fn detect_ambiguous_param_shapes(routes: &[RouteInfo], diagnostics: &mut Vec<Diagnostic>) {
+    let mut by_method_shape: HashMap<(&str, Option<&str>, String), Vec<RoutePattern>> = HashMap::new();
+    for route in routes {
+        if route.is_static_path {
+            continue;
+        }
+        // Invalid constraints are reported by LG015
+        let Ok(pattern) = RoutePattern::try_parse(route.path) else {
+            continue;
+        };
+        let key = (route.method, route.host, route_shape(route.path));
+        let same_shape = by_method_shape.entry(key).or_default();
+        if let Some(existing) = same_shape
+            .iter()
+            .find(|existing| existing.path != pattern.path && patterns_may_overlap(existing, &pattern))
+        {
+            diagnostics.push(Diagnostic {
+                code: "LG101",
+                severity: Severity::Warning,
+                message: format!(
+                    "ambiguous param route shape for method '{}': '{}' vs '{}'",
+                    route.method, existing.path, route.path
+                ),
+                tip: Some("Prefer a single canonical param shape per endpoint, or constrain params so they cannot overlap (':id<u64>')."),
+            });
+        }
+        same_shape.push(pattern);
+    }
+}
+
 fn detect_param_static_overlaps(routes: &[RouteInfo], diagnostics: &mut Vec<Diagnostic>) {
-    let mut static_by_method: HashMap<&str, Vec<&str>> = HashMap::new();
-    let mut param_by_method: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut static_by_method: HashMap<(&str, Option<&str>), Vec<&str>> = HashMap::new();
+    let mut param_by_method: HashMap<(&str, Option<&str>), Vec<&str>> = HashMap::new();
     for route in routes {
+        let key = (route.method, route.host);
         if route.is_static_path {
-            static_by_method.entry(route.method).or_default().push(route.path);
+            static_by_method.entry(key).or_default().push(route.path);
         } else {
-            param_by_method.entry(route.method).or_default().push(route.path);
+            param_by_method.entry(key).or_default().push(route.path);
         }
     }
 
-    for (method, static_paths) in &static_by_method {
-        let Some(param_paths) = param_by_method.get(method) else {
+    for (key @ (method, _), static_paths) in &static_by_method {
+        let Some(param_paths) = param_by_method.get(key) else {
             continue;
         };
         for static_path in static_paths {
@@ -454,20 +539,7 @@ fn detect_plugin_factory_issues(
 }
 
 fn path_matches_param_shape(static_path: &str, param_path: &str) -> bool {
-    let s_parts: Vec<&str> = static_path.split('/').filter(|s| !s.is_empty()).collect();
-    let p_parts: Vec<&str> = param_path.split('/').filter(|s| !s.is_empty()).collect();
-    if s_parts.len() != p_parts.len() {
-        return false;
-    }
-    for (s, p) in s_parts.iter().zip(p_parts.iter()) {
-        if p.starts_with(':') {
-            continue;
-        }
-        if s != p {
-            return false;
-        }
-    }
-    true
+    RoutePattern::try_parse(param_path).is_ok_and(|pattern| pattern.matches(static_path).is_some())
 }
 
 fn route_shape(path: &str) -> String {
@@ -479,6 +551,8 @@ fn route_shape(path: &str) -> String {
         out.push('/');
         if segment.starts_with(':') {
             out.push(':');
+        } else if segment.starts_with('*') {
+            out.push('*');
         } else {
             out.push_str(segment);
         }
@@ -526,6 +600,7 @@ mod tests {
     #[test]
     fn test_route_shape_normalization() {
         assert_eq!(route_shape("/users/:id"), "/users/:");
+        assert_eq!(route_shape("/static/*path"), "/static/*");
         assert_eq!(route_shape("/"), "/");
     }
 
@@ -533,6 +608,100 @@ mod tests {
     fn test_param_shape_match() {
         assert!(path_matches_param_shape("/users/42", "/users/:id"));
         assert!(!path_matches_param_shape("/users/42/posts", "/users/:id"));
+        assert!(path_matches_param_shape("/static/css/app.css", "/static/*path"));
+        assert!(!path_matches_param_shape("/static", "/static/*path"));
+    }
+
+    fn route(path: &'static str) -> RouteInfo {
+        RouteInfo {
+            method: "GET",
+            path,
+            handler: |_, res| Box::pin(async move { res }),
+            precomputed_hash: 0,
+            is_static_path: false,
+            streaming_body: false,
+            name: None,
+            host: None,
+        }
+    }
+
+    fn validation_codes(path: &'static str) -> Vec<&'static str> {
+        let mut diagnostics = Vec::new();
+        validate_route(&route(path), &HashSet::new(), &mut diagnostics);
+        diagnostics.into_iter().map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn test_catch_all_must_be_last() {
+        assert!(validation_codes("/static/*path").is_empty());
+        assert_eq!(validation_codes("/static/*path/edit"), ["LG014"]);
+        assert_eq!(validation_codes("/static/*"), ["LG009"]);
+    }
+
+    #[test]
+    fn test_param_constraints_are_validated() {
+        assert!(validation_codes("/users/:id<u64>").is_empty());
+        assert!(validation_codes("/codes/:code<[A-Z]{3}>").is_empty());
+        assert_eq!(validation_codes("/users/:id<[0-9>"), ["LG015"]);
+        assert_eq!(validation_codes("/users/:id<u64"), ["LG010"]);
+    }
+
+    #[test]
+    fn test_only_overlapping_constraints_are_ambiguous() {
+        let ambiguous = |a, b| {
+            let mut diagnostics = Vec::new();
+            detect_ambiguous_param_shapes(&[route(a), route(b)], &mut diagnostics);
+            !diagnostics.is_empty()
+        };
+
+        assert!(ambiguous("/users/:id", "/users/:username"));
+        assert!(ambiguous("/users/:id<u64>", "/users/:username"));
+        assert!(!ambiguous("/users/:id<u64>", "/users/:username<[a-z][a-z0-9_]*>"));
+        assert!(!ambiguous("/users/:id<u64>", "/users/:uuid<uuid>"));
+        assert!(ambiguous("/files/*path", "/files/*rest"));
+
+        assert!(!path_matches_param_shape("/users/me", "/users/:id<u64>"));
+    }
+
+    #[test]
+    fn test_duplicate_route_names() {
+        let named = |method, path, name| RouteInfo {
+            method,
+            name: Some(name),
+            ..route(path)
+        };
+        let codes = |routes: &[RouteInfo]| {
+            let mut diagnostics = Vec::new();
+            detect_duplicate_route_names(routes, &mut diagnostics);
+            diagnostics.into_iter().map(|d| d.code).collect::<Vec<_>>()
+        };
+
+        assert!(codes(&[named("GET", "/users/:id", "user"), named("PUT", "/users/:id", "user")]).is_empty());
+        assert_eq!(codes(&[named("GET", "/users/:id", "user"), named("GET", "/users", "user")]), ["LG016"]);
+        assert!(codes(&[route("/a"), route("/b")]).is_empty());
+    }
+
+    #[test]
+    fn test_hosts_keep_routes_apart() {
+        let on = |host, path| RouteInfo {
+            host: Some(host),
+            ..route(path)
+        };
+        let mut diagnostics = Vec::new();
+        detect_ambiguous_param_shapes(
+            &[on("api.example.com", "/users/:id"), on("admin.example.com", "/users/:name")],
+            &mut diagnostics,
+        );
+        detect_param_static_overlaps(
+            &[on("api.example.com", "/users/:id"), RouteInfo { is_static_path: true, ..route("/users/me") }],
+            &mut diagnostics,
+        );
+        assert!(diagnostics.is_empty());
+
+        let mut diagnostics = Vec::new();
+        validate_route(&on("api.example.com:8080", "/users/:id"), &HashSet::new(), &mut diagnostics);
+        let codes: Vec<_> = diagnostics.into_iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["LG017"]);
     }
 
     #[test]
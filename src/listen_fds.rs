@@ -0,0 +1,59 @@
+//! Listening sockets handed over by systemd socket activation
+//!
+//! Follows the `sd_listen_fds` protocol: `LISTEN_PID` names the process the
+//! sockets are meant for and `LISTEN_FDS` counts them, starting at fd 3. A
+//! parent process can use the same variables to pass its listeners to a
+//! replacement for zero-downtime restarts.
+
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+
+/// First inherited descriptor (`SD_LISTEN_FDS_START`)
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Take ownership of the inherited listeners, if any were passed to this process
+///
+/// The variables are cleared so child processes do not try to adopt the same sockets.
+pub(crate) fn take() -> Vec<OwnedFd> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(name);
+    }
+
+    let count = count(pid.as_deref(), fds.as_deref(), std::process::id());
+    (LISTEN_FDS_START..LISTEN_FDS_START + count as RawFd)
+        .map(|fd| {
+            // Inherited descriptors must not leak into processes we spawn
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                OwnedFd::from_raw_fd(fd)
+            }
+        })
+        .collect()
+}
+
+/// Number of descriptors meant for `own_pid`
+fn count(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> usize {
+    let (Some(pid), Some(fds)) = (pid, fds) else {
+        return 0;
+    };
+    if pid.trim().parse::<u32>().ok() != Some(own_pid) {
+        // Set for another process (e.g. inherited from our parent's environment)
+        return 0;
+    }
+    fds.trim().parse().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_only_fds_meant_for_this_process() {
+        assert_eq!(count(Some("42"), Some("2"), 42), 2);
+        assert_eq!(count(Some("41"), Some("2"), 42), 0);
+        assert_eq!(count(None, Some("2"), 42), 0);
+        assert_eq!(count(Some("42"), None, 42), 0);
+        assert_eq!(count(Some("42"), Some("many"), 42), 0);
+    }
+}
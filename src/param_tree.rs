@@ -0,0 +1,285 @@
+//! Radix tree over the segments of parameterized routes
+//!
+//! Static edges are compressed, so a run like `api/v1/users` shared by a
+//! group of routes is a single edge. Lookups walk the request path in place
+//! without splitting it. Every route reachable from the path is a
+//! candidate, and the one that sorts first in `param_routes` wins. The tree
+//! stores route indices, so the winner is the same as a linear scan of the
+//! sorted list would find. Param values are captured during the walk, so the
+//! winner's path is not matched again.
+
+use std::borrow::Cow;
+
+use ahash::AHashMap;
+
+use crate::constraint::ParamConstraint;
+use crate::perfect_hash_router::{RoutePattern, Segment};
+
+#[derive(Default)]
+struct Node {
+    /// Static edges keyed by their first segment; the label may span several segments
+    statics: AHashMap<String, (String, usize)>,
+    /// Param edges; params at the same position share an edge per constraint
+    params: Vec<(Option<ParamConstraint>, usize)>,
+    /// Catch-all routes ending here, needing at least one more segment
+    catch_alls: Vec<usize>,
+    /// Routes whose pattern ends exactly here (same shape, different param names)
+    routes: Vec<usize>,
+    /// Lowest route index in this subtree, to skip subtrees that cannot win
+    min_route: usize,
+}
+
+pub(crate) struct ParamTree {
+    nodes: Vec<Node>,
+}
+
+impl ParamTree {
+    /// Build the tree for `patterns`, given in priority order
+    pub(crate) fn build<'a>(patterns: impl IntoIterator<Item = &'a RoutePattern>) -> Self {
+        let mut tree = ParamTree {
+            nodes: vec![Node::default()],
+        };
+        for (index, pattern) in patterns.into_iter().enumerate() {
+            tree.insert(index, pattern);
+        }
+        tree.compress(0);
+        tree.set_min_route(0);
+        tree
+    }
+
+    fn insert(&mut self, index: usize, pattern: &RoutePattern) {
+        let mut node = 0;
+        for segment in &pattern.segments {
+            node = match segment {
+                Segment::Static(label) => match self.nodes[node].statics.get(label.as_str()) {
+                    Some(&(_, child)) => child,
+                    None => {
+                        let child = self.push_node();
+                        self.nodes[node].statics.insert(label.clone(), (label.clone(), child));
+                        child
+                    }
+                },
+                Segment::Param { constraint, .. } => {
+                    match self.nodes[node].params.iter().find(|(c, _)| c == constraint) {
+                        Some(&(_, child)) => child,
+                        None => {
+                            let child = self.push_node();
+                            self.nodes[node].params.push((constraint.clone(), child));
+                            child
+                        }
+                    }
+                }
+                Segment::CatchAll { .. } => {
+                    self.nodes[node].catch_alls.push(index);
+                    return;
+                }
+            };
+        }
+        self.nodes[node].routes.push(index);
+    }
+
+    fn push_node(&mut self) -> usize {
+        self.nodes.push(Node::default());
+        self.nodes.len() - 1
+    }
+
+    /// Merge static edges into children that only continue with one static edge
+    fn compress(&mut self, node: usize) {
+        let keys: Vec<String> = self.nodes[node].statics.keys().cloned().collect();
+        for key in keys {
+            loop {
+                let (label, child) = self.nodes[node].statics[&key].clone();
+                let next = &self.nodes[child];
+                if next.statics.len() != 1
+                    || !next.params.is_empty()
+                    || !next.catch_alls.is_empty()
+                    || !next.routes.is_empty()
+                {
+                    break;
+                }
+                let (_, (tail, grandchild)) = next.statics.iter().next().expect("one static edge");
+                let merged = (format!("{label}/{tail}"), *grandchild);
+                self.nodes[node].statics.insert(key.clone(), merged);
+            }
+            let child = self.nodes[node].statics[&key].1;
+            self.compress(child);
+        }
+        let params: Vec<usize> = self.nodes[node].params.iter().map(|&(_, child)| child).collect();
+        for child in params {
+            self.compress(child);
+        }
+    }
+
+    fn set_min_route(&mut self, node: usize) -> usize {
+        let children: Vec<usize> = self.nodes[node]
+            .statics
+            .values()
+            .map(|&(_, child)| child)
+            .chain(self.nodes[node].params.iter().map(|&(_, child)| child))
+            .collect();
+        let mut min = self.nodes[node]
+            .routes
+            .iter()
+            .chain(&self.nodes[node].catch_alls)
+            .copied()
+            .min()
+            .unwrap_or(usize::MAX);
+        for child in children {
+            min = min.min(self.set_min_route(child));
+        }
+        self.nodes[node].min_route = min;
+        min
+    }
+
+    /// First route, in priority order, matching `path` and accepted by `accept`
+    ///
+    /// Returns the route with its param values in pattern order: params
+    /// decoded, a catch-all kept canonical. `path` must be normalized (see
+    /// `normalize_path`).
+    pub(crate) fn find(&self, path: &str, mut accept: impl FnMut(usize) -> bool) -> Option<(usize, Vec<String>)> {
+        let mut best = usize::MAX;
+        let mut captured = Vec::new();
+        self.walk(0, segments_of(path), &mut Vec::new(), &mut best, &mut |index, best, values| {
+            if index < *best && accept(index) {
+                *best = index;
+                captured = values.iter().map(|value| value.to_string()).collect();
+            }
+        });
+        (best != usize::MAX).then_some((best, captured))
+    }
+
+    /// Call `visit` with every route matching `path`
+    pub(crate) fn for_each_match(&self, path: &str, mut visit: impl FnMut(usize)) {
+        let mut limit = usize::MAX;
+        self.walk(0, segments_of(path), &mut Vec::new(), &mut limit, &mut |index, _, _| visit(index));
+    }
+
+    /// `rest` is the unmatched path, empty or starting with '/'; `values`
+    /// holds the params captured on the way here
+    fn walk<'p>(
+        &self,
+        node: usize,
+        rest: &'p str,
+        values: &mut Vec<Cow<'p, str>>,
+        limit: &mut usize,
+        visit: &mut impl FnMut(usize, &mut usize, &[Cow<'p, str>]),
+    ) {
+        let node = &self.nodes[node];
+        if node.min_route >= *limit {
+            return;
+        }
+        if rest.is_empty() {
+            for &index in &node.routes {
+                visit(index, limit, values);
+            }
+            return;
+        }
+
+        let segment_end = rest[1..].find('/').map_or(rest.len(), |i| i + 1);
+        let segment = &rest[1..segment_end];
+
+        if let Some((label, child)) = node.statics.get(segment) {
+            if let Some(after) = rest[1..].strip_prefix(label.as_str()) {
+                if after.is_empty() || after.starts_with('/') {
+                    self.walk(*child, after, values, limit, visit);
+                }
+            }
+        }
+
+        if !node.params.is_empty() {
+            let value = if segment.contains('%') {
+                Cow::Owned(crate::path::decode_param(segment))
+            } else {
+                Cow::Borrowed(segment)
+            };
+            values.push(value);
+            for (constraint, child) in &node.params {
+                if constraint.as_ref().is_none_or(|c| c.accepts(values.last().expect("pushed above"))) {
+                    self.walk(*child, &rest[segment_end..], values, limit, visit);
+                }
+            }
+            values.pop();
+        }
+
+        if !node.catch_alls.is_empty() {
+            // Kept as canonical: `%2F` stays encoded, so every '/' is a real separator
+            values.push(Cow::Borrowed(rest[1..].trim_end_matches('/')));
+            for &index in &node.catch_alls {
+                visit(index, limit, values);
+            }
+            values.pop();
+        }
+    }
+}
+
+/// The root path has no segments
+fn segments_of(path: &str) -> &str {
+    if path == "/" {
+        ""
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(paths: &[&str]) -> ParamTree {
+        let patterns: Vec<RoutePattern> = paths.iter().map(|path| RoutePattern::parse(path)).collect();
+        ParamTree::build(&patterns)
+    }
+
+    #[test]
+    fn compresses_static_runs_and_keeps_branches() {
+        let tree = build(&["/api/v1/users/:id", "/api/v1/posts/:id", "/api/v2/:id"]);
+        let root = &tree.nodes[0];
+        assert_eq!(root.statics["api"].0, "api");
+        let api = &tree.nodes[root.statics["api"].1];
+        assert_eq!(api.statics["v1"].0, "v1");
+        assert_eq!(api.statics["v2"].0, "v2");
+
+        let tree = build(&["/api/v1/users/:id"]);
+        assert_eq!(tree.nodes[0].statics["api"].0, "api/v1/users");
+        assert_eq!(tree.find("/api/v1/users/7", |_| true).map(|(index, _)| index), Some(0));
+        assert_eq!(tree.find("/api/v1/users", |_| true).map(|(index, _)| index), None);
+        assert_eq!(tree.find("/api/v1/usersx/7", |_| true).map(|(index, _)| index), None);
+    }
+
+    #[test]
+    fn lowest_index_wins_among_all_matches() {
+        // Sorted like `param_routes`: two static segments beat one
+        let tree = build(&["/:a/b/c", "/x/:b/:c", "/x/*rest"]);
+        assert_eq!(tree.find("/x/b/c", |_| true).map(|(index, _)| index), Some(0));
+        assert_eq!(tree.find("/x/b/c", |index| index != 0).map(|(index, _)| index), Some(1));
+        assert_eq!(tree.find("/x/b/c/d", |_| true).map(|(index, _)| index), Some(2));
+        assert_eq!(tree.find("/x", |_| true).map(|(index, _)| index), None);
+
+        let mut matched = Vec::new();
+        tree.for_each_match("/x/b/c", |index| matched.push(index));
+        matched.sort();
+        assert_eq!(matched, [0, 1, 2]);
+    }
+
+    #[test]
+    fn checks_constraints_on_decoded_segments() {
+        let tree = build(&["/users/:id<u64>", "/users/:name<a.b>", "/users/:other"]);
+        assert_eq!(tree.find("/users/42", |_| true).map(|(index, _)| index), Some(0));
+        assert_eq!(tree.find("/users/a%2Fb", |_| true).map(|(index, _)| index), Some(1));
+        assert_eq!(tree.find("/users/ABC", |_| true).map(|(index, _)| index), Some(2));
+        assert_eq!(tree.find("/", |_| true).map(|(index, _)| index), None);
+    }
+
+    #[test]
+    fn captures_values_of_the_winning_route() {
+        let tree = build(&["/users/:id<u64>/files/*rest", "/users/:name/:tab"]);
+        assert_eq!(
+            tree.find("/users/42/files/a%2Fb/c", |_| true),
+            Some((0, vec!["42".to_string(), "a%2Fb/c".to_string()]))
+        );
+        assert_eq!(
+            tree.find("/users/a%2Fb/files", |_| true),
+            Some((1, vec!["a/b".to_string(), "files".to_string()]))
+        );
+    }
+}
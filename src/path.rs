@@ -0,0 +1,175 @@
+//! Request path canonicalization, applied before routing
+//!
+//! The raw request target is percent-decoded and its dot segments and
+//! slashes are cleaned up according to `PathConfig`, so middleware, routes
+//! and `Path` params all see the same path. `%2F` and `%25` stay encoded in
+//! the canonical path so a decoded byte never changes its segments; the
+//! router decodes them when it hands out params.
+
+use std::borrow::Cow;
+
+use crate::config::{DotSegments, PathConfig, TrailingSlash};
+
+/// Why a request path was not routed as sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PathRejection {
+    /// The path cannot be decoded or escapes the root (400)
+    Invalid(&'static str),
+    /// The client should ask for this canonical path instead (308)
+    Redirect(String),
+}
+
+/// Canonical form of `raw`, borrowed when it needs no changes
+pub(crate) fn canonicalize<'a>(raw: &'a str, config: &PathConfig) -> Result<Cow<'a, str>, PathRejection> {
+    let decoded = decode(raw)?;
+    let needs_cleanup = decoded.contains("//") || has_dot_segment(&decoded);
+    let trailing = decoded.len() > 1 && decoded.ends_with('/');
+    if !needs_cleanup && (!trailing || config.trailing_slash != TrailingSlash::Redirect) {
+        return Ok(decoded);
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/').skip(1) {
+        match segment {
+            "." | ".." if config.dot_segments == DotSegments::Reject => {
+                return Err(PathRejection::Invalid("Dot segments are not allowed in the path"));
+            }
+            "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(PathRejection::Invalid("Path escapes the root"));
+                }
+            }
+            "" if config.merge_slashes => {}
+            segment => segments.push(segment),
+        }
+    }
+    // Without merging, a trailing slash leaves an empty last segment behind
+    if segments.last() == Some(&"") {
+        segments.pop();
+    }
+
+    let mut path = format!("/{}", segments.join("/"));
+    if trailing && path.len() > 1 {
+        if config.trailing_slash == TrailingSlash::Redirect {
+            return Err(PathRejection::Redirect(path));
+        }
+        path.push('/');
+    }
+    Ok(Cow::Owned(path))
+}
+
+/// Percent-decode `raw`, keeping `%2F` and `%25` encoded
+fn decode(raw: &str) -> Result<Cow<'_, str>, PathRejection> {
+    if !raw.contains('%') {
+        return Ok(Cow::Borrowed(raw));
+    }
+
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let byte = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(PathRejection::Invalid("Malformed percent-encoding in the path"))?;
+        match byte {
+            b'/' => out.extend_from_slice(b"%2F"),
+            b'%' => out.extend_from_slice(b"%25"),
+            byte => out.push(byte),
+        }
+        i += 3;
+    }
+    String::from_utf8(out)
+        .map(Cow::Owned)
+        .map_err(|_| PathRejection::Invalid("Path is not valid UTF-8"))
+}
+
+fn has_dot_segment(path: &str) -> bool {
+    path.split('/').any(|segment| segment == "." || segment == "..")
+}
+
+/// Decode the `%2F` and `%25` a canonical path keeps, for a route param
+pub(crate) fn decode_param(segment: &str) -> String {
+    if !segment.contains('%') {
+        return segment.to_string();
+    }
+    segment.replace("%2F", "/").replace("%25", "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perfect_hash_router::normalize_path;
+
+    fn canonical(raw: &str) -> Result<String, PathRejection> {
+        canonicalize(raw, &PathConfig::default()).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn decodes_percent_escapes_but_keeps_slashes_encoded() {
+        assert_eq!(canonical("/users/John%20Doe").unwrap(), "/users/John Doe");
+        assert_eq!(canonical("/caf%C3%A9").unwrap(), "/café");
+        assert_eq!(canonical("/files/a%2Fb").unwrap(), "/files/a%2Fb");
+        assert_eq!(canonical("/files/100%25").unwrap(), "/files/100%25");
+        assert_eq!(decode_param("a%2Fb%2541"), "a/b%41");
+        assert!(matches!(canonicalize("/plain", &PathConfig::default()), Ok(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn rejects_undecodable_paths() {
+        assert!(matches!(canonical("/bad%zz"), Err(PathRejection::Invalid(_))));
+        assert!(matches!(canonical("/cut%4"), Err(PathRejection::Invalid(_))));
+        assert!(matches!(canonical("/latin1%E9"), Err(PathRejection::Invalid(_))));
+    }
+
+    #[test]
+    fn resolves_or_rejects_dot_segments() {
+        assert_eq!(canonical("/a/./b/../c").unwrap(), "/a/c");
+        assert_eq!(canonical("/static/%2e%2e/secret").unwrap(), "/secret");
+        assert!(matches!(canonical("/../etc/passwd"), Err(PathRejection::Invalid(_))));
+        assert!(matches!(canonical("/a/%2E%2E/%2e%2e/x"), Err(PathRejection::Invalid(_))));
+
+        let reject = PathConfig { dot_segments: DotSegments::Reject, ..Default::default() };
+        assert!(canonicalize("/a/./b", &reject).is_err());
+        assert_eq!(canonicalize("/a.b/c..d", &reject).unwrap(), "/a.b/c..d");
+    }
+
+    #[test]
+    fn slashes_follow_the_config() {
+        assert_eq!(canonical("//a///b").unwrap(), "/a/b");
+        assert_eq!(canonical("/a/b/").unwrap(), "/a/b/");
+
+        let keep = PathConfig { merge_slashes: false, ..Default::default() };
+        assert_eq!(canonicalize("/a//b/./c", &keep).unwrap(), "/a//b/c");
+
+        let redirect = PathConfig { trailing_slash: TrailingSlash::Redirect, ..Default::default() };
+        assert_eq!(canonicalize("/a/b/", &redirect), Err(PathRejection::Redirect("/a/b".into())));
+        assert_eq!(canonicalize("//a//", &redirect), Err(PathRejection::Redirect("/a".into())));
+        assert_eq!(canonicalize("/", &redirect).unwrap(), "/");
+    }
+
+    #[test]
+    fn canonical_paths_route_like_the_router_normalizes_them() {
+        for (raw, routed) in [
+            ("/", "/"),
+            ("/users/42", "/users/42"),
+            ("/users/42/", "/users/42"),
+            ("//users//42", "/users/42"),
+            ("/users/./42", "/users/42"),
+            ("/users/x/../42", "/users/42"),
+            ("/users/%34%32", "/users/42"),
+        ] {
+            let canonical = canonical(raw).unwrap();
+            assert_eq!(normalize_path(&canonical), routed, "{raw}");
+            // Already canonical paths come back unchanged
+            assert_eq!(self::canonical(&canonical).unwrap(), canonical, "{raw}");
+        }
+    }
+}
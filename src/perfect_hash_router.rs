@@ -1,7 +1,10 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
 use ahash::AHashMap;
 
+use crate::constraint::{self, ParamConstraint};
+use crate::param_tree::ParamTree;
 use crate::AsyncHandler;
 use crate::Method;
 
@@ -12,8 +15,16 @@ type HandlerBox = Arc<dyn AsyncHandler>;
 pub enum Segment {
     /// Static path segment (e.g., "users", "api")
     Static(String),
-    /// Dynamic parameter (e.g., ":id", ":name")
-    Param { name: String },
+    /// Dynamic parameter (e.g., ":id", ":name"), optionally constrained (":id<u64>")
+    Param {
+        name: String,
+        constraint: Option<ParamConstraint>,
+    },
+    /// Trailing catch-all (e.g., "*path"), capturing every remaining segment
+    ///
+    /// The capture is the rest of the canonical path, `%2F` and `%25` still
+    /// encoded, so a '/' in it always separated two request segments.
+    CatchAll { name: String },
 }
 
 /// Route pattern with segments and precomputed hash
@@ -25,60 +36,109 @@ pub struct RoutePattern {
     pub segments: Vec<Segment>,
     /// Number of static segments (for specificity sorting)
     pub specificity: usize,
+    /// Whether the last segment is a catch-all
+    pub catch_all: bool,
+    /// Number of constrained params (tried before unconstrained ones)
+    pub constrained: usize,
 }
 
 impl RoutePattern {
     /// Parse a path into a RoutePattern
+    ///
+    /// # Panics
+    ///
+    /// If a param constraint is invalid; the route macros and Light Guard
+    /// reject those before the router is built.
     pub fn parse(path: &str) -> Self {
-        let segments: Vec<Segment> = path
+        Self::try_parse(path).unwrap_or_else(|err| panic!("invalid route '{path}': {err}"))
+    }
+
+    /// Parse a path into a RoutePattern, failing on an invalid param constraint
+    pub fn try_parse(path: &str) -> Result<Self, String> {
+        let segments = path
             .split('/')
             .filter(|s| !s.is_empty())
             .map(|part| {
-                if part.starts_with(':') {
+                Ok(if let Some(spec) = part.strip_prefix(':') {
+                    let (name, constraint) = constraint::split_param(spec);
                     Segment::Param {
-                        name: part[1..].to_string(),
+                        name: name.to_string(),
+                        constraint: constraint.map(ParamConstraint::parse).transpose()?,
+                    }
+                } else if let Some(name) = part.strip_prefix('*') {
+                    Segment::CatchAll {
+                        name: name.to_string(),
                     }
                 } else {
                     Segment::Static(part.to_string())
-                }
+                })
             })
-            .collect();
+            .collect::<Result<Vec<Segment>, String>>()?;
 
         let specificity = segments
             .iter()
             .filter(|s| matches!(s, Segment::Static(_)))
             .count();
+        let catch_all = matches!(segments.last(), Some(Segment::CatchAll { .. }));
+        let constrained = segments
+            .iter()
+            .filter(|s| matches!(s, Segment::Param { constraint: Some(_), .. }))
+            .count();
 
-        RoutePattern {
+        Ok(RoutePattern {
             path: path.to_string(),
             segments,
             specificity,
-        }
+            catch_all,
+            constrained,
+        })
     }
 
-    /// Check if this pattern matches the given path parts
+    /// Match a normalized path against this pattern
     /// Returns Some(params) if match, None otherwise
-    pub fn matches(&self, parts: &[&str]) -> Option<AHashMap<String, String>> {
-        if parts.len() != self.segments.len() {
-            return None;
-        }
-
+    ///
+    /// A catch-all needs at least one segment to capture: `/static/*path`
+    /// matches `/static/app.js` but not `/static`.
+    pub fn matches(&self, path: &str) -> Option<AHashMap<String, String>> {
         let mut params = AHashMap::new();
+        let mut rest = path.trim_start_matches('/');
 
-        for (segment, part) in self.segments.iter().zip(parts.iter()) {
+        for segment in &self.segments {
+            if rest.is_empty() {
+                return None;
+            }
+            let (part, tail) = rest.split_once('/').unwrap_or((rest, ""));
             match segment {
                 Segment::Static(expected) => {
                     if expected != part {
                         return None;
                     }
                 }
-                Segment::Param { name } => {
-                    params.insert(name.clone(), part.to_string());
+                Segment::Param { name, constraint } => {
+                    let value = crate::path::decode_param(part);
+                    if constraint.as_ref().is_some_and(|c| !c.accepts(&value)) {
+                        return None;
+                    }
+                    params.insert(name.clone(), value);
+                }
+                Segment::CatchAll { name } => {
+                    // Kept as canonical: `%2F` stays encoded, so every '/' is a real separator
+                    params.insert(name.clone(), rest.trim_end_matches('/').to_string());
+                    return Some(params);
                 }
             }
+            rest = tail.trim_start_matches('/');
         }
 
-        Some(params)
+        rest.is_empty().then_some(params)
+    }
+
+    /// Names of the params and catch-all, in path order
+    pub(crate) fn param_names(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Static(_) => None,
+            Segment::Param { name, .. } | Segment::CatchAll { name } => Some(name.as_str()),
+        })
     }
 
     /// Check if path is fully static (no parameters)
@@ -107,15 +167,28 @@ struct ParamRoute {
     methods: HashMap<String, HandlerBox>,
 }
 
-/// Perfect hash router - O(1) for static routes, O(n) for param routes
+/// Outcome of routing a request by method and path
+pub enum RouteMatch {
+    /// A handler serves the method (HEAD falls back to the GET handler)
+    Found(HandlerBox, AHashMap<String, String>),
+    /// The path is routed, but not for this method; lists the methods it allows
+    MethodNotAllowed(Vec<String>),
+    /// No route matches the path under any method
+    NotFound,
+}
+
+/// Perfect hash router - O(1) for static routes, radix tree for param routes
 pub struct PerfectHashRouter {
     /// Static routes stored in a collision-free table.
     static_routes: Vec<StaticRoute>,
     static_table: Option<StaticPerfectTable>,
 
-    /// Parameterized routes sorted by specificity (most specific first)
-    /// O(n) search but n is typically small and ordered by likelihood
+    /// Parameterized routes sorted by specificity (most specific first,
+    /// catch-alls after param routes of equal specificity, constrained
+    /// params before unconstrained ones)
     param_routes: Vec<ParamRoute>,
+    /// Radix tree over `param_routes`; the matching route sorted first wins
+    param_tree: ParamTree,
 
     /// Total route count
     route_count: usize,
@@ -127,6 +200,7 @@ impl PerfectHashRouter {
             static_routes: Vec::new(),
             static_table: None,
             param_routes: Vec::new(),
+            param_tree: ParamTree::build([]),
             route_count: 0,
         }
     }
@@ -157,7 +231,7 @@ impl PerfectHashRouter {
                 if let Some(conflict) = self
                     .param_routes
                     .iter()
-                    .find(|r| same_route_shape(&r.pattern, &pattern) && r.methods.contains_key(&method_upper))
+                    .find(|r| patterns_may_overlap(&r.pattern, &pattern) && r.methods.contains_key(&method_upper))
                 {
                     eprintln!(
                         "[ROUTER] Ambiguous param route shape detected for method {}: '{}' and '{}'. \
@@ -200,17 +274,13 @@ Resolution is deterministic (specificity desc, path lexical asc).",
                 );
             }
 
-            let handler = Arc::from(Box::new(route.handler) as Box<dyn AsyncHandler>);
-            if self.insert_static_route(method_upper, normalized_path, handler) {
+            let handler = Arc::from(boxed_route_handler(route));
+            if self.insert_static_route(method_upper, normalized_path.into_owned(), handler) {
                 self.route_count += 1;
             }
         } else {
             // Metadata can lie (or be stale). Dynamic routes are always rebuilt from path shape.
-            self.add_route(
-                route.method,
-                route.path,
-                Box::new(route.handler) as Box<dyn AsyncHandler>,
-            );
+            self.add_route(route.method, route.path, boxed_route_handler(route));
         }
     }
 
@@ -246,25 +316,63 @@ Resolution is deterministic (specificity desc, path lexical asc).",
             }
         }
 
-        // Try parameterized routes (O(n), but sorted by specificity)
-        let parts: Vec<&str> = normalized_path
-            .split('/')
-            .filter(|s| !s.is_empty())
-            .collect();
-        let parts_len = parts.len();
-
-        for param_route in &self.param_routes {
-            if param_route.pattern.segments.len() != parts_len {
-                continue;
-            }
-            if let Some(params) = param_route.pattern.matches(&parts) {
-                if let Some(handler) = param_route.methods.get(method_str) {
-                    return Some((Arc::clone(handler), params));
-                }
+        // Try parameterized routes: the best match that serves the method
+        let (index, values) = self
+            .param_tree
+            .find(&normalized_path, |index| self.param_routes[index].methods.contains_key(method_str))?;
+        let route = &self.param_routes[index];
+        let params = route.pattern.param_names().map(str::to_string).zip(values).collect();
+        route
+            .methods
+            .get(method_str)
+            .map(|handler| (Arc::clone(handler), params))
+    }
+
+    /// Route a request, telling an unknown path apart from a method the path does not allow
+    pub fn route(&self, method: &Method, path: &str) -> RouteMatch {
+        if let Some((handler, params)) = self.find(method, path) {
+            return RouteMatch::Found(handler, params);
+        }
+        if matches!(method, Method::HEAD) {
+            if let Some((handler, params)) = self.find(&Method::GET, path) {
+                return RouteMatch::Found(handler, params);
             }
         }
 
-        None
+        let allowed = self.allowed_methods(path);
+        if allowed.is_empty() {
+            RouteMatch::NotFound
+        } else {
+            RouteMatch::MethodNotAllowed(allowed)
+        }
+    }
+
+    /// Methods served for `path`, sorted, including the implied HEAD and OPTIONS
+    ///
+    /// Empty when no route matches the path.
+    pub fn allowed_methods(&self, path: &str) -> Vec<String> {
+        let normalized_path = normalize_path(path);
+
+        let mut allowed: Vec<String> = self
+            .static_routes
+            .iter()
+            .filter(|route| route.path == normalized_path)
+            .map(|route| route.method.clone())
+            .collect();
+        self.param_tree.for_each_match(&normalized_path, |index| {
+            allowed.extend(self.param_routes[index].methods.keys().cloned());
+        });
+        if allowed.is_empty() {
+            return allowed;
+        }
+
+        if allowed.iter().any(|m| m == "GET") {
+            allowed.push("HEAD".to_string());
+        }
+        allowed.push("OPTIONS".to_string());
+        allowed.sort();
+        allowed.dedup();
+        allowed
     }
 
     /// Compute perfect hash for static route
@@ -356,8 +464,11 @@ Resolution is deterministic (specificity desc, path lexical asc).",
             b.pattern
                 .specificity
                 .cmp(&a.pattern.specificity)
+                .then_with(|| a.pattern.catch_all.cmp(&b.pattern.catch_all))
+                .then_with(|| b.pattern.constrained.cmp(&a.pattern.constrained))
                 .then_with(|| a.pattern.path.cmp(&b.pattern.path))
         });
+        self.param_tree = ParamTree::build(self.param_routes.iter().map(|route| &route.pattern));
     }
 }
 
@@ -389,18 +500,33 @@ fn method_to_str(method: &Method) -> &'static str {
     }
 }
 
-fn normalize_path(path: &str) -> String {
+/// Box a compile-time route handler, keeping its body streaming flag
+fn boxed_route_handler(route: &crate::RouteInfo) -> Box<dyn AsyncHandler> {
+    if route.streaming_body {
+        Box::new(crate::Streaming(route.handler))
+    } else {
+        Box::new(route.handler)
+    }
+}
+
+/// Path with a leading '/' and no empty segments, borrowed when already in that form
+pub(crate) fn normalize_path(path: &str) -> Cow<'_, str> {
+    let trailing = path.len() > 1 && path.ends_with('/');
+    if path.starts_with('/') && !trailing && !path.contains("//") {
+        return Cow::Borrowed(path);
+    }
+
     let normalized_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
     if normalized_segments.is_empty() {
-        "/".to_string()
+        Cow::Borrowed("/")
     } else {
-        format!("/{}", normalized_segments.join("/"))
+        Cow::Owned(format!("/{}", normalized_segments.join("/")))
     }
 }
 
 #[inline]
 fn is_static_path_runtime(path: &str) -> bool {
-    !path.as_bytes().contains(&b':')
+    !path.as_bytes().contains(&b':') && !path.contains("/*")
 }
 
 #[inline]
@@ -432,7 +558,8 @@ fn static_slot(method: &str, path: &str, seed: u64, table_len: usize) -> usize {
     (hash_route_key_with_seed(method, path, seed) as usize) % table_len
 }
 
-fn same_route_shape(a: &RoutePattern, b: &RoutePattern) -> bool {
+/// Whether two patterns have the same shape and constraints some path satisfies in both
+pub(crate) fn patterns_may_overlap(a: &RoutePattern, b: &RoutePattern) -> bool {
     if a.segments.len() != b.segments.len() {
         return false;
     }
@@ -440,7 +567,9 @@ fn same_route_shape(a: &RoutePattern, b: &RoutePattern) -> bool {
     for (left, right) in a.segments.iter().zip(b.segments.iter()) {
         match (left, right) {
             (Segment::Static(lhs), Segment::Static(rhs)) if lhs == rhs => {}
-            (Segment::Param { .. }, Segment::Param { .. }) => {}
+            (Segment::Param { constraint: lhs, .. }, Segment::Param { constraint: rhs, .. })
+                if constraint::may_overlap(lhs.as_ref(), rhs.as_ref()) => {}
+            (Segment::CatchAll { .. }, Segment::CatchAll { .. }) => {}
             _ => return false,
         }
     }
@@ -486,8 +615,7 @@ mod tests {
     #[test]
     fn test_pattern_matching() {
         let pattern = RoutePattern::parse("/users/:id/posts");
-        let parts = vec!["users", "123", "posts"];
-        let params = pattern.matches(&parts).unwrap();
+        let params = pattern.matches("/users/123/posts").unwrap();
 
         assert_eq!(params.get("id"), Some(&"123".to_string()));
     }
@@ -495,8 +623,94 @@ mod tests {
     #[test]
     fn test_pattern_no_match() {
         let pattern = RoutePattern::parse("/users/:id/posts");
-        let parts = vec!["users", "123"]; // Too few segments
-        assert!(pattern.matches(&parts).is_none());
+        assert!(pattern.matches("/users/123").is_none()); // Too few segments
+    }
+
+    #[test]
+    fn test_catch_all_captures_rest_of_path() {
+        let mut router = PerfectHashRouter::new();
+        router.add_route("GET", "/static/*path", Box::new(MockHandler));
+
+        let (_, params) = router.find(&Method::GET, "/static/css/site/app.css").unwrap();
+        assert_eq!(params.get("path").unwrap(), "css/site/app.css");
+        let (_, params) = router.find(&Method::GET, "/static/a%2Fb/c").unwrap();
+        assert_eq!(params.get("path").unwrap(), "a%2Fb/c");
+
+        // Nothing to capture
+        assert!(router.find(&Method::GET, "/static").is_none());
+        assert!(!is_static_path_runtime("/static/*path"));
+        assert!(!crate::const_is_static_path("/static/*path"));
+        assert!(crate::const_is_static_path("/a*b"));
+    }
+
+    #[test]
+    fn test_catch_all_loses_to_more_specific_routes() {
+        let mut router = PerfectHashRouter::new();
+        router.add_route("GET", "/docs/*rest", Box::new(MockHandler));
+        router.add_route("GET", "/docs/:page", Box::new(MockHandler));
+        router.add_route("GET", "/docs/api/*rest", Box::new(MockHandler));
+
+        let (_, params) = router.find(&Method::GET, "/docs/intro").unwrap();
+        assert_eq!(params.get("page").unwrap(), "intro");
+        let (_, params) = router.find(&Method::GET, "/docs/intro/setup").unwrap();
+        assert_eq!(params.get("rest").unwrap(), "intro/setup");
+        let (_, params) = router.find(&Method::GET, "/docs/api/router").unwrap();
+        assert_eq!(params.get("rest").unwrap(), "router");
+        assert_eq!(router.allowed_methods("/docs/a/b/c"), ["GET", "HEAD", "OPTIONS"]);
+    }
+
+    #[test]
+    fn test_constrained_params_pick_the_matching_route() {
+        let mut router = PerfectHashRouter::new();
+        router.add_route("GET", "/users/:username", Box::new(MockHandler));
+        router.add_route("GET", "/users/:id<u64>", Box::new(MockHandler));
+        router.add_route("GET", "/posts/:slug<[a-z0-9-]+>", Box::new(MockHandler));
+
+        let (_, params) = router.find(&Method::GET, "/users/42").unwrap();
+        assert_eq!(params.get("id").unwrap(), "42");
+        let (_, params) = router.find(&Method::GET, "/users/ada").unwrap();
+        assert_eq!(params.get("username").unwrap(), "ada");
+
+        assert!(router.find(&Method::GET, "/posts/hello-world").is_some());
+        assert!(router.find(&Method::GET, "/posts/Hello").is_none());
+        assert!(RoutePattern::try_parse("/users/:id<[0-9>").is_err());
+    }
+
+    #[test]
+    fn test_param_tree_agrees_with_linear_scan() {
+        let mut router = PerfectHashRouter::new();
+        let routes = [
+            ("GET", "/:a/b/c"),
+            ("GET", "/x/:b/:c"),
+            ("POST", "/x/:b/:c"),
+            ("GET", "/x/*rest"),
+            ("GET", "/api/v1/users/:id<u64>"),
+            ("GET", "/api/v1/users/:name"),
+            ("DELETE", "/api/v1/users/:id"),
+            ("GET", "/api/v1/users/:id/posts/:post"),
+            ("GET", "/api/v2/:resource/*rest"),
+            ("GET", "/:tenant/api/v1/users/:id"),
+            ("GET", "/files/:name<[a-z]+\\.txt>"),
+        ];
+        for (method, path) in routes {
+            router.add_route(method, path, Box::new(MockHandler));
+        }
+
+        let paths = [
+            "/x/b/c", "/x/y/z", "/x/1/2/3", "/x", "/api/v1/users/42", "/api/v1/users/ada",
+            "/api/v1/users/42/posts/7", "/api/v2/things/a/b", "/api/v2/things", "/acme/api/v1/users/1",
+            "/files/notes.txt", "/files/notes.md", "/files/a%2Fb", "/", "/nope",
+        ];
+        for path in paths {
+            for method in [Method::GET, Method::POST, Method::DELETE] {
+                let expected = router.param_routes.iter().find_map(|route| {
+                    let params = route.pattern.matches(path)?;
+                    route.methods.contains_key(method_to_str(&method)).then_some(params)
+                });
+                let found = router.find(&method, path).map(|(_, params)| params);
+                assert_eq!(found, expected, "{} {}", method_to_str(&method), path);
+            }
+        }
     }
 
     #[test]
@@ -559,6 +773,38 @@ mod tests {
         assert!(result.is_none()); // POST not registered
     }
 
+    #[test]
+    fn test_route_tells_method_not_allowed_from_not_found() {
+        let mut router = PerfectHashRouter::new();
+        router.add_route("GET", "/users", Box::new(MockHandler));
+        router.add_route("POST", "/users", Box::new(MockHandler));
+        router.add_route("DELETE", "/users/:id", Box::new(MockHandler));
+
+        assert!(matches!(router.route(&Method::POST, "/users"), RouteMatch::Found(..)));
+        assert!(matches!(router.route(&Method::GET, "/missing"), RouteMatch::NotFound));
+        match router.route(&Method::PUT, "/users") {
+            RouteMatch::MethodNotAllowed(allowed) => {
+                assert_eq!(allowed, ["GET", "HEAD", "OPTIONS", "POST"]);
+            }
+            _ => panic!("expected 405"),
+        }
+        match router.route(&Method::GET, "/users/7") {
+            RouteMatch::MethodNotAllowed(allowed) => assert_eq!(allowed, ["DELETE", "OPTIONS"]),
+            _ => panic!("expected 405"),
+        }
+    }
+
+    #[test]
+    fn test_head_falls_back_to_get() {
+        let mut router = PerfectHashRouter::new();
+        router.add_route("GET", "/users/:id", Box::new(MockHandler));
+
+        match router.route(&Method::HEAD, "/users/7") {
+            RouteMatch::Found(_, params) => assert_eq!(params.get("id"), Some(&"7".to_string())),
+            _ => panic!("HEAD should use the GET handler"),
+        }
+    }
+
     #[test]
     fn test_router_stats() {
         let mut router = PerfectHashRouter::new();
@@ -640,6 +886,9 @@ mod tests {
             handler: route_info_handler,
             precomputed_hash: 42,
             is_static_path: true,
+            streaming_body: false,
+            name: None,
+            host: None,
         };
 
         router.add_route_info(&bad);
@@ -651,6 +900,28 @@ mod tests {
         assert_eq!(stats.param_routes, 1);
     }
 
+    #[test]
+    fn test_route_info_keeps_streaming_flag() {
+        let mut router = PerfectHashRouter::new();
+        for (path, streaming_body) in [("/upload", true), ("/files/:id", true), ("/plain", false)] {
+            router.add_route_info(&crate::RouteInfo {
+                method: "POST",
+                path,
+                handler: route_info_handler,
+                precomputed_hash: 0,
+                is_static_path: !path.contains(':'),
+                streaming_body,
+                name: None,
+                host: None,
+            });
+        }
+
+        let streams = |path: &str| router.find(&Method::POST, path).unwrap().0.streams_body();
+        assert!(streams("/upload"));
+        assert!(streams("/files/7"));
+        assert!(!streams("/plain"));
+    }
+
     #[test]
     fn test_route_info_ignores_incorrect_precomputed_hash() {
         let mut router = PerfectHashRouter::new();
@@ -660,6 +931,9 @@ mod tests {
             handler: route_info_handler,
             precomputed_hash: 12345, // intentionally wrong
             is_static_path: true,
+            streaming_body: false,
+            name: None,
+            host: None,
         };
 
         router.add_route_info(&bad);
@@ -676,6 +950,9 @@ mod tests {
             handler: route_info_handler,
             precomputed_hash: hash_route_key("GET", "/health/"),
             is_static_path: true,
+            streaming_body: false,
+            name: None,
+            host: None,
         };
 
         router.add_route_info(&bad);
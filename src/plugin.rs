@@ -1,3 +1,4 @@
+use crate::request::{Context, RemoteAddr};
 use crate::{Request, Response};
 use std::any::Any;
 use std::collections::{HashMap, HashSet};
@@ -18,6 +19,35 @@ impl std::error::Error for PluginError {}
 /// Plugin result type
 pub type PluginResult<T> = Result<T, PluginError>;
 
+/// Decision returned by `Plugin::on_stream_accept`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamAction {
+    /// Keep the connection and hand it to the next plugin
+    Accept,
+    /// Close the connection immediately, before any request is read
+    Reject,
+}
+
+/// Freshly accepted connection handed to `Plugin::on_stream_accept`
+#[derive(Debug)]
+pub enum AcceptedStream<'a> {
+    Tcp(&'a mut tokio::net::TcpStream),
+    /// Connection on a `unix:` listener
+    #[cfg(unix)]
+    Unix(&'a mut tokio::net::UnixStream),
+}
+
+impl AcceptedStream<'_> {
+    /// Borrow the stream again for the next plugin
+    pub(crate) fn reborrow(&mut self) -> AcceptedStream<'_> {
+        match self {
+            AcceptedStream::Tcp(stream) => AcceptedStream::Tcp(stream),
+            #[cfg(unix)]
+            AcceptedStream::Unix(stream) => AcceptedStream::Unix(stream),
+        }
+    }
+}
+
 /// Plugin metadata
 #[derive(Debug, Clone)]
 pub struct PluginMetadata {
@@ -85,8 +115,19 @@ pub trait Plugin: Send + Sync {
     }
     
     /// Called when a new connection is accepted (for stream-level plugins)
-    async fn on_stream_accept(&self, _stream: &mut tokio::net::TcpStream) -> PluginResult<()> {
-        Ok(())
+    ///
+    /// Runs for TCP and Unix socket connections alike; `peer` is the address,
+    /// or the Unix peer credentials, that requests will see as `remote_addr`.
+    /// Values inserted into `conn` are copied into the context of every request
+    /// served on this connection, and dropped once the connection and all its
+    /// requests are gone. Return `StreamAction::Reject` to close the connection.
+    async fn on_stream_accept(
+        &self,
+        _stream: AcceptedStream<'_>,
+        _peer: &RemoteAddr,
+        _conn: &mut Context,
+    ) -> PluginResult<StreamAction> {
+        Ok(StreamAction::Accept)
     }
     
     /// Get plugin state (for accessing plugin-specific data)
@@ -253,6 +294,12 @@ impl Default for PluginRegistry {
     }
 }
 
+/// Order plugins for the per-request hooks: higher priority first,
+/// ties keep their registration order.
+pub(crate) fn sort_by_priority(plugins: &mut [Arc<dyn Plugin>]) {
+    plugins.sort_by_key(|p| std::cmp::Reverse(p.priority()));
+}
+
 /// Global plugin registry
 static PLUGIN_REGISTRY: std::sync::OnceLock<tokio::sync::RwLock<PluginRegistry>> = std::sync::OnceLock::new();
 
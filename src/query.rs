@@ -0,0 +1,374 @@
+//! Query string deserialization for `Query<T>`
+//!
+//! The raw query is first parsed into a tree: repeated keys (`tag=a&tag=b`)
+//! and `tag[]=a` become lists, `filter[min]=1` becomes a nested map. The tree
+//! is then deserialized with values parsed from their text on demand, so
+//! numbers, bools and enums work like they would from JSON.
+
+use std::fmt;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+
+use crate::{Error, Result};
+
+/// Deserialize `T` from a raw query string (without the leading `?`)
+pub(crate) fn from_query_str<T: DeserializeOwned>(raw: &str) -> Result<T> {
+    let root = Node::Map(parse(raw));
+    serde_path_to_error::deserialize(root).map_err(|err| {
+        let path = err.path().to_string();
+        if path == "." {
+            Error::BadRequest(format!("Invalid query: {}", err.inner()))
+        } else {
+            Error::BadRequest(format!("Invalid query parameter `{}`: {}", path, err.inner()))
+        }
+    })
+}
+
+/// Every decoded value of `name` (or `name[]`) in a raw query string
+pub(crate) fn values_of(raw: &str, name: &str) -> Vec<String> {
+    form_urlencoded::parse(raw.as_bytes())
+        .filter(|(key, _)| key == name || key.strip_suffix("[]") == Some(name))
+        .map(|(_, value)| value.into_owned())
+        .collect()
+}
+
+#[derive(Debug)]
+enum Node {
+    Value(String),
+    List(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+fn parse(raw: &str) -> Vec<(String, Node)> {
+    let mut root = Vec::new();
+    for (key, value) in form_urlencoded::parse(raw.as_bytes()) {
+        let (name, rest) = split_key(&key);
+        insert(&mut root, name, &rest, value.into_owned());
+    }
+    root
+}
+
+/// `a[b][]` -> (`a`, [`b`, ``]); a key with unbalanced brackets stays whole
+fn split_key(key: &str) -> (&str, Vec<&str>) {
+    let Some(open) = key.find('[').filter(|&open| open > 0 && key.ends_with(']')) else {
+        return (key, Vec::new());
+    };
+    let segments: Vec<&str> = key[open + 1..key.len() - 1].split("][").collect();
+    if segments.iter().any(|segment| segment.contains(['[', ']'])) {
+        return (key, Vec::new());
+    }
+    (&key[..open], segments)
+}
+
+fn insert(map: &mut Vec<(String, Node)>, name: &str, rest: &[&str], value: String) {
+    let position = map.iter().position(|(key, _)| key == name);
+    let Some(position) = position else {
+        map.push((name.to_string(), build(rest, value)));
+        return;
+    };
+
+    let node = &mut map[position].1;
+    match (rest.first(), node) {
+        // `a=1&a=2` and `a[]=1&a[]=2` collect into a list
+        (None | Some(&""), node @ Node::Value(_)) => {
+            let first = std::mem::replace(node, Node::List(Vec::new()));
+            if let Node::List(items) = node {
+                items.push(first);
+                items.push(build(rest.get(1..).unwrap_or_default(), value));
+            }
+        }
+        (None | Some(&""), Node::List(items)) => items.push(build(rest.get(1..).unwrap_or_default(), value)),
+        (Some(&key), Node::Map(fields)) if !key.is_empty() => insert(fields, key, &rest[1..], value),
+        // Conflicting shapes such as `a=1&a[b]=2`: the first one wins
+        _ => {}
+    }
+}
+
+fn build(rest: &[&str], value: String) -> Node {
+    match rest.split_first() {
+        None => Node::Value(value),
+        Some((&"", rest)) => Node::List(vec![build(rest, value)]),
+        Some((key, rest)) => Node::Map(vec![(key.to_string(), build(rest, value))]),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct QueryError(String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl de::Error for QueryError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        QueryError(msg.to_string())
+    }
+}
+
+impl Node {
+    /// The text of a single value; repeated keys read as their last value
+    fn into_scalar(self) -> std::result::Result<String, QueryError> {
+        match self {
+            Node::Value(value) => Ok(value),
+            Node::List(mut items) => match items.pop() {
+                Some(last) => last.into_scalar(),
+                None => Err(QueryError("expected a value, found an empty list".into())),
+            },
+            Node::Map(_) => Err(QueryError("expected a value, found nested fields".into())),
+        }
+    }
+
+    fn parse<T>(self) -> std::result::Result<T, QueryError>
+    where
+        T: std::str::FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self.into_scalar()?;
+        value.parse().map_err(|err| QueryError(format!("cannot parse `{}`: {}", value, err)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, QueryError> for Node {
+    type Deserializer = Node;
+
+    fn into_deserializer(self) -> Node {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node {
+    type Error = QueryError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        match self {
+            Node::Value(value) => visitor.visit_string(value),
+            Node::List(items) => visitor.visit_seq(SeqDeserializer::new(items.into_iter())),
+            Node::Map(fields) => visitor.visit_map(MapDeserializer::new(fields.into_iter())),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        match self.into_scalar()?.as_str() {
+            "true" | "1" | "on" => visitor.visit_bool(true),
+            "false" | "0" | "off" => visitor.visit_bool(false),
+            other => Err(QueryError(format!("cannot parse `{}` as a bool", other))),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        visitor.visit_string(self.into_scalar()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        visitor.visit_string(self.into_scalar()?)
+    }
+
+    /// An empty value (`?page=`) reads as `None`
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        match &self {
+            Node::Value(value) if value.is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, QueryError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// A single value reads as a one-item list, `a[0]=x&a[1]=y` as a list in index order
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        let items = match self {
+            Node::List(items) => items,
+            Node::Value(value) => vec![Node::Value(value)],
+            Node::Map(fields) => {
+                let mut indexed = fields
+                    .into_iter()
+                    .map(|(key, node)| key.parse::<usize>().map(|index| (index, node)))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| QueryError("expected a list, found nested fields".into()))?;
+                indexed.sort_by_key(|(index, _)| *index);
+                indexed.into_iter().map(|(_, node)| node).collect()
+            }
+        };
+        visitor.visit_seq(SeqDeserializer::new(items.into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, QueryError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        match self {
+            Node::Map(fields) => visitor.visit_map(MapDeserializer::new(fields.into_iter())),
+            _ => Err(QueryError("expected nested fields such as `name[field]=value`".into())),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, QueryError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, QueryError> {
+        let variant: StringDeserializer<QueryError> = self.into_scalar()?.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, QueryError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Sort {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Range {
+        min: u32,
+        max: Option<u32>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        q: String,
+        #[serde(default)]
+        tag: Vec<String>,
+        page: Option<u32>,
+        sort: Option<Sort>,
+        exact: Option<bool>,
+        price: Option<Range>,
+    }
+
+    #[test]
+    fn repeated_and_bracketed_keys_become_lists() {
+        let search: Search = from_query_str("q=rust&tag=web&tag=async").unwrap();
+        assert_eq!(search.tag, ["web", "async"]);
+
+        let search: Search = from_query_str("q=rust&tag[]=web&tag[]=async").unwrap();
+        assert_eq!(search.tag, ["web", "async"]);
+
+        let search: Search = from_query_str("q=rust&tag=solo").unwrap();
+        assert_eq!(search.tag, ["solo"]);
+
+        let search: Search = from_query_str("q=rust&tag[1]=b&tag[0]=a").unwrap();
+        assert_eq!(search.tag, ["a", "b"]);
+    }
+
+    #[test]
+    fn values_are_typed_and_nested() {
+        let search: Search =
+            from_query_str("q=hello+world&page=2&sort=desc&exact=true&price[min]=10&price[max]=").unwrap();
+        assert_eq!(
+            search,
+            Search {
+                q: "hello world".into(),
+                tag: vec![],
+                page: Some(2),
+                sort: Some(Sort::Desc),
+                exact: Some(true),
+                price: Some(Range { min: 10, max: None }),
+            }
+        );
+
+        let map: HashMap<String, String> = from_query_str("a=1&b=%C3%A9&a=3").unwrap();
+        assert_eq!(map["a"], "3");
+        assert_eq!(map["b"], "é");
+    }
+
+    #[test]
+    fn errors_name_the_failing_field() {
+        let message = |raw: &str| match from_query_str::<Search>(raw) {
+            Err(Error::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other.map(|_| ())),
+        };
+        assert_eq!(message("page=2"), "Invalid query: missing field `q`");
+        assert!(message("q=x&page=two").starts_with("Invalid query parameter `page`: cannot parse `two`"));
+        assert!(message("q=x&price[min]=low").starts_with("Invalid query parameter `price.min`"));
+        assert!(message("q=x&sort=sideways").starts_with("Invalid query parameter `sort`: unknown variant"));
+    }
+
+    #[test]
+    fn values_of_collects_every_occurrence() {
+        assert_eq!(values_of("tag=a&x=1&tag=b&tag[]=c", "tag"), ["a", "b", "c"]);
+        assert!(values_of("x=1", "tag").is_empty());
+    }
+}
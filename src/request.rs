@@ -4,6 +4,9 @@ use std::sync::Arc;
 use dashmap::DashMap;
 use ahash::AHashMap;
 
+use crate::body::{BodyStream, PendingBody};
+use crate::headers::HeaderMap;
+
 #[derive(Debug, Clone)]
 pub enum Method {
     GET,
@@ -27,14 +30,39 @@ pub enum Version {
 #[derive(Debug, Clone)]
 pub struct Uri {
     pub path: String,
+    /// Decoded query params; a repeated key keeps its last value (see `Request::query_all`)
     pub query: Option<HashMap<String, String>>,
+    /// The query string as sent, without the leading `?`
+    pub raw_query: Option<String>,
 }
 
 impl Uri {
+    /// Build a URI from a path and decoded query params
+    ///
+    /// A map has no order, so `raw_query` lists the params sorted by key;
+    /// use `with_raw_query` to keep the order a client sent.
     pub fn new(path: &str, query: Option<HashMap<String, String>>) -> Self {
+        let raw_query = query.as_ref().map(|query| {
+            let mut pairs: Vec<_> = query.iter().collect();
+            pairs.sort();
+            form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish()
+        });
         Uri {
             path: String::from(path),
             query,
+            raw_query,
+        }
+    }
+
+    /// Build a URI from a path and a raw query string (without the leading `?`)
+    pub fn with_raw_query(path: &str, raw_query: Option<&str>) -> Self {
+        let raw_query = raw_query.filter(|raw| !raw.is_empty());
+        Uri {
+            path: String::from(path),
+            query: raw_query.map(|raw| form_urlencoded::parse(raw.as_bytes()).into_owned().collect()),
+            raw_query: raw_query.map(String::from),
         }
     }
 }
@@ -70,6 +98,21 @@ impl Context {
             })
     }
 
+    /// Copy every entry into a new, independent context (values stay shared)
+    pub(crate) fn fork(&self) -> Context {
+        let data = DashMap::with_capacity(self.data.len());
+        for entry in self.data.iter() {
+            data.insert(*entry.key(), Arc::clone(entry.value()));
+        }
+        Self {
+            data: Arc::new(data),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     /// Get value as owned (requires Clone) - for backwards compatibility
     pub fn get_cloned<T: Any + Send + Sync + Clone>(&self) -> Option<T> {
         self.data.get(&TypeId::of::<T>())
@@ -81,6 +124,12 @@ impl Context {
     }
 }
 
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl std::fmt::Debug for Context {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Context")
@@ -89,16 +138,75 @@ impl std::fmt::Debug for Context {
     }
 }
 
+/// Address of the peer that sent a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteAddr {
+    /// TCP peer
+    Ip(std::net::SocketAddr),
+    /// Peer on a Unix domain socket, with its credentials where the OS reports them
+    Unix(Option<PeerCred>),
+}
+
+/// Credentials of the process on the other end of a Unix domain socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCred {
+    pub uid: u32,
+    pub gid: u32,
+    /// Not available on every platform
+    pub pid: Option<i32>,
+}
+
+impl RemoteAddr {
+    /// IP address of a TCP peer
+    pub fn ip(&self) -> Option<std::net::IpAddr> {
+        self.socket_addr().map(|addr| addr.ip())
+    }
+
+    pub fn socket_addr(&self) -> Option<std::net::SocketAddr> {
+        match self {
+            RemoteAddr::Ip(addr) => Some(*addr),
+            RemoteAddr::Unix(_) => None,
+        }
+    }
+
+    pub fn peer_cred(&self) -> Option<PeerCred> {
+        match self {
+            RemoteAddr::Ip(_) => None,
+            RemoteAddr::Unix(cred) => *cred,
+        }
+    }
+}
+
+impl From<std::net::SocketAddr> for RemoteAddr {
+    fn from(addr: std::net::SocketAddr) -> Self {
+        RemoteAddr::Ip(addr)
+    }
+}
+
+impl std::fmt::Display for RemoteAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteAddr::Ip(addr) => addr.fmt(f),
+            RemoteAddr::Unix(None) => f.write_str("unix"),
+            RemoteAddr::Unix(Some(cred)) => match cred.pid {
+                Some(pid) => write!(f, "unix(uid={}, pid={})", cred.uid, pid),
+                None => write!(f, "unix(uid={})", cred.uid),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Request {
     pub method: Method,
     pub uri: Uri,
     pub version: Version,
-    pub headers: AHashMap<String, Vec<String>>,
+    pub headers: HeaderMap,
     pub body: Vec<u8>,
-    pub remote_addr: Option<std::net::SocketAddr>,
+    pub remote_addr: Option<RemoteAddr>,
     pub params: AHashMap<String, String>,
     pub context: Context,
+    pub(crate) pending_body: PendingBody,
 }
 
 impl Request {
@@ -106,22 +214,55 @@ impl Request {
         method: Method,
         uri: Uri,
         version: Version,
-        headers: AHashMap<String, Vec<String>>,
+        headers: impl Into<HeaderMap>,
         body: Vec<u8>,
-        remote_addr: Option<std::net::SocketAddr>,
+        remote_addr: Option<RemoteAddr>,
     ) -> Self {
         Request {
             method,
             uri,
             version,
-            headers,
+            headers: headers.into(),
             body,
             remote_addr,
             params: AHashMap::new(),
             context: Context::new(),
+            pending_body: PendingBody::default(),
         }
     }
     
+    /// Copy of the request line, headers, params and context without the body.
+    ///
+    /// The context is shared, so values inserted by the handler stay visible.
+    pub(crate) fn without_body(&self) -> Request {
+        Request {
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            version: self.version.clone(),
+            headers: self.headers.clone(),
+            body: Vec::new(),
+            remote_addr: self.remote_addr,
+            params: self.params.clone(),
+            context: self.context.clone(),
+            pending_body: PendingBody::default(),
+        }
+    }
+
+    /// Take the request body as a stream
+    ///
+    /// On streaming routes this reads from the connection; otherwise it
+    /// drains the buffered `body`. Either way it can only be taken once.
+    pub fn take_body_stream(&mut self) -> BodyStream {
+        self.pending_body
+            .0
+            .take()
+            .unwrap_or_else(|| BodyStream::buffered(std::mem::take(&mut self.body)))
+    }
+
+    pub(crate) fn set_body_stream(&mut self, body: BodyStream) {
+        self.pending_body = PendingBody(Some(body));
+    }
+
     /// Insert a value into the request context
     pub fn set_context<T: Any + Send + Sync>(&mut self, value: T) {
         self.context.insert(value);
@@ -150,20 +291,29 @@ impl Request {
         self.params.get(name)?.parse().ok()
     }
     
-    /// Get a header value by name (returns first value if multiple exist)
+    /// Get a header value by name, case-insensitively (returns first value if multiple exist)
     pub fn header(&self, name: &str) -> Option<&str> {
-        self.headers.get(name)?.first().map(|s| s.as_str())
+        self.headers.get(name).map(|s| s.as_str())
     }
     
-    /// Get all header values by name
-    pub fn header_all(&self, name: &str) -> Option<&Vec<String>> {
-        self.headers.get(name)
+    /// Get all header values by name, in the order they were sent
+    pub fn header_all(&self, name: &str) -> Vec<&str> {
+        self.headers.get_all(name).map(|s| s.as_str()).collect()
     }
     
     /// Get query parameter from URI (if query is parsed)
     pub fn query(&self, name: &str) -> Option<&String> {
         self.uri.query.as_ref()?.get(name)
     }
+
+    /// Get every value of a query parameter, in order (`?tag=a&tag=b` or `?tag[]=a&tag[]=b`)
+    pub fn query_all(&self, name: &str) -> Vec<String> {
+        self.uri
+            .raw_query
+            .as_deref()
+            .map(|raw| crate::query::values_of(raw, name))
+            .unwrap_or_default()
+    }
     
     /// Get cookie by name
     /// 
@@ -174,16 +324,12 @@ impl Request {
     /// ```
     pub fn cookie(&self, name: &str) -> Option<String> {
         self.headers
-            .get("cookie")
-            .or_else(|| self.headers.get("Cookie"))
-            .and_then(|cookies| {
-                cookies.iter()
-                    .flat_map(|s| s.split(';'))
-                    .find_map(|pair| {
-                        let pair = pair.trim();
-                        let (k, v) = pair.split_once('=')?;
-                        (k.trim() == name).then(|| v.trim().to_string())
-                    })
+            .get_all("cookie")
+            .flat_map(|s| s.split(';'))
+            .find_map(|pair| {
+                let pair = pair.trim();
+                let (k, v) = pair.split_once('=')?;
+                (k.trim() == name).then(|| v.trim().to_string())
             })
     }
     
@@ -191,12 +337,10 @@ impl Request {
     pub fn cookies(&self) -> std::collections::HashMap<String, String> {
         let mut result = std::collections::HashMap::new();
         
-        if let Some(cookie_headers) = self.headers.get("cookie").or_else(|| self.headers.get("Cookie")) {
-            for cookie_header in cookie_headers {
-                for pair in cookie_header.split(';') {
-                    if let Some((k, v)) = pair.trim().split_once('=') {
-                        result.insert(k.trim().to_string(), v.trim().to_string());
-                    }
+        for cookie_header in self.headers.get_all("cookie") {
+            for pair in cookie_header.split(';') {
+                if let Some((k, v)) = pair.trim().split_once('=') {
+                    result.insert(k.trim().to_string(), v.trim().to_string());
                 }
             }
         }
@@ -214,3 +358,21 @@ impl Request {
         std::str::from_utf8(&self.body)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_new_serializes_the_query_in_key_order() {
+        let query: HashMap<String, String> = [("z", "1"), ("a", "x y"), ("m", "&")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let uri = Uri::new("/search", Some(query));
+        assert_eq!(uri.raw_query.as_deref(), Some("a=x+y&m=%26&z=1"));
+
+        let uri = Uri::with_raw_query("/search", Some("z=1&a=2"));
+        assert_eq!(uri.raw_query.as_deref(), Some("z=1&a=2"));
+    }
+}
@@ -1,53 +1,9 @@
+use crate::headers::HeaderMap;
 use crate::Version;
-use std::collections::HashMap;
 use std::pin::Pin;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum StatusCode {
-    Ok,
-    Created,
-    NoContent,
-    Found,
-    BadRequest,
-    Unauthorized,
-    Forbidden,
-    NotFound,
-    InternalServerError,
-    Custom(u16, String),
-}
-
-impl StatusCode {
-    pub fn as_str(&self) -> String {
-        match self {
-            StatusCode::Ok => "200 OK".to_string(),
-            StatusCode::Created => "201 Created".to_string(),
-            StatusCode::NoContent => "204 No Content".to_string(),
-            StatusCode::Found => "302 Found".to_string(),
-            StatusCode::BadRequest => "400 Bad Request".to_string(),
-            StatusCode::Unauthorized => "401 Unauthorized".to_string(),
-            StatusCode::Forbidden => "403 Forbidden".to_string(),
-            StatusCode::NotFound => "404 Not Found".to_string(),
-            StatusCode::InternalServerError => "500 Internal Server Error".to_string(),
-            StatusCode::Custom(code, text) => format!("{code} {text}"),
-        }
-    }
-
-    pub fn code(&self) -> u16 {
-        match self {
-            StatusCode::Ok => 200,
-            StatusCode::Created => 201,
-            StatusCode::NoContent => 204,
-            StatusCode::Found => 302,
-            StatusCode::BadRequest => 400,
-            StatusCode::Unauthorized => 401,
-            StatusCode::Forbidden => 403,
-            StatusCode::NotFound => 404,
-            StatusCode::InternalServerError => 500,
-            StatusCode::Custom(code, _) => *code,
-        }
-    }
-}
+pub use crate::status::StatusCode;
 
 pub enum ResponseBody {
     Static(Vec<u8>),
@@ -82,8 +38,10 @@ impl std::fmt::Debug for ResponseBody {
 pub struct Response {
     pub version: Version,
     pub status: StatusCode,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body: ResponseBody,
+    /// The error this response was rendered from, for the server's error handler
+    pub(crate) error: Option<Box<crate::Error>>,
 }
 
 impl std::fmt::Debug for Response {
@@ -100,7 +58,7 @@ impl std::fmt::Debug for Response {
 impl Response {
     pub fn new(status: StatusCode, body: impl Into<Vec<u8>>) -> Self {
         let body = body.into();
-        let mut headers = HashMap::new();
+        let mut headers = HeaderMap::new();
         headers.insert("Content-Length".into(), body.len().to_string());
         headers.insert("Connection".into(), "close".into());
 
@@ -109,6 +67,7 @@ impl Response {
             status,
             headers,
             body: ResponseBody::Static(body),
+            error: None,
         }
     }
 
@@ -116,7 +75,7 @@ impl Response {
     where
         R: AsyncRead + Send + 'static,
     {
-        let mut headers = HashMap::new();
+        let mut headers = HeaderMap::new();
         headers.insert("Transfer-Encoding".into(), "chunked".into());
         headers.insert("Connection".into(), "close".into());
 
@@ -125,6 +84,7 @@ impl Response {
             status,
             headers,
             body: ResponseBody::Stream(Box::pin(reader)),
+            error: None,
         }
     }
 
@@ -245,10 +205,8 @@ impl Response {
     /// response.set_cookie(cookie);
     /// ```
     pub fn set_cookie(&mut self, cookie: crate::Cookie) {
-        self.headers
-            .entry("Set-Cookie".to_string())
-            .or_insert_with(String::new)
-            .push_str(&cookie.to_header_value());
+        // Each cookie needs its own Set-Cookie line
+        self.headers.append("Set-Cookie".into(), cookie.to_header_value());
     }
     
     /// Delete a cookie by setting it to expire immediately
@@ -1,20 +1,94 @@
 use std::sync::Arc;
 use ahash::AHashMap;
 
-use crate::perfect_hash_router::{PerfectHashRouter, RouterStats};
+use crate::host::HostPattern;
+use crate::perfect_hash_router::{PerfectHashRouter, RouteMatch, RouterStats};
 use crate::AsyncHandler;
 use crate::Method;
 
 type HandlerBox = Arc<dyn AsyncHandler>;
 
+/// Renders an `Error` that became a response, given the request it answers
+pub type ErrorHandler = Arc<dyn Fn(crate::Error, &crate::Request) -> crate::Response + Send + Sync>;
+
+/// Routes served only on hosts matching `pattern`
+struct VirtualHost {
+    pattern: HostPattern,
+    routes: PerfectHashRouter,
+}
+
 pub struct Router {
     inner: PerfectHashRouter,
+    /// Virtual hosts in priority order; requests no pattern matches use `inner`
+    hosts: Vec<VirtualHost>,
+    fallback: Option<HandlerBox>,
+    not_found: Option<HandlerBox>,
+    error_handler: Option<ErrorHandler>,
+    paths: crate::PathConfig,
+    /// Route names for `url_for`, name -> path
+    names: crate::url::RouteNames,
 }
 
 impl Router {
     pub fn new() -> Self {
         Self {
             inner: PerfectHashRouter::new(),
+            hosts: Vec::new(),
+            fallback: None,
+            not_found: None,
+            error_handler: None,
+            paths: crate::PathConfig::default(),
+            names: Default::default(),
+        }
+    }
+
+    /// Handler for requests no route matches, under any method
+    pub fn set_fallback(&mut self, handler: Box<dyn AsyncHandler>) {
+        self.fallback = Some(Arc::from(handler));
+    }
+
+    /// Handler rendering the 404 answer when no route (or fallback) matches
+    pub fn set_not_found(&mut self, handler: Box<dyn AsyncHandler>) {
+        self.not_found = Some(Arc::from(handler));
+    }
+
+    /// Renderer for every `Error` turned into a response
+    pub fn set_error_handler(&mut self, handler: ErrorHandler) {
+        self.error_handler = Some(handler);
+    }
+
+    /// How request paths are canonicalized before they reach this router
+    pub fn set_path_config(&mut self, paths: crate::PathConfig) {
+        self.paths = paths;
+    }
+
+    pub(crate) fn path_config(&self) -> &crate::PathConfig {
+        &self.paths
+    }
+
+    pub(crate) fn fallback(&self) -> Option<&HandlerBox> {
+        self.fallback.as_ref()
+    }
+
+    pub(crate) fn not_found(&self) -> Option<&HandlerBox> {
+        self.not_found.as_ref()
+    }
+
+    pub(crate) fn renders_errors(&self) -> bool {
+        self.error_handler.is_some()
+    }
+
+    /// Render `error` with the error handler, if one is set
+    pub(crate) fn error_response(&self, error: crate::Error, request: &crate::Request) -> Option<crate::Response> {
+        self.error_handler.as_ref().map(|handler| handler(error, request))
+    }
+
+    /// Re-render `response` with the error handler when it came from an `Error`
+    pub(crate) fn render_error(&self, request: &crate::Request, response: &mut crate::Response) {
+        if let Some(handler) = &self.error_handler {
+            if let Some(error) = response.error.take() {
+                *response = handler(*error, request);
+            }
         }
     }
 
@@ -22,8 +96,95 @@ impl Router {
         self.inner.add_route(method, path, handler);
     }
 
+    /// Add a route served only on hosts matching `host` (`api.example.com`, `*.example.com`)
+    ///
+    /// Panics on an invalid host pattern.
+    pub fn add_host_route(&mut self, host: &str, method: &str, path: &str, handler: Box<dyn AsyncHandler>) {
+        self.host_routes(host).add_route(method, path, handler);
+    }
+
+    /// Name the route at `path` for `url_for`
+    ///
+    /// Several methods of one path may share a name. Panics when `name`
+    /// already names another path, which Light Guard reports as `LG016` for
+    /// macro routes.
+    pub fn add_route_name(&mut self, name: &str, path: &str) {
+        let same_path = |a: &str, b: &str| a.trim_end_matches('/') == b.trim_end_matches('/');
+        match self.names.get(name) {
+            Some(existing) if !same_path(existing, path) => {
+                panic!("route name '{}' is used by both '{}' and '{}'", name, existing, path)
+            }
+            Some(_) => {}
+            None => {
+                Arc::make_mut(&mut self.names).insert(name.to_string(), path.to_string());
+            }
+        }
+    }
+
+    /// URL of the route named `name` on this router; see `url_for`
+    pub fn url_for<K, V>(
+        &self,
+        name: &str,
+        params: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<String, crate::UrlError>
+    where
+        K: AsRef<str>,
+        V: std::fmt::Display,
+    {
+        let path = self
+            .names
+            .get(name)
+            .ok_or_else(|| crate::UrlError::UnknownRoute(name.to_string()))?;
+        crate::url::fill(name, path, params)
+    }
+
+    /// Names `url_for` sees while this router serves a request, if it has any
+    pub(crate) fn route_names(&self) -> Option<&crate::url::RouteNames> {
+        (!self.names.is_empty()).then_some(&self.names)
+    }
+
     pub fn add_route_info(&mut self, route: &crate::RouteInfo) {
-        self.inner.add_route_info(route);
+        if let Some(name) = route.name {
+            self.add_route_name(name, route.path);
+        }
+        match route.host {
+            Some(host) => self.host_routes(host).add_route_info(route),
+            None => self.inner.add_route_info(route),
+        }
+    }
+
+    fn host_routes(&mut self, host: &str) -> &mut PerfectHashRouter {
+        let pattern = HostPattern::parse(host);
+        let index = match self.hosts.iter().position(|vhost| vhost.pattern.source == pattern.source) {
+            Some(index) => index,
+            None => {
+                // Equally specific patterns keep their registration order
+                let index = self
+                    .hosts
+                    .partition_point(|vhost| vhost.pattern.priority() <= pattern.priority());
+                self.hosts.insert(
+                    index,
+                    VirtualHost {
+                        pattern,
+                        routes: PerfectHashRouter::new(),
+                    },
+                );
+                index
+            }
+        };
+        &mut self.hosts[index].routes
+    }
+
+    /// Routes serving `host`, with the params its pattern captured
+    fn routes_for(&self, host: Option<&str>) -> (&PerfectHashRouter, Option<AHashMap<String, String>>) {
+        if let Some(host) = host {
+            for vhost in &self.hosts {
+                if let Some(captures) = vhost.pattern.matches(host) {
+                    return (&vhost.routes, Some(captures));
+                }
+            }
+        }
+        (&self.inner, None)
     }
 
     pub fn add_routes_info_sorted(&mut self, routes: &[crate::RouteInfo]) {
@@ -36,7 +197,7 @@ impl Router {
         });
 
         for route in sorted {
-            self.inner.add_route_info(route);
+            self.add_route_info(route);
         }
     }
 
@@ -44,6 +205,37 @@ impl Router {
         self.inner.find(method, path)
     }
 
+    /// Route a request, telling an unknown path apart from a method it does not allow
+    pub fn route(&self, method: &Method, path: &str) -> RouteMatch {
+        self.inner.route(method, path)
+    }
+
+    /// Route a request by its `Host` header, then by its path
+    ///
+    /// A host matching a virtual host is only served that host's routes;
+    /// any other host (or none) is served the default routes. Params
+    /// captured from the host join the path params, which win on a clash.
+    pub fn route_for_host(&self, host: Option<&str>, method: &Method, path: &str) -> RouteMatch {
+        let (routes, captures) = self.routes_for(host);
+        match routes.route(method, path) {
+            RouteMatch::Found(handler, mut params) => {
+                for (name, value) in captures.into_iter().flatten() {
+                    params.entry(name).or_insert(value);
+                }
+                RouteMatch::Found(handler, params)
+            }
+            other => other,
+        }
+    }
+
+    /// Whether the route matching `host`, `method` and `path` wants its body streamed
+    pub(crate) fn streams_body(&self, host: Option<&str>, method: &Method, path: &str) -> bool {
+        self.routes_for(host)
+            .0
+            .find(method, path)
+            .is_some_and(|(handler, _)| handler.streams_body())
+    }
+
     pub fn stats(&self) -> RouterStats {
         self.inner.stats()
     }
@@ -1,27 +1,149 @@
-use bytes::{BufMut, BytesMut};
-use std::collections::HashMap;
+use bytes::{Buf, BufMut, BytesMut};
+use smallvec::SmallVec;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use ahash::AHashMap;
+use crate::headers::HeaderMap;
 
-use crate::response::ResponseBody;
+use crate::body::{BodySender, BodyStream};
+use crate::chunked::{ChunkedDecoder, Decoded};
+use crate::request::{Context, PeerCred, RemoteAddr};
+use crate::shutdown::ShutdownHandle;
+use crate::response::{ResponseBody, StatusCode};
 use crate::{
-    AsyncHandler, AsyncMiddleware, Flow, Method, Middleware, Request, Response, Router, Uri,
-    Version,
+    AsyncHandler, AsyncMiddleware, Flow, Method, Middleware, Request, Response, RouteMatch,
+    AcceptedStream, Router, StreamAction, Uri, Version,
 };
 
 // Thread-local buffer pool for zero contention
 use std::cell::RefCell;
 use memchr;
 
-const BUFFER_SIZE: usize = 8192;
+pub(crate) const BUFFER_SIZE: usize = 8192;
 const MAX_POOLED_BUFFERS_PER_THREAD: usize = 64; // Increased from 32
 const LARGE_BUFFER_SIZE: usize = 65536; // 64KB for large requests
 const MAX_LARGE_BUFFERS: usize = 8;
 #[cfg(feature = "http2")]
 const HTTP2_PREFACE: &[u8; 24] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
 
+/// Byte stream a connection is served over: plain TCP or TLS
+pub(crate) trait Io: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Io for T {}
+
+/// Byte stream HTTP/1.1 is served over
+///
+/// Unlike `Io` it need not be `Send`: io_uring sockets stay on the worker
+/// thread that accepted them.
+pub(crate) trait Http1Io: AsyncRead + AsyncWrite + Unpin + 'static {
+    /// Stream a WebSocket takes over after the upgrade response
+    fn into_websocket(self) -> std::io::Result<impl Io>;
+}
+
+impl Http1Io for TcpStream {
+    fn into_websocket(self) -> std::io::Result<impl Io> {
+        Ok(self)
+    }
+}
+
+#[cfg(unix)]
+impl Http1Io for tokio::net::UnixStream {
+    fn into_websocket(self) -> std::io::Result<impl Io> {
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<S: Io> Http1Io for tokio_rustls::server::TlsStream<S> {
+    fn into_websocket(self) -> std::io::Result<impl Io> {
+        Ok(self)
+    }
+}
+
+/// Plaintext stream accepted by a listener
+#[cfg_attr(not(feature = "http2"), allow(dead_code))]
+pub(crate) trait Connection: Io + Http1Io {
+    /// Read the next bytes without consuming them (used to spot the HTTP/2 preface)
+    fn peek(&self, buf: &mut [u8]) -> impl std::future::Future<Output = std::io::Result<usize>> + Send;
+
+    fn readable(&self) -> impl std::future::Future<Output = std::io::Result<()>> + Send;
+}
+
+impl Connection for TcpStream {
+    fn peek(&self, buf: &mut [u8]) -> impl std::future::Future<Output = std::io::Result<usize>> + Send {
+        TcpStream::peek(self, buf)
+    }
+
+    fn readable(&self) -> impl std::future::Future<Output = std::io::Result<()>> + Send {
+        TcpStream::readable(self)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for tokio::net::UnixStream {
+    async fn peek(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use std::os::fd::AsRawFd;
+
+        self.async_io(tokio::io::Interest::READABLE, || {
+            let read = unsafe {
+                libc::recv(
+                    self.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    libc::MSG_PEEK,
+                )
+            };
+            if read < 0 {
+                Err(std::io::Error::last_os_error())
+            } else {
+                Ok(read as usize)
+            }
+        })
+        .await
+    }
+
+    fn readable(&self) -> impl std::future::Future<Output = std::io::Result<()>> + Send {
+        tokio::net::UnixStream::readable(self)
+    }
+}
+
+/// Connection and request deadlines from `ServerConfig`, and the buffered body limit (`None` = no limit)
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Timeouts {
+    pub(crate) header: Option<Duration>,
+    pub(crate) body: Option<Duration>,
+    pub(crate) keep_alive: Option<Duration>,
+    pub(crate) handler: Option<Duration>,
+    pub(crate) max_body: Option<usize>,
+}
+
+impl Timeouts {
+    pub(crate) fn from_config(config: &crate::ServerConfig) -> Self {
+        let secs = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        Self {
+            header: secs(config.header_timeout),
+            body: secs(config.body_timeout),
+            keep_alive: secs(config.keepalive_timeout),
+            handler: secs(config.request_timeout),
+            max_body: (config.max_body_size > 0).then_some(config.max_body_size),
+        }
+    }
+}
+
+/// Await `fut` until `deadline`; `None` means it expired first
+async fn within<F: std::future::Future>(deadline: Option<Instant>, fut: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+fn deadline_after(limit: Option<Duration>) -> Option<Instant> {
+    limit.map(|limit| Instant::now() + limit)
+}
+
 // Multi-tier buffer pool for different request sizes
 thread_local! {
     static BUFFER_POOL: RefCell<Vec<BytesMut>> = RefCell::new(Vec::with_capacity(MAX_POOLED_BUFFERS_PER_THREAD));
@@ -65,48 +187,13 @@ fn return_buffer(mut buf: BytesMut) {
     }
 }
 
-async fn check_port_availability(addr: &str) {
-    let port = addr.split(':').last().unwrap_or("8080");
-    
-    #[cfg(unix)]
-    {
-        let output = tokio::process::Command::new("lsof")
-            .arg("-i")
-            .arg(format!(":{}", port))
-            .arg("-P")
-            .arg("-n")
-            .output()
-            .await;
-
-        if let Ok(output) = output {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let lines: Vec<&str> = stdout.lines().collect();
-            
-            if lines.len() > 1 {
-                println!("\n⚠️  [WARNING] Port {} is already in use by other process(es):", port);
-                println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-                for line in &lines {
-                    println!("{}", line);
-                }
-                println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-                println!("\n🔴 Due to SO_REUSEPORT being enabled, the server will bind successfully");
-                println!("   but traffic may be distributed between multiple processes!");
-                println!("\n💡 To fix this, kill the existing process(es) using:");
-                println!("   kill -9 <PID>  (replace <PID> with the process ID from above)\n");
-                
-                // Give user time to read the warning
-                tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-            }
-        }
-    }
-}
-
 pub struct Server {
     pub(crate) router: Router,
     pub(crate) middlewares: Vec<Middleware>,
     pub(crate) async_middlewares: Vec<AsyncMiddleware>,
     prefix: String,
     ws_routes: std::collections::HashMap<String, Arc<dyn crate::websocket::WebSocketHandler>>,
+    shutdown: ShutdownHandle,
 }
 
 impl Server {
@@ -117,9 +204,15 @@ impl Server {
             async_middlewares: Vec::new(),
             prefix: String::new(),
             ws_routes: std::collections::HashMap::new(),
+            shutdown: ShutdownHandle::new(),
         }
     }
 
+    /// Handle for shutting the server down gracefully without a signal
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
     /// Establece un prefijo global para todas las rutas
     pub fn prefix(mut self, prefix: &str) -> Self {
         self.prefix = prefix.trim_end_matches('/').to_string();
@@ -139,6 +232,53 @@ impl Server {
         self
     }
 
+    /// Like `route`, naming it for `url_for`
+    ///
+    /// The name refers to the full path, including the server prefix. Panics
+    /// when the name is already given to another path.
+    pub fn named_route<H>(mut self, name: &str, method: &str, path: &str, handler: H) -> Self
+    where
+        H: AsyncHandler + 'static,
+    {
+        self.router.add_route_name(name, &format!("{}{}", self.prefix, path));
+        self.route(method, path, handler)
+    }
+
+    /// URL of a route named on this server; see `url_for`
+    pub fn url_for<K, V>(
+        &self,
+        name: &str,
+        params: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<String, crate::UrlError>
+    where
+        K: AsRef<str>,
+        V: std::fmt::Display,
+    {
+        self.router.url_for(name, params)
+    }
+
+    /// Serve the routes added in `configurator` only on hosts matching `pattern`
+    ///
+    /// Patterns are exact (`api.example.com`), capture a label
+    /// (`:tenant.example.com`), or start with a wildcard (`*.example.com`,
+    /// or `*sub.example.com` to capture it). Requests whose host matches no
+    /// pattern are served the routes added directly on the server.
+    pub fn host<F>(mut self, pattern: &str, configurator: F) -> Self
+    where
+        F: FnOnce(ServerScope) -> ServerScope,
+    {
+        let scope = ServerScope {
+            prefix: String::new(),
+            routes: Vec::new(),
+        };
+
+        for (method, path, handler) in configurator(scope).routes {
+            self.router.add_host_route(pattern, &method, &path, handler);
+        }
+
+        self
+    }
+
     pub fn route_info(mut self, route: &crate::RouteInfo) -> Self {
         self.router.add_route_info(route);
         self
@@ -204,6 +344,36 @@ impl Server {
         self.route("DELETE", path, handler)
     }
 
+    /// Handle requests no route matches, under any method (e.g. an SPA's `index.html`)
+    pub fn fallback<H>(mut self, handler: H) -> Self
+    where
+        H: AsyncHandler + 'static,
+    {
+        self.router.set_fallback(Box::new(handler));
+        self
+    }
+
+    /// Render the 404 answer for requests no route (or fallback) matches
+    ///
+    /// The status stays 404 whatever the handler sets; it shapes the body and headers.
+    pub fn not_found<H>(mut self, handler: H) -> Self
+    where
+        H: AsyncHandler + 'static,
+    {
+        self.router.set_not_found(Box::new(handler));
+        self
+    }
+
+    /// Render every `Error` that becomes a response: handler and middleware
+    /// errors, extractor failures in macro routes, and the built-in 404/405/503
+    pub fn error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(crate::Error, &Request) -> Response + Send + Sync + 'static,
+    {
+        self.router.set_error_handler(Arc::new(handler));
+        self
+    }
+
     pub fn middleware(mut self, mw: Middleware) -> Self {
         self.middlewares.push(mw);
         self
@@ -229,19 +399,66 @@ impl Server {
     }
 
     pub async fn listen(self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.listen_on(&[addr]).await
+    }
+
+    /// Serve the same routes on several addresses, e.g. a public and an admin port
+    ///
+    /// IPv6 addresses like `[::]:8080` also accept IPv4 clients unless
+    /// `ServerConfig::ipv6_only` is set.
+    pub async fn listen_on<A: AsRef<str>>(self, addrs: &[A]) -> Result<(), Box<dyn std::error::Error>> {
+        // Load config if not already loaded
+        let server_config = crate::config::config().read().await.server.clone();
+        let listeners = bind_all(addrs, &server_config)?;
+        self.serve(listeners, server_config).await
+    }
+
+    /// Start server using configuration from Firework.toml
+    ///
+    /// Under systemd socket activation (`LISTEN_FDS`/`LISTEN_PID`) the inherited
+    /// sockets are served instead of binding `address:port`.
+    pub async fn listen_with_config(self) -> Result<(), Box<dyn std::error::Error>> {
+        let config = crate::config::get_config().await;
+
+        #[cfg(unix)]
+        {
+            let inherited = crate::listen_fds::take();
+            if !inherited.is_empty() {
+                let listeners = inherited
+                    .into_iter()
+                    .map(Listener::adopt)
+                    .collect::<std::io::Result<Vec<_>>>()?;
+                return self.serve(listeners, config.server.clone()).await;
+            }
+        }
+
+        let listeners = bind_all(&[config.bind_address()], &config.server)?;
+        self.serve(listeners, config.server.clone()).await
+    }
+
+    async fn serve(
+        self,
+        listeners: Vec<(Listener, String)>,
+        server_config: crate::ServerConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         // Configure stdout/stderr to be unbuffered for immediate output in async contexts
         use std::io::Write;
         let _ = std::io::stdout().flush();
         let _ = std::io::stderr().flush();
-        
-        let router = Arc::new(self.router);
-        let middlewares = Arc::new(self.middlewares);
-        let async_middlewares = Arc::new(self.async_middlewares);
-        
-        let ws_routes = Arc::new(self.ws_routes);
 
-        // Load config if not already loaded
-        let _ = crate::config::config();
+        if listeners.is_empty() {
+            return Err("no listen address given".into());
+        }
+
+        #[cfg(feature = "tls")]
+        let tls = match &server_config.tls {
+            Some(tls_config) => Some(Arc::new(crate::tls::TlsAcceptor::load(tls_config)?)),
+            None => None,
+        };
+        #[cfg(not(feature = "tls"))]
+        if server_config.tls.is_some() {
+            return Err("[server.tls] is set but firework was built without the `tls` feature".into());
+        }
 
         // Initialize plugins ONCE and cache them to avoid locking on every request
         let plugin_registry = crate::plugin::registry();
@@ -250,142 +467,122 @@ impl Server {
             registry.init_all().await?;
             registry.start_all().await?;
             // Cache plugins to avoid RwLock on every request
-            Arc::new(registry.plugins().to_vec())
+            let mut plugins = registry.plugins().to_vec();
+            crate::plugin::sort_by_priority(&mut plugins);
+            Arc::new(plugins)
         };
 
-        // Check if port is already in use
-        check_port_availability(addr).await;
-
-        // Create listener with SO_REUSEPORT and SO_REUSEADDR for better performance
-        use socket2::{Domain, Protocol, Socket, Type};
-        use std::net::SocketAddr;
-
-        let socket_addr: SocketAddr = addr.parse()?;
-        let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
-
-        socket.set_reuse_address(true)?;
-        #[cfg(all(unix, not(target_os = "solaris"), not(target_os = "illumos")))]
-        {
-            use std::os::fd::AsRawFd;
-            unsafe {
-                let optval: libc::c_int = 1;
-                libc::setsockopt(
-                    socket.as_raw_fd(),
-                    libc::SOL_SOCKET,
-                    libc::SO_REUSEPORT,
-                    &optval as *const _ as *const libc::c_void,
-                    std::mem::size_of_val(&optval) as libc::socklen_t,
-                );
+        let mut router = self.router;
+        router.set_path_config(server_config.paths.clone());
+
+        // Every listener feeds the same router, middleware and plugins
+        let shared = Shared {
+            router: Arc::new(router),
+            middlewares: Arc::new(self.middlewares),
+            async_middlewares: Arc::new(self.async_middlewares),
+            ws_routes: Arc::new(self.ws_routes),
+            plugins,
+            shutdown: self.shutdown,
+            timeouts: Timeouts::from_config(&server_config),
+            #[cfg(feature = "tls")]
+            tls,
+        };
+        let shutdown = shared.shutdown.clone();
+
+        #[cfg(feature = "tls")]
+        let cert_watcher = shared.tls.as_ref().map(|tls| tokio::spawn(Arc::clone(tls).watch()));
+        #[cfg(feature = "tls")]
+        let scheme = if shared.tls.is_some() { "https" } else { "http" };
+        #[cfg(not(feature = "tls"))]
+        let scheme = "http";
+        for (listener, addr) in &listeners {
+            match listener {
+                Listener::Tcp(_) => println!("[SERVER] Listening on {}://{}", scheme, addr),
+                #[cfg(unix)]
+                Listener::Unix(..) => println!("[SERVER] Listening on {} ({})", addr, scheme),
             }
         }
-
-        socket.set_nonblocking(true)?;
-        socket.bind(&socket_addr.into())?;
-        socket.listen(1024)?;
-
-        let listener: std::net::TcpListener = socket.into();
-        listener.set_nonblocking(true)?;
-        let listener = TcpListener::from_std(listener)?;
-
-        println!("[SERVER] Listening on {} with SO_REUSEPORT", addr);
         println!("[SERVER] Press Ctrl+C for graceful shutdown");
 
-        // Setup graceful shutdown signal handler
-        let shutdown_signal = async {
-            #[cfg(unix)]
-            {
-                use tokio::signal::unix::{signal, SignalKind};
-                
-                let mut sigterm = signal(SignalKind::terminate())
-                    .expect("Failed to setup SIGTERM handler");
-                let mut sigint = signal(SignalKind::interrupt())
-                    .expect("Failed to setup SIGINT handler");
-                
-                tokio::select! {
-                    _ = sigterm.recv() => {
-                        println!("\n[SERVER] Received SIGTERM, shutting down gracefully...");
-                    }
-                    _ = sigint.recv() => {
-                        println!("\n[SERVER] Received SIGINT (Ctrl+C), shutting down gracefully...");
-                    }
-                }
+        let (worker_failed, mut worker_failures) = tokio::sync::mpsc::unbounded_channel();
+        let workers = match server_config.runtime {
+            crate::RuntimeMode::MultiThread => None,
+            crate::RuntimeMode::ThreadPerCore | crate::RuntimeMode::IoUring => {
+                Some(spawn_workers(&listeners, &shared, &server_config, worker_failed)?)
             }
-            
-            #[cfg(not(unix))]
-            {
-                tokio::signal::ctrl_c()
-                    .await
-                    .expect("Failed to listen for Ctrl+C");
-                println!("\n[SERVER] Received Ctrl+C, shutting down gracefully...");
+        };
+
+        let accepting = async {
+            match &workers {
+                // The workers accept on their own copies of the listeners
+                Some(_) => match worker_failures.recv().await {
+                    Some(e) => Err(e),
+                    None => std::future::pending().await,
+                },
+                None => futures_util::future::try_join_all(
+                    listeners.iter().map(|(listener, _)| accept_loop(listener, &shared)),
+                )
+                .await
+                .map(drop),
             }
         };
 
-        // Run server with graceful shutdown
+        // Run server until a signal or the shutdown handle stops it
         tokio::select! {
-            result = async {
-                loop {
-                    let (socket, remote_addr) = listener.accept().await?;
-
-                    // Disable Nagle's algorithm for lower latency
-                    let _ = socket.set_nodelay(true);
-
-                    let router = Arc::clone(&router);
-                    let middlewares = Arc::clone(&middlewares);
-                    let async_middlewares = Arc::clone(&async_middlewares);
-                    let ws_routes = Arc::clone(&ws_routes);
-                    let plugins = Arc::clone(&plugins);
-
-                    tokio::spawn(async move {
-                        let result = handle_connection(socket, router, middlewares, async_middlewares, remote_addr, ws_routes, plugins).await;
-                        
-                        if let Err(e) = result {
-                            // Check if it's an IO error and if it's a common client disconnection
-                            if let Some(io_err) = e.downcast_ref::<std::io::Error>() {
-                                use std::io::ErrorKind;
-                                match io_err.kind() {
-                                    ErrorKind::ConnectionReset | ErrorKind::BrokenPipe | ErrorKind::ConnectionAborted => {
-                                        // Client closed connection early - this is normal with HMR/fast navigation
-                                        // Silently ignore these
-                                        return;
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            // Log other errors
-                            eprintln!("[ERROR] Connection handler error: {}", e);
-                        }
-                    });
-                }
-                #[allow(unreachable_code)]
-                Ok::<(), Box<dyn std::error::Error>>(())
-            } => {
+            result = accepting => {
+                // Take the remaining workers down with the one that failed
+                shutdown.shutdown();
                 result?;
             }
-            _ = shutdown_signal => {
-                println!("[SERVER] Initiating graceful shutdown...");
-                
-                // Give active connections time to finish (max 10 seconds)
-                println!("[SERVER] Waiting for active connections to complete (max 10s)...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-                
-                // Shutdown plugins
-                let plugin_registry = crate::plugin::registry();
-                let registry = plugin_registry.read().await;
-                registry.shutdown_all().await?;
-                
-                println!("[SERVER] Shutdown complete ✅");
+            _ = shutdown_signal() => {}
+            _ = shutdown.requested() => {
+                println!("\n[SERVER] Shutdown requested, shutting down gracefully...");
+            }
+        }
+
+        // The accept loops are gone; close the listeners and tell open connections
+        drop(listeners);
+        shutdown.shutdown();
+        #[cfg(feature = "tls")]
+        if let Some(cert_watcher) = cert_watcher {
+            cert_watcher.abort();
+        }
+        println!("[SERVER] Initiating graceful shutdown...");
+
+        let deadline = Duration::from_secs(server_config.shutdown_timeout);
+        let active = shutdown.active_connections();
+        if active > 0 {
+            println!(
+                "[SERVER] Waiting for {} active connection(s) to complete (max {}s)...",
+                active,
+                deadline.as_secs()
+            );
+            if tokio::time::timeout(deadline, shutdown.drained()).await.is_err() {
+                println!(
+                    "[SERVER] Shutdown deadline reached, dropping {} connection(s)",
+                    shutdown.active_connections()
+                );
             }
         }
+        if let Some(workers) = workers {
+            // Each worker stops once drained or past the same deadline
+            let _ = tokio::task::spawn_blocking(move || {
+                for worker in workers {
+                    let _ = worker.join();
+                }
+            })
+            .await;
+        }
+
+        // Shutdown plugins
+        let registry = plugin_registry.read().await;
+        registry.shutdown_all().await?;
+
+        println!("[SERVER] Shutdown complete ✅");
 
         Ok(())
     }
 
-    /// Start server using configuration from Firework.toml
-    pub async fn listen_with_config(self) -> Result<(), Box<dyn std::error::Error>> {
-        let config = crate::config::get_config().await;
-        let addr = config.bind_address();
-        self.listen(&addr).await
-    }
 }
 
 impl Default for Server {
@@ -394,423 +591,1288 @@ impl Default for Server {
     }
 }
 
-async fn handle_connection(
-    mut socket: TcpStream,
-    router: Arc<Router>,
-    middlewares: Arc<Vec<Middleware>>,
-    async_middlewares: Arc<Vec<AsyncMiddleware>>,
-    remote_addr: std::net::SocketAddr,
-    ws_routes: Arc<std::collections::HashMap<String, Arc<dyn crate::websocket::WebSocketHandler>>>,
-    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    #[cfg(feature = "http2")]
-    if detect_http2_handshake(&socket).await? {
-        return handle_http2_connection(socket, router, middlewares, async_middlewares, remote_addr, plugins).await;
-    }
-
-    let mut read_buf = get_buffer();
+/// What every connection task needs from the server; one copy is shared by all listeners
+#[derive(Clone)]
+pub(crate) struct Shared {
+    pub(crate) router: Arc<Router>,
+    pub(crate) middlewares: Arc<Vec<Middleware>>,
+    pub(crate) async_middlewares: Arc<Vec<AsyncMiddleware>>,
+    pub(crate) ws_routes: Arc<std::collections::HashMap<String, Arc<dyn crate::websocket::WebSocketHandler>>>,
+    pub(crate) plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
+    pub(crate) shutdown: ShutdownHandle,
+    pub(crate) timeouts: Timeouts,
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<Arc<crate::tls::TlsAcceptor>>,
+}
 
+/// Accept connections on `listener` and serve each one on its own task, until accepting fails
+async fn accept_loop(listener: &Listener, shared: &Shared) -> std::io::Result<()> {
     loop {
-        // Read request data
-        read_buf.clear();
+        let (socket, remote_addr) = listener.accept().await?;
+        let shared = shared.clone();
+        let connection = shared.shutdown.track();
 
-        let mut total_read = 0;
-        loop {
-            if read_buf.len() >= BUFFER_SIZE * 2 {
-                // Request too large, reject
-                return Err("Request too large".into());
-            }
+        tokio::spawn(async move {
+            let _connection = connection;
 
-            read_buf.resize(read_buf.len() + 4096, 0);
-            match socket.read(&mut read_buf[total_read..]).await {
-                Ok(0) => {
-                    return_buffer(read_buf);
-                    return Ok(()); // Connection closed
-                }
-                Ok(n) => {
-                    total_read += n;
-                    read_buf.truncate(total_read);
-
-                    // Check if we have complete headers (look for \r\n\r\n)
-                    if let Some(pos) = find_header_end(&read_buf[..total_read]) {
-                        // Parse headers
-                        let mut headers = [httparse::EMPTY_HEADER; 64];
-                        let mut req = httparse::Request::new(&mut headers);
-
-                        match req.parse(&read_buf[..pos]) {
-                            Ok(httparse::Status::Complete(headers_len)) => {
-                                // Extract method, path, version
-                                let method = parse_method(req.method.unwrap_or("GET"));
-                                let path = req.path.unwrap_or("/");
-                                let version = parse_version(req.version.unwrap_or(1));
-
-                                // Parse headers with AHashMap (faster than std HashMap)
-                                // Pre-extract content-length and connection
-                                let mut header_map = AHashMap::with_capacity(req.headers.len());
-                                let mut content_length = 0;
-                                let mut keep_alive = version == Version::Http11;
-
-                                for header in req.headers {
-                                    let name = header.name;
-                                    let value = std::str::from_utf8(header.value).unwrap_or("");
-
-                                    // Fast path: skip common headers we don't need in map
-                                    if name.eq_ignore_ascii_case("content-length") {
-                                        content_length = value.parse().unwrap_or(0);
-                                        continue;
-                                    } else if name.eq_ignore_ascii_case("connection") {
-                                        keep_alive = value.eq_ignore_ascii_case("keep-alive");
-                                        continue;
-                                    }
-
-                                    // Intern common header names - use &'static str when possible
-                                    let name_str = intern_header_name_static(name);
-
-                                    header_map
-                                        .entry(name_str)
-                                        .or_insert_with(Vec::new)
-                                        .push(value.to_string());
-                                }
-
-                                // Read body if Content-Length > 0
-                                let body_start = headers_len;
-                                let body = if content_length > 0 {
-                                    let mut body_data = vec![0u8; content_length];
-                                    let already_read = total_read.saturating_sub(body_start);
-
-                                    if already_read > 0 {
-                                        let copy_len = already_read.min(content_length);
-                                        body_data[..copy_len].copy_from_slice(
-                                            &read_buf[body_start..body_start + copy_len],
-                                        );
-                                    }
-
-                                    if already_read < content_length {
-                                        socket.read_exact(&mut body_data[already_read..]).await?;
-                                    }
-
-                                    body_data
-                                } else {
-                                    Vec::new()
-                                };
-
-                                // Parse path and query
-                                let (path_only, query) = parse_path_and_query(path);
-                                let uri = Uri::new(path_only, query);
-
-                                // Create request (NO CLONING in hot path)
-                                let mut request = Request::new(
-                                    method,
-                                    uri,
-                                    version,
-                                    header_map,
-                                    body,
-                                    Some(remote_addr),
-                                );
-                                let mut response = Response::default();
-
-                                // Set keep-alive header early
-                                if keep_alive {
-                                    response
-                                        .headers
-                                        .insert("Connection".to_string(), "keep-alive".to_string());
-                                }
-
-                                // Execute middlewares (zero-cost - no cloning!)
-                                let mut stopped = false;
-
-                                // Sync middlewares
-                                for mw in middlewares.iter() {
-                                    match mw(&mut request, &mut response) {
-                                        Flow::Stop(final_res) => {
-                                            response = final_res;
-                                            stopped = true;
-                                            break;
-                                        }
-                                        Flow::Continue => {}
-                                    }
-                                }
-
-                                // Execute async middlewares if not stopped
-                                if !stopped {
-                                    for mw in async_middlewares.iter() {
-                                        match mw(&mut request, &mut response).await {
-                                            Flow::Stop(final_res) => {
-                                                response = final_res;
-                                                stopped = true;
-                                                break;
-                                            }
-                                            Flow::Continue => {}
-                                        }
-                                    }
-                                }
-                                
-                                // Execute plugin on_request hooks if not stopped
-                                // Uses cached plugin list - no lock needed!
-                                if !stopped {
-                                    for plugin in plugins.iter() {
-                                        match plugin.on_request(&mut request, &mut response).await {
-                                            Ok(Some(plugin_response)) => {
-                                                response = plugin_response;
-                                                stopped = true;
-                                                break;
-                                            }
-                                            Ok(None) => {}
-                                            Err(e) => {
-                                                eprintln!("[PLUGIN] Error in {}: {}", plugin.name(), e);
-                                            }
-                                        }
-                                    }
-                                }
-
-                                // Route and execute handler if not stopped
-                                if !stopped {
-                                    // Check if this is a WebSocket upgrade request
-                                    if crate::websocket::is_websocket_upgrade(&request) {
-                                        if let Some(ws_handler) = ws_routes.get(path_only) {
-                                            // Perform WebSocket handshake
-                                            if let Some(mut upgrade_response) = crate::websocket::websocket_upgrade(&request) {
-                                                // Send upgrade response
-                                                write_response(&mut socket, &mut upgrade_response, false).await?;
-                                                
-                                                // Create WebSocket and handle it
-                                                let ws = crate::websocket::WebSocket::new(socket).await;
-                                                ws_handler.call(ws).await;
-                                                
-                                                // WebSocket connection handled, return
-                                                return_buffer(read_buf);
-                                                return Ok(());
-                                            }
-                                        }
-                                    }
-                                    
-                                    // Normal HTTP request handling
-                                    if let Some((handler, params)) =
-                                        router.find(&request.method, path_only)
-                                    {
-                                        request.params = params;
-                                        response = handler.call(request, response).await;
-                                    } else {
-                                        response = Response::new(
-                                            crate::response::StatusCode::NotFound,
-                                            b"Not Found\n",
-                                        );
-                                        if keep_alive {
-                                            response.headers.insert(
-                                                "Connection".to_string(),
-                                                "keep-alive".to_string(),
-                                            );
-                                        }
-                                    }
-                                }
-
-                                // Write response
-                                write_response(&mut socket, &mut response, keep_alive).await?;
-
-                                if !keep_alive {
-                                    return_buffer(read_buf);
-                                    return Ok(());
-                                }
-
-                                // Continue to next request on same connection
-                                break;
-                            }
-                            Ok(httparse::Status::Partial) => {
-                                // Need more data
-                                continue;
-                            }
-                            Err(_) => {
-                                return_buffer(read_buf);
-                                return Err("Invalid HTTP request".into());
-                            }
-                        }
-                    }
+            let result = match socket {
+                Accepted::Tcp(mut socket) => {
+                    // Disable Nagle's algorithm for lower latency
+                    let _ = socket.set_nodelay(true);
+
+                    // Let stream-level plugins inspect, tag or reject the connection
+                    let accepted = AcceptedStream::Tcp(&mut socket);
+                    let Some(conn_context) = accept_stream(&shared.plugins, accepted, &remote_addr).await else {
+                        return;
+                    };
+                    serve_connection(socket, remote_addr, conn_context, shared).await
                 }
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    tokio::task::yield_now().await;
-                    continue;
+                #[cfg(unix)]
+                Accepted::Unix(mut socket) => {
+                    let accepted = AcceptedStream::Unix(&mut socket);
+                    let Some(conn_context) = accept_stream(&shared.plugins, accepted, &remote_addr).await else {
+                        return;
+                    };
+                    serve_connection(socket, remote_addr, conn_context, shared).await
                 }
-                Err(e) => {
-                    return_buffer(read_buf);
-                    return Err(e.into());
+            };
+            report_connection_error(result);
+        });
+    }
+}
+
+/// Log why a connection ended, unless the client simply went away
+pub(crate) fn report_connection_error(result: Result<(), Box<dyn std::error::Error>>) {
+    if let Err(e) = result {
+        // Check if it's an IO error and if it's a common client disconnection
+        if let Some(io_err) = e.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind;
+            match io_err.kind() {
+                ErrorKind::ConnectionReset | ErrorKind::BrokenPipe | ErrorKind::ConnectionAborted => {
+                    // Client closed connection early - this is normal with HMR/fast navigation
+                    // Silently ignore these
+                    return;
                 }
+                _ => {}
             }
         }
+        // Log other errors
+        eprintln!("[ERROR] Connection handler error: {}", e);
     }
 }
 
-#[cfg(feature = "http2")]
-async fn detect_http2_handshake(socket: &TcpStream) -> std::io::Result<bool> {
-    let mut probe = [0u8; HTTP2_PREFACE.len()];
-    for _ in 0..8 {
-        let read = socket.peek(&mut probe).await?;
-        if read == 0 {
-            return Ok(false);
-        }
+/// Serve an accepted connection, terminating TLS first when it is configured
+async fn serve_connection<S: Connection>(
+    socket: S,
+    remote_addr: RemoteAddr,
+    conn_context: Context,
+    shared: Shared,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Shared { router, middlewares, async_middlewares, ws_routes, plugins, shutdown, timeouts, .. } = shared;
 
-        let cmp_len = read.min(HTTP2_PREFACE.len());
-        if probe[..cmp_len] != HTTP2_PREFACE[..cmp_len] {
-            return Ok(false);
-        }
+    #[cfg(feature = "tls")]
+    if let Some(tls) = shared.tls {
+        return handle_tls_connection(socket, tls, router, middlewares, async_middlewares, remote_addr, ws_routes, plugins, conn_context, shutdown, timeouts).await;
+    }
 
-        if read >= HTTP2_PREFACE.len() {
-            return Ok(true);
-        }
+    handle_connection(socket, router, middlewares, async_middlewares, remote_addr, ws_routes, plugins, conn_context, shutdown, timeouts).await
+}
 
-        socket.readable().await?;
-    }
+/// Address accepted by `Server::listen`: `host:port` or `unix:/path/to.sock`
+#[derive(Debug, PartialEq)]
+enum ListenAddr {
+    Tcp(std::net::SocketAddr),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
 
-    Ok(false)
+impl ListenAddr {
+    fn parse(addr: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(path) = addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return match path {
+                "" => Err("unix listen address needs a socket path".into()),
+                path => Ok(ListenAddr::Unix(path.into())),
+            };
+            #[cfg(not(unix))]
+            return Err(format!("unix sockets are not supported on this platform: {}", path).into());
+        }
+        Ok(ListenAddr::Tcp(addr.parse()?))
+    }
 }
 
-#[cfg(feature = "http2")]
-async fn handle_http2_connection(
-    socket: TcpStream,
-    router: Arc<Router>,
-    middlewares: Arc<Vec<Middleware>>,
-    async_middlewares: Arc<Vec<AsyncMiddleware>>,
-    remote_addr: std::net::SocketAddr,
-    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut connection = h2::server::handshake(socket).await?;
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    /// Removes the socket file when dropped, unless it was inherited
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, Option<std::path::PathBuf>),
+}
 
-    while let Some(stream) = connection.accept().await {
-        let (request, respond) = stream?;
-        let router = Arc::clone(&router);
-        let middlewares = Arc::clone(&middlewares);
-        let async_middlewares = Arc::clone(&async_middlewares);
-        let plugins = Arc::clone(&plugins);
+enum Accepted {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
 
-        tokio::spawn(async move {
-            if let Err(err) = handle_http2_stream(
-                request,
-                respond,
-                router,
-                middlewares,
-                async_middlewares,
-                remote_addr,
-                plugins,
-            )
-            .await
-            {
-                eprintln!("[HTTP2] stream error: {err}");
+impl Listener {
+    async fn accept(&self) -> std::io::Result<(Accepted, RemoteAddr)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (socket, addr) = listener.accept().await?;
+                Ok((Accepted::Tcp(socket), RemoteAddr::Ip(addr)))
             }
-        });
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (socket, _) = listener.accept().await?;
+                let cred = socket.peer_cred().ok().map(|cred| PeerCred {
+                    uid: cred.uid(),
+                    gid: cred.gid(),
+                    pid: cred.pid(),
+                });
+                Ok((Accepted::Unix(socket), RemoteAddr::Unix(cred)))
+            }
+        }
     }
-
-    Ok(())
 }
 
-#[cfg(feature = "http2")]
-async fn handle_http2_stream(
-    request: http::Request<h2::RecvStream>,
-    mut respond: h2::server::SendResponse<bytes::Bytes>,
-    router: Arc<Router>,
-    middlewares: Arc<Vec<Middleware>>,
-    async_middlewares: Arc<Vec<AsyncMiddleware>>,
-    remote_addr: std::net::SocketAddr,
-    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let (parts, mut body_stream) = request.into_parts();
-    let mut body = Vec::new();
-    while let Some(chunk) = body_stream.data().await {
-        let chunk = chunk?;
-        body.extend_from_slice(&chunk);
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, Some(path)) = self {
+            let _ = std::fs::remove_file(path);
+        }
     }
+}
 
-    let mut header_map = AHashMap::with_capacity(parts.headers.len());
-    for (name, value) in parts.headers.iter() {
-        let value = match value.to_str() {
-            Ok(v) => v.to_string(),
-            Err(_) => String::from_utf8_lossy(value.as_bytes()).to_string(),
+#[cfg(unix)]
+impl Listener {
+    /// Serve an already-listening socket passed in by systemd or a parent process
+    fn adopt(fd: std::os::fd::OwnedFd) -> std::io::Result<(Listener, String)> {
+        let socket = socket2::Socket::from(fd);
+        if socket.r#type()? != socket2::Type::STREAM {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "inherited socket is not a stream socket",
+            ));
+        }
+        socket.set_nonblocking(true)?;
+
+        let local_addr = socket.local_addr()?;
+        if let Some(addr) = local_addr.as_socket() {
+            let listener = TcpListener::from_std(socket.into())?;
+            Ok((Listener::Tcp(listener), addr.to_string()))
+        } else if local_addr.is_unix() {
+            let label = match local_addr.as_pathname() {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unix:(unnamed)".to_string(),
+            };
+            let listener = tokio::net::UnixListener::from_std(std::os::fd::OwnedFd::from(socket).into())?;
+            // The socket file belongs to whoever created it
+            Ok((Listener::Unix(listener, None), label))
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "inherited socket is neither TCP nor a Unix socket",
+            ))
+        }
+    }
+}
+
+/// Start one thread per worker, each with a single-threaded runtime serving its own copy of every listener
+///
+/// Accept errors are reported through `failed`; the threads exit once shutdown
+/// has been requested and their connections are done or `shutdown_timeout` passed.
+fn spawn_workers(
+    listeners: &[(Listener, String)],
+    shared: &Shared,
+    server_config: &crate::ServerConfig,
+    failed: tokio::sync::mpsc::UnboundedSender<std::io::Error>,
+) -> std::io::Result<Vec<std::thread::JoinHandle<()>>> {
+    let drain_timeout = Duration::from_secs(server_config.shutdown_timeout);
+    #[cfg(feature = "tls")]
+    let tls = shared.tls.is_some();
+    #[cfg(not(feature = "tls"))]
+    let tls = false;
+    let io_uring = server_config.runtime == crate::RuntimeMode::IoUring
+        && crate::io_uring_server::can_serve(listeners, tls);
+
+    let workers = (0..server_config.worker_threads())
+        .map(|index| {
+            let sockets = listeners
+                .iter()
+                .map(|(listener, _)| listener.worker_socket(index == 0))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let shared = shared.clone();
+            let failed = failed.clone();
+
+            std::thread::Builder::new()
+                .name(format!("firework-worker-{}", index))
+                .spawn(move || {
+                    #[cfg(feature = "io-uring")]
+                    if io_uring {
+                        return crate::io_uring_server::run_worker(sockets, shared, failed, drain_timeout);
+                    }
+                    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                        Ok(runtime) => runtime,
+                        Err(e) => {
+                            let _ = failed.send(e);
+                            return;
+                        }
+                    };
+                    runtime.block_on(run_worker(sockets, shared, failed, drain_timeout));
+                })
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let backend = if io_uring { "io_uring" } else { "epoll" };
+    println!("[SERVER] Serving on {} thread-per-core worker(s) ({})", workers.len(), backend);
+    Ok(workers)
+}
+
+async fn run_worker(
+    sockets: Vec<WorkerSocket>,
+    shared: Shared,
+    failed: tokio::sync::mpsc::UnboundedSender<std::io::Error>,
+    drain_timeout: Duration,
+) {
+    let shutdown = shared.shutdown.clone();
+    let accepting = async move {
+        let listeners = sockets.into_iter().map(WorkerSocket::register).collect::<std::io::Result<Vec<_>>>()?;
+        futures_util::future::try_join_all(listeners.iter().map(|listener| accept_loop(listener, &shared)))
+            .await
+            .map(drop)
+    };
+    drive_worker(accepting, shutdown, failed, drain_timeout).await;
+}
+
+/// Run a worker's accept loops until they fail or shutdown begins, then let its connections drain
+///
+/// `accepting` owns the worker's listeners, so they close as soon as it stops.
+pub(crate) async fn drive_worker(
+    accepting: impl std::future::Future<Output = std::io::Result<()>>,
+    shutdown: ShutdownHandle,
+    failed: tokio::sync::mpsc::UnboundedSender<std::io::Error>,
+    drain_timeout: Duration,
+) {
+    tokio::select! {
+        result = accepting => {
+            if let Err(e) = result {
+                let _ = failed.send(e);
+            }
+        }
+        _ = shutdown.requested() => {}
+    }
+
+    // Connections accepted here can only make progress on this thread
+    let _ = tokio::time::timeout(drain_timeout, shutdown.drained()).await;
+}
+
+/// A worker's copy of a listener, registered with the worker's runtime once on its thread
+pub(crate) enum WorkerSocket {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl WorkerSocket {
+    fn register(self) -> std::io::Result<Listener> {
+        match self {
+            WorkerSocket::Tcp(listener) => Ok(Listener::Tcp(TcpListener::from_std(listener)?)),
+            // The socket file stays owned by the server's own listener
+            #[cfg(unix)]
+            WorkerSocket::Unix(listener) => Ok(Listener::Unix(tokio::net::UnixListener::from_std(listener)?, None)),
+        }
+    }
+}
+
+impl Listener {
+    /// Socket a thread-per-core worker accepts on
+    ///
+    /// The first worker shares this socket. The others bind their own on the same
+    /// port so the kernel spreads connections over them, which needs SO_REUSEPORT;
+    /// Unix and inherited sockets without it are shared by every worker instead.
+    fn worker_socket(&self, first: bool) -> std::io::Result<WorkerSocket> {
+        match self {
+            Listener::Tcp(listener) => {
+                let socket = socket2::SockRef::from(listener);
+                #[cfg(unix)]
+                if !first && socket.reuse_port()? {
+                    let addr = listener.local_addr()?;
+                    let ipv6_only = addr.is_ipv6() && socket.only_v6()?;
+                    return bind_socket(addr, ipv6_only, true).map(WorkerSocket::Tcp);
+                }
+                #[cfg(not(unix))]
+                let _ = first;
+                Ok(WorkerSocket::Tcp(socket.try_clone()?.into()))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let socket = socket2::SockRef::from(listener).try_clone()?;
+                Ok(WorkerSocket::Unix(std::os::fd::OwnedFd::from(socket).into()))
+            }
+        }
+    }
+}
+
+/// Parse and bind every listen address
+fn bind_all<A: AsRef<str>>(
+    addrs: &[A],
+    server_config: &crate::ServerConfig,
+) -> Result<Vec<(Listener, String)>, Box<dyn std::error::Error>> {
+    // Thread-per-core workers add their own sockets on the same ports later
+    let reuse_port = server_config.runtime != crate::RuntimeMode::MultiThread;
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let addr = addr.as_ref();
+        let listener = match ListenAddr::parse(addr)? {
+            ListenAddr::Tcp(socket_addr) => Listener::Tcp(bind_listener(socket_addr, server_config.ipv6_only, reuse_port)?),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                let listener = bind_unix_listener(&path, server_config.unix_socket_mode)?;
+                Listener::Unix(listener, Some(path))
+            }
         };
-        header_map
-            .entry(name.as_str().to_ascii_lowercase())
-            .or_insert_with(Vec::new)
-            .push(value);
+        listeners.push((listener, addr.to_string()));
     }
+    Ok(listeners)
+}
 
-    let full_path = parts
-        .uri
-        .path_and_query()
-        .map(|pq| pq.as_str())
-        .unwrap_or_else(|| parts.uri.path());
-    let (path_only, query) = parse_path_and_query(full_path);
+/// Bind a Unix domain socket at `path`, replacing a stale socket file left by a dead server
+///
+/// Refuses to touch anything that is not a socket, or a socket another process
+/// is still accepting on. `mode` sets the file permissions (e.g. `0o660`).
+#[cfg(unix)]
+fn bind_unix_listener(path: &std::path::Path, mode: Option<u32>) -> std::io::Result<tokio::net::UnixListener> {
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(Error::new(
+                ErrorKind::AddrInUse,
+                format!("{} is in use by another server", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
 
-    let mut request = Request::new(
-        parse_method(parts.method.as_str()),
-        Uri::new(path_only, query),
-        Version::Http2,
-        header_map,
-        body,
-        Some(remote_addr),
-    );
-    let mut response = Response::default();
-    let mut stopped = false;
+    let listener = tokio::net::UnixListener::bind(path)?;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(listener)
+}
+
+/// Bind a listening socket for `addr` with SO_REUSEADDR
+///
+/// The socket family follows the address; for IPv6, `ipv6_only` decides whether
+/// IPv4 clients are accepted too (as IPv4-mapped addresses). `reuse_port` is
+/// for thread-per-core workers, which each bind their own socket on the same port.
+fn bind_listener(addr: std::net::SocketAddr, ipv6_only: bool, reuse_port: bool) -> std::io::Result<TcpListener> {
+    TcpListener::from_std(bind_socket(addr, ipv6_only, reuse_port)?)
+}
+
+fn bind_socket(addr: std::net::SocketAddr, ipv6_only: bool, reuse_port: bool) -> std::io::Result<std::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(ipv6_only)?;
+    }
 
+    // Lets a restarted server rebind while old connections sit in TIME_WAIT, but
+    // (unlike SO_REUSEPORT) never while another process is still listening
+    socket.set_reuse_address(true)?;
+    // Thread-per-core only: a stale server started in that mode could then share the port
+    #[cfg(unix)]
+    if reuse_port {
+        socket.set_reuse_port(true)?;
+    }
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into()).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AddrInUse => std::io::Error::new(
+            e.kind(),
+            format!("{} is already in use by another process", addr),
+        ),
+        _ => e,
+    })?;
+    socket.listen(1024)?;
+
+    Ok(socket.into())
+}
+
+/// Resolves on SIGTERM/SIGINT (Ctrl+C elsewhere)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        
+        let mut sigterm = signal(SignalKind::terminate())
+            .expect("Failed to setup SIGTERM handler");
+        let mut sigint = signal(SignalKind::interrupt())
+            .expect("Failed to setup SIGINT handler");
+        
+        tokio::select! {
+            _ = sigterm.recv() => {
+                println!("\n[SERVER] Received SIGTERM, shutting down gracefully...");
+            }
+            _ = sigint.recv() => {
+                println!("\n[SERVER] Received SIGINT (Ctrl+C), shutting down gracefully...");
+            }
+        }
+    }
+    
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+        println!("\n[SERVER] Received Ctrl+C, shutting down gracefully...");
+    }
+}
+
+/// Runs sync middlewares, async middlewares and plugin `on_request` hooks in order.
+///
+/// Returns `true` when one of them short-circuited the request; `response` then
+/// holds the final response.
+pub(crate) async fn run_request_phase(
+    middlewares: &[Middleware],
+    async_middlewares: &[AsyncMiddleware],
+    plugins: &[Arc<dyn crate::Plugin>],
+    request: &mut Request,
+    response: &mut Response,
+) -> bool {
     for mw in middlewares.iter() {
-        match mw(&mut request, &mut response) {
+        match mw(request, response) {
             Flow::Stop(final_res) => {
-                response = final_res;
-                stopped = true;
-                break;
+                *response = final_res;
+                return true;
             }
             Flow::Continue => {}
         }
     }
 
-    if !stopped {
-        for mw in async_middlewares.iter() {
-            match mw(&mut request, &mut response).await {
-                Flow::Stop(final_res) => {
-                    response = final_res;
-                    stopped = true;
-                    break;
-                }
-                Flow::Continue => {}
+    for mw in async_middlewares.iter() {
+        match mw(request, response).await {
+            Flow::Stop(final_res) => {
+                *response = final_res;
+                return true;
             }
+            Flow::Continue => {}
         }
     }
 
-    if !stopped {
-        for plugin in plugins.iter() {
-            match plugin.on_request(&mut request, &mut response).await {
-                Ok(Some(plugin_response)) => {
-                    response = plugin_response;
-                    stopped = true;
-                    break;
+    // Uses cached plugin list - no lock needed!
+    for plugin in plugins.iter() {
+        match plugin.on_request(request, response).await {
+            Ok(Some(plugin_response)) => {
+                *response = plugin_response;
+                return true;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("[PLUGIN] Error in {}: {}", plugin.name(), e);
+            }
+        }
+    }
+
+    false
+}
+
+/// Routes the request and executes its handler (unless the request phase already
+/// `stopped` it), then runs the response phase on whatever response came out.
+///
+/// A handler still running after `handler_timeout` is dropped and answered with 503.
+/// Responses rendered from an `Error` go through the router's error handler, if set.
+/// Meanwhile `url_for` resolves the router's route names.
+pub(crate) async fn run_handler_phase(
+    router: &Router,
+    plugins: &[Arc<dyn crate::Plugin>],
+    request: Request,
+    response: Response,
+    stopped: bool,
+    handler_timeout: Option<Duration>,
+) -> Response {
+    let phase = route_and_handle(router, plugins, request, response, stopped, handler_timeout);
+    match router.route_names() {
+        Some(names) => crate::url::with_names(Arc::clone(names), phase).await,
+        None => phase.await,
+    }
+}
+
+async fn route_and_handle(
+    router: &Router,
+    plugins: &[Arc<dyn crate::Plugin>],
+    mut request: Request,
+    response: Response,
+    stopped: bool,
+    handler_timeout: Option<Duration>,
+) -> Response {
+    if stopped {
+        let mut response = response;
+        router.render_error(&request, &mut response);
+        run_response_phase(plugins, &request, &mut response).await;
+        return response;
+    }
+
+    let handler = match router.route_for_host(request.header("host"), &request.method, &request.uri.path) {
+        RouteMatch::Found(handler, params) => {
+            request.params = params;
+            handler
+        }
+        RouteMatch::NotFound => match router.fallback() {
+            Some(fallback) => Arc::clone(fallback),
+            None => return answer_not_found(router, plugins, request, response, handler_timeout).await,
+        },
+        RouteMatch::MethodNotAllowed(allowed) => {
+            // OPTIONS is answered for every routed path unless a handler claims it
+            let mut response = if matches!(request.method, Method::OPTIONS) {
+                Response::new(crate::response::StatusCode::NoContent, Vec::new())
+            } else {
+                let error = crate::Error::MethodNotAllowed(format!("Allowed methods: {}", allowed.join(", ")));
+                router.error_response(error, &request).unwrap_or_else(|| {
+                    Response::new(
+                        crate::response::StatusCode::METHOD_NOT_ALLOWED,
+                        b"Method Not Allowed\n",
+                    )
+                })
+            };
+            response.headers.insert("Allow".to_string(), allowed.join(", "));
+            run_response_phase(plugins, &request, &mut response).await;
+            return response;
+        }
+    };
+
+    if plugins.is_empty() && !router.renders_errors() {
+        return call_handler(handler.as_ref(), request, response, handler_timeout).await;
+    }
+    // The handler takes ownership of the request; hooks only get its head.
+    let head = request.without_body();
+    let mut response = call_handler(handler.as_ref(), request, response, handler_timeout).await;
+    router.render_error(&head, &mut response);
+    run_response_phase(plugins, &head, &mut response).await;
+    response
+}
+
+/// 404 for a request no route matches, from the not-found handler or the error handler
+async fn answer_not_found(
+    router: &Router,
+    plugins: &[Arc<dyn crate::Plugin>],
+    request: Request,
+    response: Response,
+    handler_timeout: Option<Duration>,
+) -> Response {
+    let (head, mut response) = match router.not_found() {
+        Some(not_found) => {
+            let head = request.without_body();
+            let mut response = call_handler(not_found.as_ref(), request, response, handler_timeout).await;
+            // The handler only shapes the answer; it stays a 404
+            response.status = crate::response::StatusCode::NotFound;
+            router.render_error(&head, &mut response);
+            (head, response)
+        }
+        None => {
+            let error = crate::Error::NotFound(format!("No route for {}", request.uri.path));
+            let response = router
+                .error_response(error, &request)
+                .unwrap_or_else(|| Response::new(crate::response::StatusCode::NotFound, b"Not Found\n"));
+            (request, response)
+        }
+    };
+    run_response_phase(plugins, &head, &mut response).await;
+    response
+}
+
+/// Run `handler`, answering 503 once it outlives `timeout`
+///
+/// While a streamed body is still being read, each wait for the next chunk is
+/// bounded by the body timeout instead; the handler's clock starts once the
+/// body has been read or dropped.
+#[inline]
+async fn call_handler(
+    handler: &dyn crate::AsyncHandler,
+    mut request: Request,
+    response: Response,
+    timeout: Option<Duration>,
+) -> Response {
+    let Some(timeout) = timeout else {
+        return handler.call(request, response).await;
+    };
+    let body_read = request.pending_body.0.as_mut().map(BodyStream::notify_finished);
+    let deadline = async {
+        if let Some(body_read) = body_read {
+            let _ = body_read.await;
+        }
+        tokio::time::sleep(timeout).await
+    };
+    tokio::select! {
+        response = handler.call(request, response) => response,
+        () = deadline => crate::Error::ServiceUnavailable("Request handler timed out".into()).into_response(),
+    }
+}
+
+/// Runs every plugin's `on_response` hook, in the same priority order as `on_request`.
+pub(crate) async fn run_response_phase(
+    plugins: &[Arc<dyn crate::Plugin>],
+    request: &Request,
+    response: &mut Response,
+) {
+    for plugin in plugins.iter() {
+        if let Err(e) = plugin.on_response(request, response).await {
+            eprintln!("[PLUGIN] Error in {}: {}", plugin.name(), e);
+        }
+    }
+}
+
+/// Runs every plugin's `on_stream_accept` hook on a freshly accepted connection.
+///
+/// Returns the connection-scoped context, or `None` when a plugin rejected the
+/// connection (the caller drops the stream, which closes it).
+pub(crate) async fn accept_stream(
+    plugins: &[Arc<dyn crate::Plugin>],
+    mut socket: AcceptedStream<'_>,
+    remote_addr: &RemoteAddr,
+) -> Option<Context> {
+    let mut conn_context = Context::new();
+    for plugin in plugins.iter() {
+        match plugin.on_stream_accept(socket.reborrow(), remote_addr, &mut conn_context).await {
+            Ok(StreamAction::Accept) => {}
+            Ok(StreamAction::Reject) => return None,
+            Err(e) => {
+                eprintln!("[PLUGIN] Error in {}: {}", plugin.name(), e);
+            }
+        }
+    }
+    Some(conn_context)
+}
+
+/// Fresh request context seeded with the connection-scoped values
+#[inline]
+fn request_context(conn_context: &Context) -> Context {
+    if conn_context.is_empty() {
+        Context::new()
+    } else {
+        conn_context.fork()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_connection<S: Connection>(
+    socket: S,
+    router: Arc<Router>,
+    middlewares: Arc<Vec<Middleware>>,
+    async_middlewares: Arc<Vec<AsyncMiddleware>>,
+    remote_addr: RemoteAddr,
+    ws_routes: Arc<std::collections::HashMap<String, Arc<dyn crate::websocket::WebSocketHandler>>>,
+    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
+    conn_context: Context,
+    shutdown: ShutdownHandle,
+    timeouts: Timeouts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "http2")]
+    match sniff_http2(&socket, &shutdown, timeouts).await? {
+        None => return Ok(()),
+        Some(true) => {
+            return handle_http2_connection(socket, router, middlewares, async_middlewares, remote_addr, plugins, conn_context, shutdown, timeouts).await;
+        }
+        Some(false) => {}
+    }
+
+    serve_http1(socket, router, middlewares, async_middlewares, remote_addr, ws_routes, plugins, conn_context, shutdown, timeouts).await
+}
+
+/// Terminate TLS, then serve whichever protocol ALPN settled on
+#[cfg(feature = "tls")]
+#[allow(clippy::too_many_arguments)]
+async fn handle_tls_connection<S: Io>(
+    socket: S,
+    tls: Arc<crate::tls::TlsAcceptor>,
+    router: Arc<Router>,
+    middlewares: Arc<Vec<Middleware>>,
+    async_middlewares: Arc<Vec<AsyncMiddleware>>,
+    remote_addr: RemoteAddr,
+    ws_routes: Arc<std::collections::HashMap<String, Arc<dyn crate::websocket::WebSocketHandler>>>,
+    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
+    conn_context: Context,
+    shutdown: ShutdownHandle,
+    timeouts: Timeouts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = tokio::select! {
+        socket = within(deadline_after(timeouts.header), tls.accept(socket)) => match socket {
+            Some(socket) => socket?,
+            None => return Ok(()),
+        },
+        _ = shutdown.requested() => return Ok(()),
+    };
+
+    #[cfg(feature = "http2")]
+    if socket.get_ref().1.alpn_protocol() == Some(b"h2") {
+        return handle_http2_connection(socket, router, middlewares, async_middlewares, remote_addr, plugins, conn_context, shutdown, timeouts).await;
+    }
+
+    serve_http1(socket, router, middlewares, async_middlewares, remote_addr, ws_routes, plugins, conn_context, shutdown, timeouts).await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn serve_http1<S: Http1Io>(
+    mut socket: S,
+    router: Arc<Router>,
+    middlewares: Arc<Vec<Middleware>>,
+    async_middlewares: Arc<Vec<AsyncMiddleware>>,
+    remote_addr: RemoteAddr,
+    ws_routes: Arc<std::collections::HashMap<String, Arc<dyn crate::websocket::WebSocketHandler>>>,
+    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
+    conn_context: Context,
+    shutdown: ShutdownHandle,
+    timeouts: Timeouts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Each request consumes exactly its own bytes; whatever follows is the start
+    // of the next, pipelined request, so the buffer is never cleared between them
+    let mut read_buf = get_buffer();
+    let mut first_request = true;
+
+    loop {
+        // Read until the buffer holds a complete request head
+        let mut header_deadline = None;
+        let head_end = loop {
+            // Tolerate the stray CRLF some clients send after a body (RFC 9112 §2.2)
+            let blank = read_buf.iter().take_while(|b| matches!(b, b'\r' | b'\n')).count();
+            read_buf.advance(blank);
+
+            if let Some(end) = find_header_end(&read_buf) {
+                break end;
+            }
+            if read_buf.len() >= BUFFER_SIZE * 2 {
+                // Request too large, reject
+                return_buffer(read_buf);
+                return Err("Request too large".into());
+            }
+            if !read_buf.is_empty() && header_deadline.is_none() {
+                header_deadline = deadline_after(timeouts.header);
+            }
+
+            read_buf.reserve(4096);
+            let read = if read_buf.is_empty() {
+                // Between requests the connection is idle: it closes quietly when the
+                // idle limit passes or shutdown starts
+                let idle = if first_request { timeouts.header } else { timeouts.keep_alive };
+                tokio::select! {
+                    read = within(deadline_after(idle), socket.read_buf(&mut read_buf)) => read,
+                    _ = shutdown.requested() => None,
+                }
+            } else {
+                let read = within(header_deadline, socket.read_buf(&mut read_buf)).await;
+                if read.is_none() {
+                    // Slow header trickle (slowloris): answer 408 and hang up
+                    let response = crate::Error::RequestTimeout("Timed out reading request headers".into()).into_response();
+                    close_with(&mut socket, response).await?;
+                }
+                read
+            };
+            match read {
+                None | Some(Ok(0)) => {
+                    // Timed out, shutting down or closed by the client
+                    return_buffer(read_buf);
+                    return Ok(());
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    tokio::task::yield_now().await;
+                }
+                Some(Err(e)) => {
+                    return_buffer(read_buf);
+                    return Err(e.into());
+                }
+            }
+        };
+
+        // Parse headers
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        let headers_len = match req.parse(&read_buf[..head_end]) {
+            Ok(httparse::Status::Complete(headers_len)) => headers_len,
+            // The head ends in a blank line, so anything short of complete is malformed
+            Ok(httparse::Status::Partial) | Err(_) => {
+                return_buffer(read_buf);
+                return Err("Invalid HTTP request".into());
+            }
+        };
+
+        // Extract method, path, version
+        let method = parse_method(req.method.unwrap_or("GET"));
+        let path = req.path.unwrap_or("/");
+        let version = parse_version(req.version.unwrap_or(1));
+
+        // Pre-extract body framing headers and connection
+        let mut header_map = HeaderMap::with_capacity(req.headers.len());
+        let mut content_lengths: SmallVec<[&str; 1]> = SmallVec::new();
+        let mut transfer_encodings: SmallVec<[&str; 1]> = SmallVec::new();
+        let mut keep_alive = version == Version::Http11;
+        let mut expects_continue = false;
+
+        for header in req.headers.iter() {
+            let name = header.name;
+            let value = std::str::from_utf8(header.value).unwrap_or("");
+
+            // Fast path: skip common headers we don't need in map
+            if name.eq_ignore_ascii_case("content-length") {
+                content_lengths.push(value);
+                continue;
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                transfer_encodings.push(value);
+                continue;
+            } else if name.eq_ignore_ascii_case("connection") {
+                keep_alive = value.eq_ignore_ascii_case("keep-alive");
+                continue;
+            } else if name.eq_ignore_ascii_case("expect") {
+                expects_continue = value.eq_ignore_ascii_case("100-continue");
+            }
+
+            // Intern common header names - use &'static str when possible
+            let name_str = intern_header_name_static(name);
+
+            header_map.append(name_str, value.to_string());
+        }
+
+        let framing = body_framing(&version, &content_lengths, &transfer_encodings);
+        // Release the borrows of read_buf held by the header values
+        drop(content_lengths);
+        drop(transfer_encodings);
+        let framing = match framing {
+            Ok(framing) => framing,
+            Err(status) => {
+                // Framing is ambiguous: answer and drop the connection
+                reject_request(&mut socket, status).await?;
+                return_buffer(read_buf);
+                return Ok(());
+            }
+        };
+
+        // Parse path and query
+        let path_only = match canonical_path(&router, path) {
+            Ok(canonical) => canonical,
+            Err(response) => {
+                // The body (if any) is left unread, so the connection cannot be reused
+                close_with(&mut socket, response).await?;
+                return_buffer(read_buf);
+                return Ok(());
+            }
+        };
+        let uri = Uri::with_raw_query(&path_only, query_string(path));
+
+        // The body (if any) and later requests follow the head
+        read_buf.advance(headers_len);
+
+        // Routes taking a BodyStream read the body while the handler runs
+        let streaming = framing != BodyFraming::Empty
+            && router.streams_body(header_map.get("host").map(String::as_str), &method, &uri.path);
+        // HEAD is answered by the GET handler, minus the body
+        let head = matches!(method, Method::HEAD);
+
+        // A buffered body over the limit is refused before any of it is read or allocated
+        let too_large = |len: usize| timeouts.max_body.is_some_and(|max| len > max);
+        if !streaming && matches!(framing, BodyFraming::Length(len) if too_large(len)) {
+            reject_request(&mut socket, StatusCode::CONTENT_TOO_LARGE).await?;
+            return_buffer(read_buf);
+            return Ok(());
+        }
+
+        // A client sending `Expect: 100-continue` holds its body back until the
+        // request is accepted, so it is read after routing and the request phase
+        let expects_continue = expects_continue && version == Version::Http11 && framing != BodyFraming::Empty;
+
+        // Read body according to its framing
+        let body = if streaming || expects_continue {
+            Vec::new()
+        } else {
+            match read_request_body(&mut socket, &mut read_buf, framing, timeouts).await {
+                Ok(Some(body_data)) => body_data,
+                Ok(None) => {
+                    return_buffer(read_buf);
+                    return Ok(());
                 }
-                Ok(None) => {}
                 Err(e) => {
-                    eprintln!("[PLUGIN] Error in {}: {}", plugin.name(), e);
+                    return_buffer(read_buf);
+                    return Err(e);
+                }
+            }
+        };
+
+        // Create request (NO CLONING in hot path)
+        let mut request = Request::new(
+            method,
+            uri,
+            version,
+            header_map,
+            body,
+            Some(remote_addr),
+        );
+        request.context = request_context(&conn_context);
+        let mut first_read = None;
+        let body_sender = streaming.then(|| {
+            let (sender, mut body_stream) = BodyStream::channel();
+            if expects_continue {
+                // `100 Continue` waits until the handler asks for the body
+                first_read = Some(body_stream.notify_first_read());
+            }
+            let content_length = match framing {
+                BodyFraming::Length(len) => Some(len as u64),
+                _ => None,
+            };
+            request.set_body_stream(
+                body_stream
+                    .with_content_length(content_length)
+                    .with_read_timeout(timeouts.body),
+            );
+            sender
+        });
+        let mut response = Response::default();
+
+        // Set keep-alive header early
+        if keep_alive {
+            response
+                .headers
+                .insert("Connection".to_string(), "keep-alive".to_string());
+        }
+
+        // Execute middlewares and plugin on_request hooks (zero-cost - no cloning!).
+        // A `100-continue` body has not been read yet, so they see it empty and
+        // can turn the request away before the client sends it.
+        let stopped = run_request_phase(
+            &middlewares,
+            &async_middlewares,
+            &plugins,
+            &mut request,
+            &mut response,
+        )
+        .await;
+
+        // Check if this is a WebSocket upgrade request
+        if !stopped && crate::websocket::is_websocket_upgrade(&request) {
+            if let Some(ws_handler) = ws_routes.get(&request.uri.path) {
+                // Perform WebSocket handshake
+                if let Some(mut upgrade_response) = crate::websocket::websocket_upgrade(&request) {
+                    // Send upgrade response
+                    write_response(&mut socket, &mut upgrade_response, false, false).await?;
+
+                    // Create WebSocket and handle it
+                    let ws = crate::websocket::WebSocket::new(socket.into_websocket()?).await;
+                    ws_handler.call(ws).await;
+
+                    // WebSocket connection handled, return
+                    return_buffer(read_buf);
+                    return Ok(());
                 }
             }
         }
+
+        if expects_continue {
+            // A fallback takes unmatched requests with their body; a not-found
+            // answer stays a 404 and does without it
+            let accepted = !stopped
+                && match router.route_for_host(request.header("host"), &request.method, &request.uri.path) {
+                    RouteMatch::Found(..) => true,
+                    RouteMatch::NotFound => router.fallback().is_some(),
+                    RouteMatch::MethodNotAllowed(_) => false,
+                };
+            if !accepted {
+                // Final status without reading the body; the client may send it
+                // anyway, so the connection cannot carry another request
+                keep_alive = false;
+            } else if !streaming {
+                let read = async {
+                    write_continue(&mut socket).await?;
+                    read_request_body(&mut socket, &mut read_buf, framing, timeouts).await
+                };
+                match read.await {
+                    Ok(Some(body_data)) => request.body = body_data,
+                    Ok(None) => {
+                        return_buffer(read_buf);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        return_buffer(read_buf);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        // Route, execute handler and run plugin on_response hooks
+        let handler_phase = run_handler_phase(&router, &plugins, request, response, stopped, timeouts.handler);
+        let mut response = match body_sender {
+            None => handler_phase.await,
+            Some(sender) => {
+                let pump = pump_body(&mut socket, &mut read_buf, framing, sender, first_read);
+                let (response, drained) = run_streaming_handler(handler_phase, pump).await;
+                // Unread body bytes would be parsed as the next request
+                keep_alive &= drained;
+                response
+            }
+        };
+
+        // Write response; during shutdown it is the last one on this connection
+        keep_alive &= !shutdown.is_shutting_down();
+        write_response(&mut socket, &mut response, keep_alive, head).await?;
+
+        if !keep_alive {
+            // Orderly close (sends close_notify over TLS)
+            let _ = socket.shutdown().await;
+            return_buffer(read_buf);
+            return Ok(());
+        }
+
+        // Continue with the next request, which may already be buffered
+        first_request = false;
     }
+}
 
-    if !stopped {
-        if let Some((handler, params)) = router.find(&request.method, path_only) {
-            request.params = params;
-            response = handler.call(request, response).await;
+/// Whether the client opened with the HTTP/2 preface
+///
+/// `None` when it sent nothing before the header timeout, or shutdown began
+/// while the connection was still idle.
+#[cfg(feature = "http2")]
+pub(crate) async fn sniff_http2<S: Connection>(
+    socket: &S,
+    shutdown: &ShutdownHandle,
+    timeouts: Timeouts,
+) -> std::io::Result<Option<bool>> {
+    tokio::select! {
+        detected = within(deadline_after(timeouts.header), detect_http2_handshake(socket)) => detected.transpose(),
+        _ = shutdown.requested() => Ok(None),
+    }
+}
+
+#[cfg(feature = "http2")]
+async fn detect_http2_handshake<S: Connection>(socket: &S) -> std::io::Result<bool> {
+    let mut probe = [0u8; HTTP2_PREFACE.len()];
+    for _ in 0..8 {
+        let read = socket.peek(&mut probe).await?;
+        if read == 0 {
+            return Ok(false);
+        }
+
+        let cmp_len = read.min(HTTP2_PREFACE.len());
+        if probe[..cmp_len] != HTTP2_PREFACE[..cmp_len] {
+            return Ok(false);
+        }
+
+        if read >= HTTP2_PREFACE.len() {
+            return Ok(true);
+        }
+
+        socket.readable().await?;
+    }
+
+    Ok(false)
+}
+
+#[cfg(feature = "http2")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn handle_http2_connection<S: Io>(
+    socket: S,
+    router: Arc<Router>,
+    middlewares: Arc<Vec<Middleware>>,
+    async_middlewares: Arc<Vec<AsyncMiddleware>>,
+    remote_addr: RemoteAddr,
+    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
+    conn_context: Context,
+    shutdown: ShutdownHandle,
+    timeouts: Timeouts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(connection) = within(deadline_after(timeouts.header), h2::server::handshake(socket)).await else {
+        return Ok(());
+    };
+    let mut connection = connection?;
+    let mut draining = false;
+    // One clone per stream task still running
+    let open_streams = Arc::new(());
+
+    loop {
+        let stream = if draining {
+            connection.accept().await
         } else {
-            response = Response::new(crate::response::StatusCode::NotFound, b"Not Found\n");
+            let idle = Arc::strong_count(&open_streams) == 1;
+            let idle_deadline = if idle { deadline_after(timeouts.keep_alive) } else { None };
+            tokio::select! {
+                stream = within(idle_deadline, connection.accept()) => match stream {
+                    Some(stream) => stream,
+                    None => {
+                        // Idle past keep-alive: say GOAWAY and let the connection wind down
+                        connection.graceful_shutdown();
+                        draining = true;
+                        continue;
+                    }
+                },
+                _ = shutdown.requested() => {
+                    // GOAWAY: refuse new streams, let open ones finish
+                    connection.graceful_shutdown();
+                    draining = true;
+                    continue;
+                }
+            }
+        };
+        let Some(stream) = stream else {
+            break;
+        };
+        let (request, respond) = stream?;
+        let router = Arc::clone(&router);
+        let middlewares = Arc::clone(&middlewares);
+        let async_middlewares = Arc::clone(&async_middlewares);
+        let plugins = Arc::clone(&plugins);
+        let context = request_context(&conn_context);
+        let open_stream = Arc::clone(&open_streams);
+
+        tokio::spawn(async move {
+            let _open_stream = open_stream;
+            if let Err(err) = handle_http2_stream(
+                request,
+                respond,
+                router,
+                middlewares,
+                async_middlewares,
+                remote_addr,
+                plugins,
+                context,
+                timeouts,
+            )
+            .await
+            {
+                eprintln!("[HTTP2] stream error: {err}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "http2")]
+#[allow(clippy::too_many_arguments)]
+async fn handle_http2_stream(
+    request: http::Request<h2::RecvStream>,
+    mut respond: h2::server::SendResponse<bytes::Bytes>,
+    router: Arc<Router>,
+    middlewares: Arc<Vec<Middleware>>,
+    async_middlewares: Arc<Vec<AsyncMiddleware>>,
+    remote_addr: RemoteAddr,
+    plugins: Arc<Vec<Arc<dyn crate::Plugin>>>,
+    context: Context,
+    timeouts: Timeouts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (parts, mut recv) = request.into_parts();
+
+    let mut header_map = HeaderMap::with_capacity(parts.headers.len());
+    for (name, value) in parts.headers.iter() {
+        let value = match value.to_str() {
+            Ok(v) => v.to_string(),
+            Err(_) => String::from_utf8_lossy(value.as_bytes()).to_string(),
+        };
+        header_map.append(name.as_str().to_string(), value);
+    }
+    // HTTP/2 carries the host in `:authority`; handlers and host routing read `Host`
+    if let Some(authority) = parts.uri.authority() {
+        if header_map.get("host").is_none() {
+            header_map.append("host".to_string(), authority.as_str().to_string());
         }
     }
 
-    write_http2_response(&mut respond, &mut response).await
+    let full_path = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or_else(|| parts.uri.path());
+    let path_only = match canonical_path(&router, full_path) {
+        Ok(canonical) => canonical,
+        Err(mut response) => return write_http2_response(&mut respond, &mut response, false).await,
+    };
+    let path_only = path_only.as_ref();
+    let method = parse_method(parts.method.as_str());
+
+    // Routes taking a BodyStream read straight from the h2 stream
+    let streaming = !recv.is_end_stream()
+        && router.streams_body(header_map.get("host").map(String::as_str), &method, path_only);
+    let mut body = Vec::new();
+    if !streaming {
+        // `false` once the body passes the size limit
+        let read = async {
+            while let Some(chunk) = recv.data().await {
+                let chunk = chunk?;
+                let _ = recv.flow_control().release_capacity(chunk.len());
+                if timeouts.max_body.is_some_and(|max| body.len() + chunk.len() > max) {
+                    return Ok(false);
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok::<_, h2::Error>(true)
+        };
+        match within(deadline_after(timeouts.body), read).await {
+            Some(Ok(true)) => {}
+            Some(Ok(false)) => {
+                let mut response = crate::Error::PayloadTooLarge("Request body too large".into()).into_response();
+                return write_http2_response(&mut respond, &mut response, false).await;
+            }
+            Some(Err(e)) => return Err(e.into()),
+            None => {
+                let mut response = crate::Error::RequestTimeout("Timed out reading request body".into()).into_response();
+                return write_http2_response(&mut respond, &mut response, false).await;
+            }
+        }
+    }
+
+    let mut request = Request::new(
+        method,
+        Uri::with_raw_query(path_only, query_string(full_path)),
+        Version::Http2,
+        header_map,
+        body,
+        Some(remote_addr),
+    );
+    if streaming {
+        // h2 has already checked that the DATA frames add up to it
+        let content_length = request.header("content-length").and_then(|len| len.parse::<u64>().ok());
+        request.set_body_stream(
+            BodyStream::h2(recv)
+                .with_content_length(content_length)
+                .with_read_timeout(timeouts.body),
+        );
+    }
+    request.context = context;
+    let mut response = Response::default();
+    let stopped = run_request_phase(
+        &middlewares,
+        &async_middlewares,
+        &plugins,
+        &mut request,
+        &mut response,
+    )
+    .await;
+    let head = matches!(request.method, Method::HEAD);
+    let mut response = run_handler_phase(&router, &plugins, request, response, stopped, timeouts.handler).await;
+
+    write_http2_response(&mut respond, &mut response, head).await
 }
 
 #[cfg(feature = "http2")]
 async fn write_http2_response(
     respond: &mut h2::server::SendResponse<bytes::Bytes>,
     response: &mut Response,
+    omit_body: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let status = http::StatusCode::from_u16(response.status.code())
-        .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
+    let status = http::StatusCode::try_from(&response.status).unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
     let mut builder = http::Response::builder().status(status);
 
+    // Every value goes out as its own field, so repeated headers like Set-Cookie survive
     for (key, value) in &response.headers {
-        if is_hop_by_hop_header(key) {
+        if is_hop_by_hop_header(key) || key.eq_ignore_ascii_case("content-length") {
             continue;
         }
         builder = builder.header(key.as_str(), value.as_str());
@@ -818,10 +1880,13 @@ async fn write_http2_response(
 
     let body = response_body_to_vec(response).await?;
     builder = builder.header("content-length", body.len().to_string());
-    builder = builder.header("content-type", response.headers.get("Content-Type").cloned().unwrap_or_else(|| "text/plain; charset=utf-8".to_string()));
+    if !response.headers.contains_key("Content-Type") {
+        builder = builder.header("content-type", "text/plain; charset=utf-8");
+    }
 
     let h2_response = builder.body(())?;
-    if body.is_empty() {
+    // HEAD answers keep the Content-Length of the body they leave out
+    if body.is_empty() || omit_body {
         respond.send_response(h2_response, true)?;
     } else {
         let mut send_stream = respond.send_response(h2_response, false)?;
@@ -920,30 +1985,293 @@ fn parse_version(version: u8) -> Version {
     }
 }
 
+/// Canonicalize the path of a request target before routing (see `PathConfig`)
+///
+/// `Err` holds the answer for a path that cannot be routed as sent: 400, or a
+/// 308 redirect to the canonical path that keeps the query string.
+#[allow(clippy::result_large_err)]
+pub(crate) fn canonical_path<'a>(router: &Router, target: &'a str) -> Result<std::borrow::Cow<'a, str>, Response> {
+    let (path, query) = match target.find('?') {
+        Some(pos) => target.split_at(pos),
+        None => (target, ""),
+    };
+    crate::path::canonicalize(path, router.path_config()).map_err(|rejection| match rejection {
+        crate::path::PathRejection::Invalid(reason) => crate::Error::BadRequest(reason.into()).into_response(),
+        crate::path::PathRejection::Redirect(location) => {
+            let mut response = Response::new(StatusCode::PERMANENT_REDIRECT, Vec::new());
+            response.headers.insert("Location".to_string(), format!("{location}{query}"));
+            response
+        }
+    })
+}
+
 #[inline]
-fn parse_path_and_query(path: &str) -> (&str, Option<HashMap<String, String>>) {
-    if let Some(pos) = path.find('?') {
-        let (path_only, query_str) = path.split_at(pos);
-        let query_str = &query_str[1..]; // skip '?'
+fn query_string(target: &str) -> Option<&str> {
+    target.split_once('?').map(|(_, query)| query)
+}
 
-        if query_str.is_empty() {
-            (path_only, None)
-        } else {
-            // Use form_urlencoded for efficient parsing with proper URL decoding
-            let query: HashMap<String, String> = form_urlencoded::parse(query_str.as_bytes())
-                .into_owned()
-                .collect();
-            (path_only, Some(query))
+/// How the body of an HTTP/1.x request is delimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFraming {
+    Empty,
+    Length(usize),
+    Chunked,
+}
+
+/// Determine request body framing (RFC 9112 §6.3)
+///
+/// Requests carrying both Content-Length and Transfer-Encoding are rejected
+/// rather than guessed at, since front-end and back-end disagreeing on the
+/// framing is what makes request smuggling possible.
+fn body_framing(
+    version: &Version,
+    content_lengths: &[&str],
+    transfer_encodings: &[&str],
+) -> Result<BodyFraming, StatusCode> {
+    if !transfer_encodings.is_empty() {
+        if !content_lengths.is_empty() || *version != Version::Http11 {
+            return Err(StatusCode::BadRequest);
+        }
+
+        let codings = transfer_encodings
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty());
+        let mut chunked_last = false;
+        let mut others = false;
+        for coding in codings {
+            if chunked_last {
+                // chunked must be the final coding, and only once
+                return Err(StatusCode::BadRequest);
+            }
+            if coding.eq_ignore_ascii_case("chunked") {
+                chunked_last = true;
+            } else {
+                others = true;
+            }
+        }
+
+        return match (chunked_last, others) {
+            (true, false) => Ok(BodyFraming::Chunked),
+            (true, true) => Err(StatusCode::NOT_IMPLEMENTED),
+            (false, _) => Err(StatusCode::BadRequest),
+        };
+    }
+
+    let mut length = None;
+    for value in content_lengths.iter().flat_map(|value| value.split(',')) {
+        let value = value.trim();
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(StatusCode::BadRequest);
+        }
+        let parsed: usize = value.parse().map_err(|_| StatusCode::BadRequest)?;
+        if length.is_some_and(|existing| existing != parsed) {
+            return Err(StatusCode::BadRequest);
+        }
+        length = Some(parsed);
+    }
+
+    Ok(match length {
+        Some(0) | None => BodyFraming::Empty,
+        Some(n) => BodyFraming::Length(n),
+    })
+}
+
+/// Forward a request body from the socket into the handler's `BodyStream`
+///
+/// Returns true once the whole body has been read off the connection, false
+/// if the handler dropped the stream early or the body was cut short or malformed.
+async fn pump_body<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+    buf: &mut BytesMut,
+    framing: BodyFraming,
+    sender: BodySender,
+    first_read: Option<tokio::sync::oneshot::Receiver<()>>,
+) -> bool {
+    if let Some(first_read) = first_read {
+        // The client waits for `100 Continue`; a handler that never reads the
+        // body answers without it
+        if first_read.await.is_err() {
+            return false;
+        }
+        if let Err(e) = write_continue(socket).await {
+            let _ = sender.send(Err(e)).await;
+            return false;
+        }
+    }
+
+    match framing {
+        BodyFraming::Empty => true,
+        BodyFraming::Length(mut remaining) => {
+            while remaining > 0 {
+                if buf.is_empty() {
+                    if let Err(e) = fill_buf(socket, buf).await {
+                        let _ = sender.send(Err(e)).await;
+                        return false;
+                    }
+                }
+                let chunk = buf.split_to(buf.len().min(remaining)).freeze();
+                remaining -= chunk.len();
+                if sender.send(Ok(chunk)).await.is_err() {
+                    return false;
+                }
+            }
+            true
+        }
+        BodyFraming::Chunked => {
+            let mut decoder = ChunkedDecoder::new();
+            loop {
+                match decoder.decode(buf) {
+                    Ok(Decoded::Data(data)) => {
+                        if sender.send(Ok(data)).await.is_err() {
+                            return false;
+                        }
+                    }
+                    Ok(Decoded::Done) => return true,
+                    Ok(Decoded::NeedMore) => {
+                        if let Err(e) = fill_buf(socket, buf).await {
+                            let _ = sender.send(Err(e)).await;
+                            return false;
+                        }
+                    }
+                    Err(e) => {
+                        let _ = sender
+                            .send(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+                            .await;
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Read more body bytes into `buf`, treating EOF as a truncated body
+async fn fill_buf<S: AsyncRead + AsyncWrite + Unpin>(socket: &mut S, buf: &mut BytesMut) -> std::io::Result<()> {
+    buf.reserve(4096);
+    match socket.read_buf(buf).await? {
+        0 => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Drive the handler while the connection feeds its body
+///
+/// Returns the response and whether the body was fully consumed. The handler
+/// may answer before reading everything; the pump is then abandoned rather
+/// than waiting on a client that is still uploading.
+async fn run_streaming_handler(
+    handler_phase: impl std::future::Future<Output = Response>,
+    pump: impl std::future::Future<Output = bool>,
+) -> (Response, bool) {
+    tokio::pin!(handler_phase);
+    tokio::pin!(pump);
+    let mut drained = None;
+    loop {
+        tokio::select! {
+            response = &mut handler_phase => return (response, drained.unwrap_or(false)),
+            done = &mut pump, if drained.is_none() => drained = Some(done),
+        }
+    }
+}
+
+/// Answer a request we refuse to process and close the connection
+async fn reject_request<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+    status: StatusCode,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let body = format!("{}\n", status.as_str());
+    close_with(socket, Response::new(status, body)).await
+}
+
+/// Send a final response with `Connection: close`
+async fn close_with<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+    mut response: Response,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_response(socket, &mut response, false, false).await?;
+    let _ = socket.shutdown().await;
+    Ok(())
+}
+
+/// Read a buffered request body within the body timeout and size limit
+///
+/// `None` means the body was bad, late or too large: the client has been
+/// answered and the connection must close.
+async fn read_request_body<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+    buf: &mut BytesMut,
+    framing: BodyFraming,
+    timeouts: Timeouts,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let read = read_body(socket, buf, framing, timeouts.max_body);
+    match within(deadline_after(timeouts.body), read).await {
+        Some(Ok(body_data)) => Ok(Some(body_data)),
+        Some(Err(e))
+            if e.get_ref().and_then(|e| e.downcast_ref::<crate::chunked::ChunkedError>())
+                == Some(&crate::chunked::ChunkedError::BodyTooLarge) =>
+        {
+            reject_request(socket, StatusCode::CONTENT_TOO_LARGE).await?;
+            Ok(None)
+        }
+        Some(Err(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+            reject_request(socket, StatusCode::BadRequest).await?;
+            Ok(None)
+        }
+        Some(Err(e)) => Err(e.into()),
+        None => {
+            let response = crate::Error::RequestTimeout("Timed out reading request body".into()).into_response();
+            close_with(socket, response).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Tell a client waiting on `Expect: 100-continue` to send its body
+async fn write_continue<S: AsyncWrite + Unpin>(socket: &mut S) -> std::io::Result<()> {
+    socket.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await?;
+    socket.flush().await
+}
+
+/// Read a buffered request body from `buf` and the socket, leaving any pipelined bytes in `buf`
+///
+/// A `Content-Length` body must already be known to fit; a chunked one fails
+/// once its chunk sizes pass `max_body`.
+async fn read_body<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+    buf: &mut BytesMut,
+    framing: BodyFraming,
+    max_body: Option<usize>,
+) -> std::io::Result<Vec<u8>> {
+    match framing {
+        BodyFraming::Empty => Ok(Vec::new()),
+        BodyFraming::Length(content_length) => {
+            // Take the buffered part of the body, leaving any pipelined bytes after it
+            let buffered = buf.len().min(content_length);
+            let mut body_data = buf.split_to(buffered).to_vec();
+
+            // Grow with the bytes that arrive, never to the length the client claims up front
+            while body_data.len() < content_length {
+                let want = (content_length - body_data.len()).min(BUFFER_SIZE);
+                body_data.reserve(want);
+                if socket.read_buf(&mut (&mut body_data).limit(want)).await? == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+
+            Ok(body_data)
         }
-    } else {
-        (path, None)
+        BodyFraming::Chunked => crate::chunked::read_chunked_body(socket, buf, max_body).await,
     }
 }
 
-async fn write_response(
-    socket: &mut TcpStream,
+/// Write `response` to the socket; answers to HEAD (`omit_body`) send the head only
+async fn write_response<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
     response: &mut Response,
     keep_alive: bool,
+    omit_body: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Pre-allocate buffer for headers
     let mut write_buf = get_buffer();
@@ -973,10 +2301,11 @@ async fn write_response(
         }
     }
 
-    response
-        .headers
-        .entry("Content-Type".to_string())
-        .or_insert_with(|| "text/plain; charset=utf-8".to_string());
+    if !response.headers.contains_key("Content-Type") {
+        response
+            .headers
+            .insert("Content-Type".to_string(), "text/plain; charset=utf-8".to_string());
+    }
 
     for (key, value) in &response.headers {
         write_buf.put_slice(key.as_bytes());
@@ -993,6 +2322,7 @@ async fn write_response(
 
     // Write body
     match &mut response.body {
+        _ if omit_body => {}
         ResponseBody::Static(body_bytes) => {
             socket.write_all(body_bytes).await?;
         }
@@ -1000,6 +2330,8 @@ async fn write_response(
             response.write_stream_to(socket).await?;
         }
     }
+    // Buffering streams (io_uring) only send once flushed
+    socket.flush().await?;
 
     Ok(())
 }
@@ -1056,3 +2388,1274 @@ impl ServerScope {
         self.route("DELETE", path, handler)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PluginResult, Request};
+    use std::any::Any;
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    struct Tagger;
+
+    #[async_trait::async_trait]
+    impl crate::Plugin for Tagger {
+        fn name(&self) -> &'static str {
+            "tagger"
+        }
+
+        async fn on_stream_accept(
+            &self,
+            _stream: AcceptedStream<'_>,
+            peer: &RemoteAddr,
+            conn: &mut Context,
+        ) -> PluginResult<StreamAction> {
+            conn.insert(peer.ip());
+            Ok(StreamAction::Accept)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    struct DenyAll;
+
+    #[async_trait::async_trait]
+    impl crate::Plugin for DenyAll {
+        fn name(&self) -> &'static str {
+            "deny_all"
+        }
+
+        async fn on_stream_accept(
+            &self,
+            _stream: AcceptedStream<'_>,
+            _peer: &RemoteAddr,
+            _conn: &mut Context,
+        ) -> PluginResult<StreamAction> {
+            Ok(StreamAction::Reject)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    async fn loopback_stream() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn stream_accept_tags_every_request_context() {
+        let (mut server, _client) = loopback_stream().await;
+        let plugins: Vec<Arc<dyn crate::Plugin>> = vec![Arc::new(Tagger)];
+
+        let remote_addr = server.peer_addr().unwrap().into();
+        let conn_context = accept_stream(&plugins, AcceptedStream::Tcp(&mut server), &remote_addr)
+            .await
+            .expect("accepted");
+
+        for _ in 0..2 {
+            let mut request = Request::new(
+                Method::GET,
+                Uri::new("/", None),
+                Version::Http11,
+                HeaderMap::new(),
+                Vec::new(),
+                None,
+            );
+            request.context = request_context(&conn_context);
+            request.set_context(42u32);
+
+            let ip = request.get_context::<Option<std::net::IpAddr>>().expect("tagged");
+            assert_eq!(*ip, Some("127.0.0.1".parse().unwrap()));
+        }
+        // Request-scoped values never leak back into the connection
+        assert!(conn_context.get::<u32>().is_none());
+    }
+
+    #[tokio::test]
+    async fn stream_accept_rejection_stops_remaining_plugins() {
+        let (mut server, _client) = loopback_stream().await;
+        let plugins: Vec<Arc<dyn crate::Plugin>> = vec![Arc::new(DenyAll), Arc::new(Tagger)];
+
+        let remote_addr = server.peer_addr().unwrap().into();
+        assert!(accept_stream(&plugins, AcceptedStream::Tcp(&mut server), &remote_addr).await.is_none());
+    }
+
+    fn spawn_connection(server: TcpStream, router: Router) {
+        spawn_connection_with(server, router, Timeouts::default());
+    }
+
+    fn spawn_connection_with(server: TcpStream, router: Router, timeouts: Timeouts) {
+        let remote_addr = server.peer_addr().unwrap().into();
+        tokio::spawn(async move {
+            let _ = handle_connection(
+                server,
+                Arc::new(router),
+                Arc::new(Vec::new()),
+                Arc::new(Vec::new()),
+                remote_addr,
+                Arc::new(HashMap::new()),
+                Arc::new(Vec::new()),
+                Context::new(),
+                ShutdownHandle::new(),
+                timeouts,
+            )
+            .await;
+        });
+    }
+
+    async fn exchange(raw: &[u8]) -> String {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "POST",
+            "/echo",
+            Box::new(|req: Request, _res: Response| async move {
+                Response::new(crate::response::StatusCode::Ok, req.body)
+            }),
+        );
+        spawn_connection(server, router);
+
+        client.write_all(raw).await.unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Router with a streaming `/count` route reporting how the body arrived
+    fn streaming_router() -> Router {
+        let mut router = Router::new();
+        router.add_route(
+            "POST",
+            "/count",
+            Box::new(crate::Streaming(|mut req: Request, _res: Response| async move {
+                let buffered = req.body.len();
+                let mut body = req.take_body_stream();
+                let mut bytes = Vec::new();
+                while let Ok(Some(chunk)) = body.chunk().await {
+                    bytes.extend_from_slice(&chunk);
+                }
+                let text = format!("buffered={buffered} body={}", String::from_utf8_lossy(&bytes));
+                Response::new(crate::response::StatusCode::Ok, text)
+            })),
+        );
+        router.add_route(
+            "POST",
+            "/early",
+            Box::new(crate::Streaming(|_req: Request, _res: Response| async move {
+                Response::new(crate::response::StatusCode::Ok, b"ignored\n")
+            })),
+        );
+        router
+    }
+
+    async fn read_until(client: &mut TcpStream, needle: &str) -> String {
+        let mut out = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !String::from_utf8_lossy(&out).contains(needle) {
+            let n = client.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed early: {}", String::from_utf8_lossy(&out));
+            out.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn body_framing_follows_rfc_9112() {
+        let http11 = Version::Http11;
+        assert_eq!(body_framing(&http11, &[], &[]), Ok(BodyFraming::Empty));
+        assert_eq!(body_framing(&http11, &["0"], &[]), Ok(BodyFraming::Empty));
+        assert_eq!(body_framing(&http11, &["12"], &[]), Ok(BodyFraming::Length(12)));
+        assert_eq!(body_framing(&http11, &["5", "5"], &[]), Ok(BodyFraming::Length(5)));
+        assert_eq!(body_framing(&http11, &[], &["Chunked"]), Ok(BodyFraming::Chunked));
+
+        assert_eq!(body_framing(&http11, &["5", "6"], &[]), Err(StatusCode::BadRequest));
+        assert_eq!(body_framing(&http11, &["+5"], &[]), Err(StatusCode::BadRequest));
+        assert_eq!(body_framing(&http11, &["5"], &["chunked"]), Err(StatusCode::BadRequest));
+        assert_eq!(body_framing(&http11, &[], &["chunked, gzip"]), Err(StatusCode::BadRequest));
+        assert_eq!(body_framing(&http11, &[], &["chunked", "chunked"]), Err(StatusCode::BadRequest));
+        assert_eq!(body_framing(&Version::Http10, &[], &["chunked"]), Err(StatusCode::BadRequest));
+        assert_eq!(
+            body_framing(&http11, &[], &["gzip, chunked"]).unwrap_err().code(),
+            501
+        );
+    }
+
+    #[tokio::test]
+    async fn chunked_request_body_is_decoded() {
+        let response = exchange(
+            b"POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+              5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nX-Trailer: t\r\n\r\n",
+        )
+        .await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with("\r\n\r\nhello world"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn content_length_with_transfer_encoding_is_rejected() {
+        let response = exchange(
+            b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n\
+              0\r\n\r\n",
+        )
+        .await;
+
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{response}");
+        assert!(response.contains("Connection: close"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn malformed_chunk_size_is_rejected() {
+        let response = exchange(
+            b"POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+        )
+        .await;
+
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn claimed_content_length_is_not_allocated_up_front() {
+        let (mut server, mut client) = tokio::io::duplex(64);
+        client.write_all(b"abc").await.unwrap();
+        drop(client);
+
+        let mut buf = BytesMut::from(&b"12"[..]);
+        let err = read_body(&mut server, &mut buf, BodyFraming::Length(usize::MAX / 2), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let (mut server, mut client) = tokio::io::duplex(64);
+        client.write_all(b"cdefNEXT").await.unwrap();
+        let mut buf = BytesMut::from(&b"ab"[..]);
+        let body = read_body(&mut server, &mut buf, BodyFraming::Length(6), None).await.unwrap();
+        assert_eq!(body, b"abcdef");
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_get_content_too_large() {
+        let answer = |raw: &'static [u8]| async move {
+            let (server, mut client) = loopback_stream().await;
+            let mut router = Router::new();
+            router.add_route(
+                "POST",
+                "/echo",
+                Box::new(|req: Request, _res: Response| async move {
+                    Response::new(crate::response::StatusCode::Ok, req.body)
+                }),
+            );
+            let timeouts = Timeouts { max_body: Some(8), ..Timeouts::default() };
+            spawn_connection_with(server, router, timeouts);
+
+            client.write_all(raw).await.unwrap();
+            let mut out = Vec::new();
+            client.read_to_end(&mut out).await.unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let out = answer(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 8\r\nConnection: close\r\n\r\n12345678").await;
+        assert!(out.ends_with("\r\n\r\n12345678"), "{out}");
+
+        // Refused from the header alone, with or without `Expect`
+        for raw in [
+            &b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 999999999999\r\n\r\n"[..],
+            &b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 9\r\nExpect: 100-continue\r\n\r\n"[..],
+        ] {
+            let out = answer(raw).await;
+            assert!(out.starts_with("HTTP/1.1 413 Content Too Large\r\n"), "{out}");
+            assert!(out.contains("Connection: close"), "{out}");
+        }
+
+        // Refused once the chunk sizes pass the limit, even though the body never ends
+        let out = answer(b"POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n5\r\nworld").await;
+        assert!(out.starts_with("HTTP/1.1 413 Content Too Large\r\n"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn streaming_route_reads_body_from_socket() {
+        let (server, mut client) = loopback_stream().await;
+        spawn_connection(server, streaming_router());
+
+        client
+            .write_all(b"POST /count HTTP/1.1\r\nHost: x\r\nContent-Length: 11\r\n\r\nhello")
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        client.write_all(b" world").await.unwrap();
+        let first = read_until(&mut client, "buffered=0 body=hello world").await;
+        assert!(first.contains("Connection: keep-alive"), "{first}");
+
+        // The body was fully read, so the connection can carry another request
+        client
+            .write_all(
+                b"POST /count HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+                  3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).await.unwrap();
+        let rest = String::from_utf8(rest).unwrap();
+        assert!(rest.ends_with("buffered=0 body=abcde"), "{rest}");
+    }
+
+    #[tokio::test]
+    async fn streaming_route_answering_early_closes_connection() {
+        let (server, mut client) = loopback_stream().await;
+        spawn_connection(server, streaming_router());
+
+        // Most of the announced body is never sent
+        client
+            .write_all(b"POST /early HTTP/1.1\r\nHost: x\r\nContent-Length: 1000000\r\n\r\npartial")
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{out}");
+        assert!(out.contains("Connection: close"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn buffered_routes_ignore_streaming() {
+        let response = exchange(
+            b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 4\r\nConnection: close\r\n\r\nping",
+        )
+        .await;
+
+        assert!(response.ends_with("\r\n\r\nping"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn expect_continue_is_answered_before_the_body_is_sent() {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = streaming_router();
+        router.add_route(
+            "POST",
+            "/echo",
+            Box::new(|req: Request, _res: Response| async move {
+                Response::new(crate::response::StatusCode::Ok, req.body)
+            }),
+        );
+        spawn_connection(server, router);
+
+        for path in ["/echo", "/count"] {
+            let head = format!("POST {path} HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n");
+            client.write_all(head.as_bytes()).await.unwrap();
+            let interim = read_until(&mut client, "\r\n\r\n").await;
+            assert_eq!(interim, "HTTP/1.1 100 Continue\r\n\r\n");
+
+            client.write_all(b"hello").await.unwrap();
+            let out = read_until(&mut client, "hello").await;
+            assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{out}");
+            assert!(out.contains("Connection: keep-alive"), "{out}");
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_continue_reads_the_body_for_the_fallback() {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.set_fallback(Box::new(|req: Request, _res: Response| async move {
+            Response::new(crate::response::StatusCode::Ok, req.body)
+        }));
+        spawn_connection(server, router);
+
+        client
+            .write_all(b"POST /anything HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n")
+            .await
+            .unwrap();
+        let interim = read_until(&mut client, "\r\n\r\n").await;
+        assert_eq!(interim, "HTTP/1.1 100 Continue\r\n\r\n");
+
+        client.write_all(b"hello").await.unwrap();
+        let out = read_until(&mut client, "hello").await;
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn expect_continue_body_arrives_after_the_request_phase() {
+        fn body_len(req: &mut Request, _res: &mut Response) -> Flow {
+            let len = req.body.len();
+            req.set_context(len);
+            Flow::Continue
+        }
+
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "POST",
+            "/echo",
+            Box::new(|req: Request, _res: Response| async move {
+                let seen = req.get_context::<usize>().map(|len| *len);
+                let body = format!("middleware={seen:?} handler={}", String::from_utf8_lossy(&req.body));
+                Response::new(crate::response::StatusCode::Ok, body)
+            }),
+        );
+        let remote_addr = server.peer_addr().unwrap().into();
+        tokio::spawn(async move {
+            let _ = handle_connection(
+                server,
+                Arc::new(router),
+                Arc::new(vec![body_len as Middleware]),
+                Arc::new(Vec::new()),
+                remote_addr,
+                Arc::new(HashMap::new()),
+                Arc::new(Vec::new()),
+                Context::new(),
+                ShutdownHandle::new(),
+                Timeouts::default(),
+            )
+            .await;
+        });
+
+        // Without `Expect`, middleware sees the buffered body
+        client
+            .write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhello")
+            .await
+            .unwrap();
+        read_until(&mut client, "middleware=Some(5) handler=hello").await;
+
+        client
+            .write_all(b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n")
+            .await
+            .unwrap();
+        read_until(&mut client, "100 Continue\r\n\r\n").await;
+        client.write_all(b"hello").await.unwrap();
+        read_until(&mut client, "middleware=Some(0) handler=hello").await;
+    }
+
+    #[tokio::test]
+    async fn rejected_expect_continue_gets_final_status_without_100() {
+        let mut router = Router::new();
+        router.add_route(
+            "POST",
+            "/upload",
+            Box::new(crate::Streaming(|mut req: Request, _res: Response| async move {
+                let body = req.take_body_stream();
+                if body.content_length().is_some_and(|len| len > 4) {
+                    return crate::Error::PayloadTooLarge("too big".into()).into_response();
+                }
+                Response::new(crate::response::StatusCode::Ok, b"ok")
+            })),
+        );
+        let router = Arc::new(router);
+
+        for (path, status) in [("/upload", "413"), ("/missing", "404")] {
+            let (server, mut client) = loopback_stream().await;
+            let remote_addr = server.peer_addr().unwrap().into();
+            let router = Arc::clone(&router);
+            tokio::spawn(async move {
+                let _ = handle_connection(
+                    server,
+                    router,
+                    Arc::new(Vec::new()),
+                    Arc::new(Vec::new()),
+                    remote_addr,
+                    Arc::new(HashMap::new()),
+                    Arc::new(Vec::new()),
+                    Context::new(),
+                    ShutdownHandle::new(),
+                    Timeouts::default(),
+                )
+                .await;
+            });
+
+            let head = format!("POST {path} HTTP/1.1\r\nHost: x\r\nContent-Length: 100\r\nExpect: 100-continue\r\n\r\n");
+            client.write_all(head.as_bytes()).await.unwrap();
+            let mut out = Vec::new();
+            client.read_to_end(&mut out).await.unwrap();
+            let out = String::from_utf8(out).unwrap();
+
+            assert!(out.starts_with(&format!("HTTP/1.1 {status} ")), "{out}");
+            assert!(out.contains("Connection: close"), "{out}");
+        }
+    }
+
+    #[tokio::test]
+    async fn paths_are_canonicalized_before_routing() {
+        let answer = |target: &str| {
+            let raw = format!("GET {target} HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n");
+            async move {
+                let mut router = Router::new();
+                router.add_route(
+                    "GET",
+                    "/users/:name",
+                    Box::new(|req: Request, _res: Response| async move {
+                        let body = format!("{} {}", req.uri.path, req.params["name"]);
+                        Response::new(crate::response::StatusCode::Ok, body)
+                    }),
+                );
+                router.set_path_config(crate::PathConfig {
+                    trailing_slash: crate::TrailingSlash::Redirect,
+                    ..Default::default()
+                });
+                let (server, mut client) = loopback_stream().await;
+                spawn_connection(server, router);
+
+                client.write_all(raw.as_bytes()).await.unwrap();
+                let mut out = Vec::new();
+                client.read_to_end(&mut out).await.unwrap();
+                String::from_utf8(out).unwrap()
+            }
+        };
+
+        let out = answer("/users/John%20Doe").await;
+        assert!(out.ends_with("\r\n\r\n/users/John Doe John Doe"), "{out}");
+        let out = answer("//users/x/../a%2Fb").await;
+        assert!(out.ends_with("\r\n\r\n/users/a%2Fb a/b"), "{out}");
+
+        let out = answer("/users/../../etc/passwd").await;
+        assert!(out.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{out}");
+        let out = answer("/users/%FF").await;
+        assert!(out.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{out}");
+
+        let out = answer("/users/ann/?tab=1").await;
+        assert!(out.starts_with("HTTP/1.1 308 Permanent Redirect\r\n"), "{out}");
+        assert!(out.contains("Location: /users/ann?tab=1\r\n"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn virtual_hosts_route_by_host_header() {
+        fn tagged(tag: &'static str) -> Box<dyn AsyncHandler> {
+            Box::new(move |req: Request, _res: Response| async move {
+                let tenant = req.params.get("tenant").cloned().unwrap_or_default();
+                Response::new(crate::response::StatusCode::Ok, format!("{tag} {tenant}"))
+            })
+        }
+        let answer = |host: &'static str, path: &'static str| async move {
+            let mut router = Router::new();
+            router.add_route("GET", "/", tagged("default"));
+            router.add_route("GET", "/status", tagged("status"));
+            router.add_host_route(":tenant.example.com", "GET", "/", tagged("tenant"));
+            router.add_host_route("api.example.com", "GET", "/", tagged("api"));
+            let (server, mut client) = loopback_stream().await;
+            spawn_connection(server, router);
+
+            let raw = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+            client.write_all(raw.as_bytes()).await.unwrap();
+            let mut out = Vec::new();
+            client.read_to_end(&mut out).await.unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert!(answer("api.example.com", "/").await.ends_with("\r\n\r\napi "));
+        assert!(answer("API.Example.com:8080", "/").await.ends_with("\r\n\r\napi "));
+        assert!(answer("acme.example.com", "/").await.ends_with("\r\n\r\ntenant acme"));
+        assert!(answer("example.org", "/").await.ends_with("\r\n\r\ndefault "));
+        assert!(answer("example.org", "/status").await.ends_with("\r\n\r\nstatus "));
+
+        let out = answer("api.example.com", "/status").await;
+        assert!(out.starts_with("HTTP/1.1 404 "), "{out}");
+    }
+
+    #[tokio::test]
+    async fn head_response_omits_body_but_keeps_its_length() {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "GET",
+            "/hello",
+            Box::new(|_req: Request, _res: Response| async move {
+                Response::new(crate::response::StatusCode::Ok, b"hello\n")
+            }),
+        );
+        spawn_connection(server, router);
+
+        // The pipelined GET proves no body bytes followed the HEAD answer
+        client
+            .write_all(b"HEAD /hello HTTP/1.1\r\nHost: x\r\n\r\nGET /hello HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let (head, get) = out.split_at(out.rfind("HTTP/1.1 200 OK").unwrap());
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{out}");
+        assert!(head.contains("Content-Length: 6\r\n"), "{out}");
+        assert!(head.ends_with("\r\n\r\n"), "{out}");
+        assert!(get.ends_with("\r\n\r\nhello\n"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn repeated_headers_get_their_own_lines() {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "GET",
+            "/login",
+            Box::new(|req: Request, _res: Response| async move {
+                let mut res = Response::new(crate::response::StatusCode::Ok, req.header_all("x-tag").join(","));
+                res.headers.insert("content-type".to_string(), "application/json".to_string());
+                res.set_cookie(crate::Cookie::new("session", "abc"));
+                res.set_cookie(crate::Cookie::new("theme", "dark"));
+                res
+            }),
+        );
+        spawn_connection(server, router);
+
+        client
+            .write_all(b"GET /login HTTP/1.1\r\nHost: x\r\nX-Tag: a\r\nx-tag: b\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("\r\nSet-Cookie: session=abc"), "{out}");
+        assert!(out.contains("\r\nSet-Cookie: theme=dark"), "{out}");
+        assert_eq!(out.to_ascii_lowercase().matches("content-type:").count(), 1, "{out}");
+        assert!(out.contains("content-type: application/json\r\n"), "{out}");
+        assert!(out.ends_with("\r\n\r\na,b"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn pipelined_requests_are_answered_in_order() {
+        let response = exchange(
+            b"POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\none\
+              POST /echo HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n3\r\ntwo\r\n0\r\n\r\n\
+              GET /missing HTTP/1.1\r\nHost: x\r\n\r\n\
+              POST /echo HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\nConnection: close\r\n\r\nthree",
+        )
+        .await;
+
+        let statuses: Vec<_> = response.match_indices("HTTP/1.1 ").map(|(i, _)| &response[i + 9..i + 12]).collect();
+        assert_eq!(statuses, ["200", "200", "404", "200"], "{response}");
+        let one = response.find("\r\n\r\none").expect("first body");
+        let two = response.find("\r\n\r\ntwo").expect("second body");
+        assert!(one < two, "{response}");
+        assert!(response.ends_with("\r\n\r\nthree"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn pipelined_bodies_may_straddle_reads() {
+        let (server, mut client) = loopback_stream().await;
+        spawn_connection(server, streaming_router());
+
+        // The first body is split across writes and the next request rides on its tail
+        client
+            .write_all(b"POST /count HTTP/1.1\r\nHost: x\r\nContent-Length: 11\r\n\r\nhel")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        client
+            .write_all(b"lo worldPOST /count HTTP/1.1\r\nHost: x\r\nContent-")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        client
+            .write_all(b"Length: 3\r\nConnection: close\r\n\r\nabc")
+            .await
+            .unwrap();
+
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let first = out.find("body=hello world").expect("first response");
+        assert!(out[first..].ends_with("body=abc"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_in_flight_requests_and_closes_idle_connections() {
+        let addr = {
+            let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            probe.local_addr().unwrap()
+        };
+        let server = Server::new().get("/slow", |_req: Request, _res: Response| async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Response::new(crate::response::StatusCode::Ok, b"done")
+        });
+        let handle = server.shutdown_handle();
+
+        let clients = async {
+            let connect = || async {
+                for _ in 0..100 {
+                    if let Ok(stream) = TcpStream::connect(addr).await {
+                        return stream;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                panic!("server never started listening");
+            };
+            let mut idle = connect().await;
+            let mut busy = connect().await;
+            busy.write_all(b"GET /slow HTTP/1.1\r\nHost: x\r\n\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            handle.shutdown();
+
+            let mut out = Vec::new();
+            busy.read_to_end(&mut out).await.unwrap();
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.contains("Connection: close"), "{out}");
+            assert!(out.ends_with("done"), "{out}");
+
+            let mut buf = [0u8; 16];
+            assert_eq!(idle.read(&mut buf).await.unwrap(), 0);
+        };
+
+        let listen_addr = addr.to_string();
+        let (result, ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(server.listen(&listen_addr), clients) },
+        )
+        .await
+        .expect("shutdown finished before the deadline");
+        assert!(result.is_ok());
+        assert_eq!(handle.active_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn ipv6_listener_is_dual_stack_unless_v6_only() {
+        let dual = bind_listener("[::]:0".parse().unwrap(), false, false).unwrap();
+        let port = dual.local_addr().unwrap().port();
+        let client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let (_, peer) = dual.accept().await.unwrap();
+        assert_eq!(peer.port(), client.local_addr().unwrap().port());
+
+        let v6_only = bind_listener("[::]:0".parse().unwrap(), true, false).unwrap();
+        let port = v6_only.local_addr().unwrap().port();
+        assert!(TcpStream::connect(("127.0.0.1", port)).await.is_err());
+        assert!(TcpStream::connect(("::1", port)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn listen_on_serves_every_address_with_one_router() {
+        let free_port = |ip: &str| {
+            let probe = std::net::TcpListener::bind((ip, 0)).unwrap();
+            probe.local_addr().unwrap()
+        };
+        let addrs = [free_port("127.0.0.1"), free_port("::1")];
+        let server = Server::new().get("/", |_req: Request, _res: Response| async {
+            Response::new(crate::response::StatusCode::Ok, b"shared")
+        });
+        let handle = server.shutdown_handle();
+
+        let clients = async {
+            for addr in addrs {
+                let mut stream = None;
+                for _ in 0..100 {
+                    if let Ok(connected) = TcpStream::connect(addr).await {
+                        stream = Some(connected);
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                let mut stream = stream.expect("server never started listening");
+                stream
+                    .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                let mut out = Vec::new();
+                stream.read_to_end(&mut out).await.unwrap();
+                assert!(String::from_utf8(out).unwrap().ends_with("shared"));
+            }
+            handle.shutdown();
+        };
+
+        let listen_addrs = addrs.map(|addr| addr.to_string());
+        let (result, ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(server.listen_on(&listen_addrs), clients) },
+        )
+        .await
+        .expect("server stopped before the deadline");
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn thread_per_core_serves_on_worker_threads() {
+        let server_config = crate::ServerConfig {
+            workers: 2,
+            runtime: crate::RuntimeMode::ThreadPerCore,
+            ..Default::default()
+        };
+        let listeners = bind_all(&["127.0.0.1:0"], &server_config).unwrap();
+        let Listener::Tcp(listener) = &listeners[0].0 else { unreachable!() };
+        let addr = listener.local_addr().unwrap();
+
+        let server = Server::new().get("/", |_req: Request, _res: Response| async {
+            let thread = std::thread::current().name().unwrap_or_default().to_string();
+            Response::new(crate::response::StatusCode::Ok, thread.into_bytes())
+        });
+        let handle = server.shutdown_handle();
+
+        let clients = async {
+            for _ in 0..8 {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream
+                    .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                let mut out = Vec::new();
+                stream.read_to_end(&mut out).await.unwrap();
+                let out = String::from_utf8(out).unwrap();
+                assert!(out.ends_with("firework-worker-0") || out.ends_with("firework-worker-1"), "{}", out);
+            }
+            handle.shutdown();
+        };
+
+        let (result, ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(server.serve(listeners, server_config), clients) },
+        )
+        .await
+        .expect("workers stopped before the deadline");
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "io-uring")]
+    #[tokio::test]
+    async fn io_uring_workers_keep_connections_alive() {
+        if !crate::io_uring_available() {
+            eprintln!("io_uring unavailable here, skipping");
+            return;
+        }
+        let server_config = crate::ServerConfig {
+            workers: 1,
+            runtime: crate::RuntimeMode::IoUring,
+            ..Default::default()
+        };
+        let listeners = bind_all(&["127.0.0.1:0"], &server_config).unwrap();
+        let Listener::Tcp(listener) = &listeners[0].0 else { unreachable!() };
+        let addr = listener.local_addr().unwrap();
+
+        let server = Server::new().post("/echo", |req: Request, _res: Response| async move {
+            Response::new(crate::response::StatusCode::Ok, req.body)
+        });
+        let handle = server.shutdown_handle();
+
+        let client = async {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let body = "x".repeat(3 * BUFFER_SIZE);
+            for body in ["first", body.as_str()] {
+                let request = format!("POST /echo HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                stream.write_all(request.as_bytes()).await.unwrap();
+                let response = read_until(&mut stream, body).await;
+                assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+            }
+            handle.shutdown();
+        };
+
+        let (result, ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(server.serve(listeners, server_config), client) },
+        )
+        .await
+        .expect("io_uring worker stopped before the deadline");
+        assert!(result.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn thread_per_core_workers_get_their_own_sockets() {
+        use std::os::fd::AsRawFd;
+
+        let tcp = Listener::Tcp(bind_listener("127.0.0.1:0".parse().unwrap(), false, true).unwrap());
+        let WorkerSocket::Tcp(first) = tcp.worker_socket(true).unwrap() else { unreachable!() };
+        let WorkerSocket::Tcp(second) = tcp.worker_socket(false).unwrap() else { unreachable!() };
+        let Listener::Tcp(original) = &tcp else { unreachable!() };
+        assert_eq!(first.local_addr().unwrap(), original.local_addr().unwrap());
+        assert_eq!(second.local_addr().unwrap(), original.local_addr().unwrap());
+
+        // Same socket for the first worker, a separate SO_REUSEPORT one for the rest
+        let inode = |fd: std::os::fd::RawFd| {
+            let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+            assert_eq!(unsafe { libc::fstat(fd, &mut stat) }, 0);
+            stat.st_ino
+        };
+        assert_eq!(inode(first.as_raw_fd()), inode(original.as_raw_fd()));
+        assert_ne!(inode(second.as_raw_fd()), inode(original.as_raw_fd()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listen_addr_accepts_tcp_and_unix() {
+        assert_eq!(
+            ListenAddr::parse("[::1]:8080").unwrap(),
+            ListenAddr::Tcp("[::1]:8080".parse().unwrap())
+        );
+        assert_eq!(
+            ListenAddr::parse("unix:/run/app.sock").unwrap(),
+            ListenAddr::Unix("/run/app.sock".into())
+        );
+        assert!(ListenAddr::parse("unix:").is_err());
+        assert!(ListenAddr::parse("localhost").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_listener_replaces_only_stale_sockets() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+
+        // Left behind by a server that is gone
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let live = bind_unix_listener(&path, Some(0o660)).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+
+        let err = bind_unix_listener(&path, None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+        drop(live);
+
+        let file = dir.path().join("not-a-socket");
+        std::fs::write(&file, "keep me").unwrap();
+        assert!(bind_unix_listener(&file, None).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_unix_socket_with_peer_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+        let server = Server::new().get("/", |req: Request, _res: Response| async move {
+            let remote = req.remote_addr.expect("peer address");
+            let cred = remote.peer_cred().expect("peer credentials");
+            Response::new(crate::response::StatusCode::Ok, format!("{} uid={}", remote.ip().is_none(), cred.uid))
+        });
+        let handle = server.shutdown_handle();
+
+        let clients = async {
+            let mut stream = None;
+            for _ in 0..100 {
+                if let Ok(connected) = tokio::net::UnixStream::connect(&path).await {
+                    stream = Some(connected);
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let mut stream = stream.expect("server never started listening");
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut out = Vec::new();
+            stream.read_to_end(&mut out).await.unwrap();
+            let out = String::from_utf8(out).unwrap();
+            let uid = unsafe { libc::getuid() };
+            assert!(out.ends_with(&format!("true uid={uid}")), "{out}");
+            handle.shutdown();
+        };
+
+        let listen_addr = format!("unix:{}", path.display());
+        let (result, ()) = tokio::time::timeout(
+            Duration::from_secs(5),
+            async { tokio::join!(server.listen(&listen_addr), clients) },
+        )
+        .await
+        .expect("server stopped before the deadline");
+        assert!(result.is_ok());
+        assert!(!path.exists(), "socket file removed on shutdown");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stream_accept_rejects_unix_peers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deny.sock");
+        let listener = Listener::Unix(bind_unix_listener(&path, None).unwrap(), None);
+        let shared = Shared {
+            router: Arc::new(Router::new()),
+            middlewares: Arc::new(Vec::new()),
+            async_middlewares: Arc::new(Vec::new()),
+            ws_routes: Arc::new(HashMap::new()),
+            plugins: Arc::new(vec![Arc::new(DenyAll) as Arc<dyn crate::Plugin>]),
+            shutdown: ShutdownHandle::new(),
+            timeouts: Timeouts::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        };
+        let accepting = tokio::spawn(async move { accept_loop(&listener, &shared).await });
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let _ = stream.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        let mut out = Vec::new();
+        let _ = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut out))
+            .await
+            .expect("rejected connection was left open");
+        assert!(out.is_empty(), "{}", String::from_utf8_lossy(&out));
+        accepting.abort();
+    }
+
+    #[test]
+    fn second_bind_on_a_port_fails() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_io().build().unwrap();
+        let _guard = runtime.enter();
+
+        let first = bind_listener("127.0.0.1:0".parse().unwrap(), false, false).unwrap();
+        let err = bind_listener(first.local_addr().unwrap(), false, false).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn adopts_inherited_tcp_and_unix_listeners() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let (listener, label) = Listener::adopt(tcp.into()).unwrap();
+        assert_eq!(label, addr.to_string());
+        let _client = TcpStream::connect(addr).await.unwrap();
+        assert!(matches!(listener.accept().await.unwrap().1, RemoteAddr::Ip(_)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("activated.sock");
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let (listener, label) = Listener::adopt(unix.into()).unwrap();
+        assert_eq!(label, format!("unix:{}", path.display()));
+        let _client = tokio::net::UnixStream::connect(&path).await.unwrap();
+        assert!(matches!(listener.accept().await.unwrap().1, RemoteAddr::Unix(Some(_))));
+
+        // The socket file belongs to systemd, not to us
+        drop(listener);
+        assert!(path.exists());
+    }
+
+    fn short_timeouts() -> Timeouts {
+        Timeouts {
+            header: Some(Duration::from_millis(100)),
+            body: Some(Duration::from_millis(100)),
+            keep_alive: Some(Duration::from_millis(100)),
+            handler: Some(Duration::from_millis(100)),
+            max_body: None,
+        }
+    }
+
+    #[test]
+    fn zero_timeouts_mean_no_limit() {
+        let config = crate::ServerConfig {
+            header_timeout: 0,
+            request_timeout: 5,
+            ..Default::default()
+        };
+        let timeouts = Timeouts::from_config(&config);
+        assert_eq!(timeouts.header, None);
+        assert_eq!(timeouts.handler, Some(Duration::from_secs(5)));
+    }
+
+    #[tokio::test]
+    async fn slow_headers_get_request_timeout() {
+        let (server, mut client) = loopback_stream().await;
+        spawn_connection_with(server, Router::new(), short_timeouts());
+
+        client.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n").await.unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 408"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn stalled_body_gets_request_timeout() {
+        let (server, mut client) = loopback_stream().await;
+        spawn_connection_with(server, Router::new(), short_timeouts());
+
+        client
+            .write_all(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc")
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 408"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn slow_handler_gets_service_unavailable() {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "GET",
+            "/slow",
+            Box::new(|_req: Request, _res: Response| async move {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Response::new(crate::response::StatusCode::Ok, b"late\n")
+            }),
+        );
+        spawn_connection_with(server, router, short_timeouts());
+
+        client.write_all(b"GET /slow HTTP/1.1\r\n\r\n").await.unwrap();
+        let out = read_until(&mut client, "timed out").await;
+        assert!(out.starts_with("HTTP/1.1 503"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn steady_streamed_upload_outlives_handler_timeout() {
+        let (server, mut client) = loopback_stream().await;
+        spawn_connection_with(server, streaming_router(), short_timeouts());
+
+        // Each chunk arrives within the body timeout, the whole upload takes
+        // several handler timeouts
+        client
+            .write_all(b"POST /count HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        for _ in 0..8 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            client.write_all(b"1\r\nx\r\n").await.unwrap();
+        }
+        client.write_all(b"0\r\n\r\n").await.unwrap();
+
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{out}");
+        assert!(out.ends_with("buffered=0 body=xxxxxxxx"), "{out}");
+    }
+
+    #[tokio::test]
+    async fn idle_keep_alive_connection_is_closed() {
+        let (server, mut client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "GET",
+            "/",
+            Box::new(|_req: Request, _res: Response| async move {
+                Response::new(crate::response::StatusCode::Ok, b"ok\n")
+            }),
+        );
+        spawn_connection_with(server, router, short_timeouts());
+
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        read_until(&mut client, "ok\n").await;
+
+        // Nothing more is sent; the server hangs up without a response
+        let mut rest = Vec::new();
+        tokio::time::timeout(Duration::from_secs(2), client.read_to_end(&mut rest))
+            .await
+            .expect("idle connection closed")
+            .unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[cfg(feature = "http2")]
+    #[tokio::test]
+    async fn h2_body_stream_knows_the_content_length() {
+        let (server, client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "POST",
+            "/length",
+            Box::new(crate::Streaming(|mut req: Request, _res: Response| async move {
+                let body = req.take_body_stream();
+                Response::new(crate::response::StatusCode::Ok, format!("{:?}", body.content_length()))
+            })),
+        );
+        spawn_connection(server, router);
+
+        let (mut send, connection) = h2::client::handshake(client).await.unwrap();
+        tokio::spawn(connection);
+        let mut answer = |content_length: Option<&str>| {
+            let mut request = http::Request::post("http://localhost/length");
+            if let Some(len) = content_length {
+                request = request.header("content-length", len);
+            }
+            let (response, mut body) = send.send_request(request.body(()).unwrap(), false).unwrap();
+            body.send_data(bytes::Bytes::from_static(b"hello"), true).unwrap();
+            async move {
+                let mut body = response.await.unwrap().into_body();
+                let mut bytes = Vec::new();
+                while let Some(chunk) = body.data().await {
+                    bytes.extend_from_slice(&chunk.unwrap());
+                }
+                String::from_utf8(bytes).unwrap()
+            }
+        };
+
+        assert_eq!(answer(Some("5")).await, "Some(5)");
+        assert_eq!(answer(None).await, "None");
+    }
+
+    #[cfg(feature = "tls")]
+    async fn tls_client(alpn: &[&[u8]]) -> tokio_rustls::client::TlsStream<TcpStream> {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, cert) = crate::tls::tests::self_signed(dir.path());
+        let tls = Arc::new(crate::tls::TlsAcceptor::load(&config).unwrap());
+
+        let (server, client) = loopback_stream().await;
+        let mut router = Router::new();
+        router.add_route(
+            "GET",
+            "/",
+            Box::new(|req: Request, _res: Response| async move {
+                let mut res = Response::new(crate::response::StatusCode::Ok, format!("secure {:?}\n", req.version));
+                res.headers.insert("content-type".to_string(), "text/plain".to_string());
+                res.set_cookie(crate::Cookie::new("a", "1"));
+                res.set_cookie(crate::Cookie::new("b", "2"));
+                res
+            }),
+        );
+        let remote_addr = server.peer_addr().unwrap().into();
+        tokio::spawn(async move {
+            let _ = handle_tls_connection(
+                server,
+                tls,
+                Arc::new(router),
+                Arc::new(Vec::new()),
+                Arc::new(Vec::new()),
+                remote_addr,
+                Arc::new(HashMap::new()),
+                Arc::new(Vec::new()),
+                Context::new(),
+                ShutdownHandle::new(),
+                Timeouts::default(),
+            )
+            .await;
+        });
+
+        let name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
+        crate::tls::tests::connector(cert, alpn).connect(name, client).await.unwrap()
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn serves_http1_over_tls() {
+        let mut client = tls_client(&[b"http/1.1"]).await;
+        client
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        client.read_to_end(&mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 200"), "{out}");
+        assert!(out.ends_with("secure Http11\n"), "{out}");
+    }
+
+    #[cfg(all(feature = "tls", feature = "http2"))]
+    #[tokio::test]
+    async fn alpn_h2_serves_http2_over_tls() {
+        let client = tls_client(&[b"h2", b"http/1.1"]).await;
+        let (mut send, connection) = h2::client::handshake(client).await.unwrap();
+        tokio::spawn(connection);
+
+        let request = http::Request::get("https://localhost/").body(()).unwrap();
+        let (response, _) = send.send_request(request, true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let cookies: Vec<_> = response.headers().get_all("set-cookie").iter().collect();
+        assert_eq!(cookies.len(), 2, "{:?}", response.headers());
+        assert_eq!(response.headers().get_all("content-type").iter().count(), 1);
+        assert_eq!(response.headers().get_all("content-length").iter().count(), 1);
+
+        let mut body = response.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(bytes, b"secure Http2\n");
+    }
+}
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{watch, Notify};
+
+/// Triggers and observes the graceful shutdown of a running `Server`
+///
+/// Shutdown stops the accept loop, closes idle keep-alive connections, answers
+/// in-flight requests with `Connection: close`, and waits for them (up to
+/// `ServerConfig::shutdown_timeout`) before plugins are shut down.
+///
+/// ```rust,ignore
+/// let server = routes!();
+/// let handle = server.shutdown_handle();
+/// tokio::spawn(server.listen("127.0.0.1:8080"));
+/// // ...
+/// handle.shutdown();
+/// ```
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    requested: watch::Sender<bool>,
+    active: AtomicUsize,
+    drained: Notify,
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                requested: watch::Sender::new(false),
+                active: AtomicUsize::new(0),
+                drained: Notify::new(),
+            }),
+        }
+    }
+
+    /// Begin graceful shutdown; calling it again has no further effect
+    pub fn shutdown(&self) {
+        self.inner.requested.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.inner.requested.borrow()
+    }
+
+    /// Connections currently open (idle or serving a request)
+    pub fn active_connections(&self) -> usize {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    /// Resolves once shutdown has been requested
+    pub async fn requested(&self) {
+        let mut rx = self.inner.requested.subscribe();
+        let _ = rx.wait_for(|requested| *requested).await;
+    }
+
+    /// Resolves once every tracked connection has closed
+    pub(crate) async fn drained(&self) {
+        loop {
+            let notified = self.inner.drained.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.active_connections() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Count a connection as open until the returned guard is dropped
+    pub(crate) fn track(&self) -> ConnectionGuard {
+        self.inner.active.fetch_add(1, Ordering::AcqRel);
+        ConnectionGuard {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("shutting_down", &self.is_shutting_down())
+            .field("active_connections", &self.active_connections())
+            .finish()
+    }
+}
+
+pub(crate) struct ConnectionGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.inner.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.drained.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn drained_waits_for_every_connection() {
+        let handle = ShutdownHandle::new();
+        let first = handle.track();
+        let second = handle.track();
+        assert_eq!(handle.active_connections(), 2);
+
+        let waiter = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.drained().await }
+        });
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        drop(second);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("drained after last connection closed")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn requested_resolves_for_late_waiters() {
+        let handle = ShutdownHandle::new();
+        assert!(!handle.is_shutting_down());
+
+        handle.shutdown();
+        handle.shutdown();
+        assert!(handle.is_shutting_down());
+        tokio::time::timeout(Duration::from_secs(1), handle.requested())
+            .await
+            .expect("already requested");
+    }
+}
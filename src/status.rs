@@ -0,0 +1,305 @@
+//! HTTP status codes and their reason phrases
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// HTTP response status
+///
+/// The most common codes are variants; every code in the IANA registry is
+/// also an associated constant (`StatusCode::CONFLICT`). Two statuses are
+/// equal when their codes are, whatever reason phrase a `Custom` carries.
+#[derive(Debug, Clone)]
+pub enum StatusCode {
+    Ok,
+    Created,
+    NoContent,
+    Found,
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    InternalServerError,
+    Custom(u16, Cow<'static, str>),
+}
+
+/// Reason phrase `from_u16` gives a code missing from the registry
+const UNREGISTERED_REASON: &str = "Custom";
+
+macro_rules! status_value {
+    ($code:literal, $phrase:literal) => {
+        StatusCode::Custom($code, Cow::Borrowed($phrase))
+    };
+    ($code:literal, $phrase:literal, $variant:ident) => {
+        StatusCode::$variant
+    };
+}
+
+macro_rules! status_codes {
+    ($($code:literal $name:ident $phrase:literal $(=> $variant:ident)?;)*) => {
+        impl StatusCode {
+            $(
+                #[doc = concat!("`", stringify!($code), " ", $phrase, "`")]
+                pub const $name: StatusCode = status_value!($code, $phrase $(, $variant)?);
+            )*
+        }
+
+        /// Registered reason phrase for `code`
+        fn canonical_reason(code: u16) -> Option<&'static str> {
+            match code {
+                $($code => Some($phrase),)*
+                _ => None,
+            }
+        }
+
+        /// `"<code> <phrase>"` for a registered code
+        fn canonical_line(code: u16) -> Option<&'static str> {
+            match code {
+                $($code => Some(concat!(stringify!($code), " ", $phrase)),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+// https://www.iana.org/assignments/http-status-codes
+status_codes! {
+    100 CONTINUE "Continue";
+    101 SWITCHING_PROTOCOLS "Switching Protocols";
+    102 PROCESSING "Processing";
+    103 EARLY_HINTS "Early Hints";
+    200 OK "OK" => Ok;
+    201 CREATED "Created" => Created;
+    202 ACCEPTED "Accepted";
+    203 NON_AUTHORITATIVE_INFORMATION "Non-Authoritative Information";
+    204 NO_CONTENT "No Content" => NoContent;
+    205 RESET_CONTENT "Reset Content";
+    206 PARTIAL_CONTENT "Partial Content";
+    207 MULTI_STATUS "Multi-Status";
+    208 ALREADY_REPORTED "Already Reported";
+    226 IM_USED "IM Used";
+    300 MULTIPLE_CHOICES "Multiple Choices";
+    301 MOVED_PERMANENTLY "Moved Permanently";
+    302 FOUND "Found" => Found;
+    303 SEE_OTHER "See Other";
+    304 NOT_MODIFIED "Not Modified";
+    305 USE_PROXY "Use Proxy";
+    307 TEMPORARY_REDIRECT "Temporary Redirect";
+    308 PERMANENT_REDIRECT "Permanent Redirect";
+    400 BAD_REQUEST "Bad Request" => BadRequest;
+    401 UNAUTHORIZED "Unauthorized" => Unauthorized;
+    402 PAYMENT_REQUIRED "Payment Required";
+    403 FORBIDDEN "Forbidden" => Forbidden;
+    404 NOT_FOUND "Not Found" => NotFound;
+    405 METHOD_NOT_ALLOWED "Method Not Allowed";
+    406 NOT_ACCEPTABLE "Not Acceptable";
+    407 PROXY_AUTHENTICATION_REQUIRED "Proxy Authentication Required";
+    408 REQUEST_TIMEOUT "Request Timeout";
+    409 CONFLICT "Conflict";
+    410 GONE "Gone";
+    411 LENGTH_REQUIRED "Length Required";
+    412 PRECONDITION_FAILED "Precondition Failed";
+    413 CONTENT_TOO_LARGE "Content Too Large";
+    414 URI_TOO_LONG "URI Too Long";
+    415 UNSUPPORTED_MEDIA_TYPE "Unsupported Media Type";
+    416 RANGE_NOT_SATISFIABLE "Range Not Satisfiable";
+    417 EXPECTATION_FAILED "Expectation Failed";
+    421 MISDIRECTED_REQUEST "Misdirected Request";
+    422 UNPROCESSABLE_CONTENT "Unprocessable Content";
+    423 LOCKED "Locked";
+    424 FAILED_DEPENDENCY "Failed Dependency";
+    425 TOO_EARLY "Too Early";
+    426 UPGRADE_REQUIRED "Upgrade Required";
+    428 PRECONDITION_REQUIRED "Precondition Required";
+    429 TOO_MANY_REQUESTS "Too Many Requests";
+    431 REQUEST_HEADER_FIELDS_TOO_LARGE "Request Header Fields Too Large";
+    451 UNAVAILABLE_FOR_LEGAL_REASONS "Unavailable For Legal Reasons";
+    500 INTERNAL_SERVER_ERROR "Internal Server Error" => InternalServerError;
+    501 NOT_IMPLEMENTED "Not Implemented";
+    502 BAD_GATEWAY "Bad Gateway";
+    503 SERVICE_UNAVAILABLE "Service Unavailable";
+    504 GATEWAY_TIMEOUT "Gateway Timeout";
+    505 HTTP_VERSION_NOT_SUPPORTED "HTTP Version Not Supported";
+    506 VARIANT_ALSO_NEGOTIATES "Variant Also Negotiates";
+    507 INSUFFICIENT_STORAGE "Insufficient Storage";
+    508 LOOP_DETECTED "Loop Detected";
+    510 NOT_EXTENDED "Not Extended";
+    511 NETWORK_AUTHENTICATION_REQUIRED "Network Authentication Required";
+}
+
+impl StatusCode {
+    /// `413` under its former name (RFC 7231)
+    pub const PAYLOAD_TOO_LARGE: StatusCode = StatusCode::CONTENT_TOO_LARGE;
+    /// `422` under its former name (RFC 4918)
+    pub const UNPROCESSABLE_ENTITY: StatusCode = StatusCode::UNPROCESSABLE_CONTENT;
+
+    /// Status for `code`, with its registered reason phrase, or `"Custom"` for a
+    /// code missing from the registry
+    pub fn from_u16(code: u16) -> StatusCode {
+        match code {
+            200 => StatusCode::Ok,
+            201 => StatusCode::Created,
+            204 => StatusCode::NoContent,
+            302 => StatusCode::Found,
+            400 => StatusCode::BadRequest,
+            401 => StatusCode::Unauthorized,
+            403 => StatusCode::Forbidden,
+            404 => StatusCode::NotFound,
+            500 => StatusCode::InternalServerError,
+            code => StatusCode::Custom(code, Cow::Borrowed(canonical_reason(code).unwrap_or(UNREGISTERED_REASON))),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            StatusCode::Ok => 200,
+            StatusCode::Created => 201,
+            StatusCode::NoContent => 204,
+            StatusCode::Found => 302,
+            StatusCode::BadRequest => 400,
+            StatusCode::Unauthorized => 401,
+            StatusCode::Forbidden => 403,
+            StatusCode::NotFound => 404,
+            StatusCode::InternalServerError => 500,
+            StatusCode::Custom(code, _) => *code,
+        }
+    }
+
+    /// Reason phrase: the one a `Custom` carries, else the registered one
+    pub fn reason(&self) -> &str {
+        match self {
+            StatusCode::Custom(_, reason) => reason,
+            status => canonical_reason(status.code()).unwrap_or(""),
+        }
+    }
+
+    /// Status line text, e.g. `"404 Not Found"`
+    ///
+    /// Borrowed for every registered code with its registered phrase; only a
+    /// `Custom` with its own phrase is formatted.
+    pub fn as_str(&self) -> Cow<'static, str> {
+        let code = self.code();
+        match canonical_line(code) {
+            Some(line) if self.reason() == canonical_reason(code).unwrap_or_default() => Cow::Borrowed(line),
+            _ => Cow::Owned(format!("{} {}", code, self.reason())),
+        }
+    }
+
+    /// `1xx`
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.code())
+    }
+
+    /// `2xx`
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+
+    /// `3xx`
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.code())
+    }
+
+    /// `4xx`
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.code())
+    }
+
+    /// `5xx`
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.code())
+    }
+}
+
+impl PartialEq for StatusCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.code() == other.code()
+    }
+}
+
+impl Eq for StatusCode {}
+
+impl std::hash::Hash for StatusCode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.code().hash(state);
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
+impl From<u16> for StatusCode {
+    fn from(code: u16) -> Self {
+        StatusCode::from_u16(code)
+    }
+}
+
+impl From<http::StatusCode> for StatusCode {
+    fn from(status: http::StatusCode) -> Self {
+        StatusCode::from_u16(status.as_u16())
+    }
+}
+
+/// Fails for codes outside `100..=999`, which HTTP cannot carry
+impl TryFrom<&StatusCode> for http::StatusCode {
+    type Error = http::status::InvalidStatusCode;
+
+    fn try_from(status: &StatusCode) -> Result<Self, Self::Error> {
+        http::StatusCode::from_u16(status.code())
+    }
+}
+
+impl TryFrom<StatusCode> for http::StatusCode {
+    type Error = http::status::InvalidStatusCode;
+
+    fn try_from(status: StatusCode) -> Result<Self, Self::Error> {
+        http::StatusCode::try_from(&status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_codes_have_phrases_and_static_lines() {
+        assert_eq!(StatusCode::CONFLICT.as_str(), "409 Conflict");
+        assert!(matches!(StatusCode::TOO_MANY_REQUESTS.as_str(), Cow::Borrowed("429 Too Many Requests")));
+        assert!(matches!(StatusCode::Ok.as_str(), Cow::Borrowed("200 OK")));
+        assert!(matches!(StatusCode::from_u16(418).as_str(), Cow::Owned(_)));
+        assert_eq!(StatusCode::from_u16(799).as_str(), "799 Custom");
+        assert_eq!(StatusCode::Custom(599, "Custom".into()).as_str(), "599 Custom");
+        assert_eq!(StatusCode::Custom(404, "Gone Fishing".into()).as_str(), "404 Gone Fishing");
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE.reason(), "Content Too Large");
+    }
+
+    #[test]
+    fn variants_constants_and_customs_compare_by_code() {
+        assert_eq!(StatusCode::NOT_FOUND, StatusCode::NotFound);
+        assert!(matches!(StatusCode::from_u16(404), StatusCode::NotFound));
+        assert_eq!(StatusCode::Custom(405, "Method Not Allowed".into()), StatusCode::METHOD_NOT_ALLOWED);
+        assert_ne!(StatusCode::CONFLICT, StatusCode::GONE);
+    }
+
+    #[test]
+    fn range_helpers() {
+        assert!(StatusCode::SWITCHING_PROTOCOLS.is_informational());
+        assert!(StatusCode::NoContent.is_success());
+        assert!(StatusCode::PERMANENT_REDIRECT.is_redirect());
+        assert!(StatusCode::NotFound.is_client_error() && !StatusCode::NotFound.is_server_error());
+        assert!(StatusCode::GATEWAY_TIMEOUT.is_server_error());
+    }
+
+    #[test]
+    fn converts_to_and_from_http() {
+        for code in [100, 200, 308, 404, 418, 429, 511, 599, 999] {
+            let http = http::StatusCode::from_u16(code).unwrap();
+            let status = StatusCode::from(http);
+            assert_eq!(status.code(), code);
+            assert_eq!(http::StatusCode::try_from(&status).unwrap(), http);
+        }
+        assert!(http::StatusCode::try_from(StatusCode::Custom(42, "Nope".into())).is_err());
+    }
+}
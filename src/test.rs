@@ -1,13 +1,17 @@
-use crate::{Method, Request, Response, Router, Server, Uri, Version, Flow, Middleware, AsyncMiddleware};
-use std::collections::HashMap;
+use crate::{Method, Plugin, Request, Response, Router, Server, Uri, Version, Middleware, AsyncMiddleware};
 use std::sync::Arc;
-use ahash::AHashMap;
 
 /// Test client for making requests to the application
+///
+/// Requests go through the same middleware, plugin hooks and routing as on
+/// the server. Plugins come from the global registry, as when serving, plus
+/// any added with `with_plugin`; their lifecycle hooks (`on_init`,
+/// `on_start`) are not run.
 pub struct TestClient {
     router: Arc<Router>,
     middlewares: Vec<Middleware>,
     async_middlewares: Vec<AsyncMiddleware>,
+    plugins: Vec<Arc<dyn Plugin>>,
 }
 
 impl TestClient {
@@ -17,9 +21,17 @@ impl TestClient {
             router: Arc::new(server.router),
             middlewares: server.middlewares,
             async_middlewares: server.async_middlewares,
+            plugins: Vec::new(),
         }
     }
 
+    /// Run a plugin's request and response hooks on every request, besides the registered ones
+    pub fn with_plugin(mut self, plugin: Arc<dyn Plugin>) -> Self {
+        self.plugins.push(plugin);
+        crate::plugin::sort_by_priority(&mut self.plugins);
+        self
+    }
+
     /// Create a GET request
     pub fn get(&self, path: &str) -> TestRequest<'_> {
         TestRequest::new(self, Method::GET, path)
@@ -45,48 +57,67 @@ impl TestClient {
         TestRequest::new(self, Method::DELETE, path)
     }
 
+    /// Create a HEAD request
+    pub fn head(&self, path: &str) -> TestRequest<'_> {
+        TestRequest::new(self, Method::HEAD, path)
+    }
+
+    /// Create an OPTIONS request
+    pub fn options(&self, path: &str) -> TestRequest<'_> {
+        TestRequest::new(self, Method::OPTIONS, path)
+    }
+
     /// Execute a request and return the response
     async fn execute(&self, mut request: Request) -> TestResponse {
-        let mut response = Response::default();
-        let mut stopped = false;
-
-        // Apply sync middlewares
-        for mw in &self.middlewares {
-            match mw(&mut request, &mut response) {
-                Flow::Stop(final_res) => {
-                    response = final_res;
-                    stopped = true;
-                    break;
-                }
-                Flow::Continue => {}
-            }
+        // Requests are routed on their canonical path, as on the wire
+        match crate::server::canonical_path(&self.router, &request.uri.path) {
+            Ok(path) => request.uri.path = path.into_owned(),
+            Err(response) => return TestResponse::new(response),
         }
+        let mut response = Response::default();
+        let plugins = self.plugins().await;
+
+        let stopped = crate::server::run_request_phase(
+            &self.middlewares,
+            &self.async_middlewares,
+            &plugins,
+            &mut request,
+            &mut response,
+        )
+        .await;
+
+        let head = matches!(request.method, Method::HEAD);
+        let mut response = crate::server::run_handler_phase(
+            &self.router,
+            &plugins,
+            request,
+            response,
+            stopped,
+            None,
+        )
+        .await;
 
-        // Apply async middlewares
-        if !stopped {
-            for mw in &self.async_middlewares {
-                match mw(&mut request, &mut response).await {
-                    Flow::Stop(final_res) => {
-                        response = final_res;
-                        stopped = true;
-                        break;
-                    }
-                    Flow::Continue => {}
-                }
+        // Like the server, answer HEAD with the GET head and no body
+        if head {
+            if let Some(len) = response.body.len() {
+                response.headers.insert("Content-Length".to_string(), len.to_string());
             }
+            response.body = crate::response::ResponseBody::Static(Vec::new());
         }
 
-        // Find route handler
-        if !stopped {
-            if let Some((handler, params)) = self.router.find(&request.method, &request.uri.path) {
-                request.params = params;
-                response = handler.call(request, response).await;
-            } else {
-                response = Response::new(crate::response::StatusCode::NotFound, b"Not Found");
+        TestResponse::new(response)
+    }
+
+    /// Registered plugins, as the server caches them, and those added with `with_plugin`
+    async fn plugins(&self) -> Vec<Arc<dyn Plugin>> {
+        let mut plugins = crate::plugin::registry().read().await.plugins().to_vec();
+        for plugin in &self.plugins {
+            if !plugins.iter().any(|registered| Arc::ptr_eq(registered, plugin)) {
+                plugins.push(Arc::clone(plugin));
             }
         }
-
-        TestResponse::new(response)
+        crate::plugin::sort_by_priority(&mut plugins);
+        plugins
     }
 }
 
@@ -95,9 +126,9 @@ pub struct TestRequest<'a> {
     client: &'a TestClient,
     method: Method,
     path: String,
-    headers: AHashMap<String, Vec<String>>,
+    headers: crate::HeaderMap,
     body: Vec<u8>,
-    query: HashMap<String, String>,
+    query: Vec<(String, String)>,
 }
 
 impl<'a> TestRequest<'a> {
@@ -106,28 +137,22 @@ impl<'a> TestRequest<'a> {
             client,
             method,
             path: path.to_string(),
-            headers: AHashMap::new(),
+            headers: crate::HeaderMap::new(),
             body: Vec::new(),
-            query: HashMap::new(),
+            query: Vec::new(),
         }
     }
 
     /// Add a header to the request
     pub fn header(mut self, key: &str, value: &str) -> Self {
-        self.headers
-            .entry(key.to_string())
-            .or_insert_with(Vec::new)
-            .push(value.to_string());
+        self.headers.append(key.to_string(), value.to_string());
         self
     }
 
     /// Set JSON body (automatically sets Content-Type)
     pub fn json(mut self, json: &str) -> Self {
         self.body = json.as_bytes().to_vec();
-        self.headers
-            .entry("Content-Type".to_string())
-            .or_insert_with(Vec::new)
-            .push("application/json".to_string());
+        self.headers.insert("Content-Type".to_string(), "application/json".to_string());
         self
     }
 
@@ -143,15 +168,22 @@ impl<'a> TestRequest<'a> {
         self
     }
 
-    /// Add a query parameter
+    /// Add a query parameter (call again with the same key to repeat it)
     pub fn query(mut self, key: &str, value: &str) -> Self {
-        self.query.insert(key.to_string(), value.to_string());
+        self.query.push((key.to_string(), value.to_string()));
         self
     }
 
     /// Execute the request and return the response
     pub async fn send(self) -> TestResponse {
-        let uri = Uri::new(&self.path, if self.query.is_empty() { None } else { Some(self.query) });
+        // A query in the path itself (`/search?q=x`) comes before the `.query()` params
+        let (path, inline_query) = match self.path.split_once('?') {
+            Some((path, query)) => (path, query),
+            None => (self.path.as_str(), ""),
+        };
+        let mut raw_query = form_urlencoded::Serializer::for_suffix(inline_query.to_string(), 0);
+        raw_query.extend_pairs(&self.query);
+        let uri = Uri::with_raw_query(path, Some(&raw_query.finish()));
         
         let request = Request::new(
             self.method,
@@ -205,7 +237,7 @@ impl TestResponse {
     }
 
     /// Get all headers
-    pub fn headers(&self) -> &HashMap<String, String> {
+    pub fn headers(&self) -> &crate::HeaderMap {
         &self.response.headers
     }
 
@@ -335,7 +367,7 @@ macro_rules! assert_json {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Response;
+    use crate::{Flow, PluginResult, Response};
 
     async fn hello_handler(_req: Request, mut res: Response) -> Response {
         res.set_body(b"Hello, World!".to_vec());
@@ -419,6 +451,44 @@ mod tests {
             .assert_header("Content-Type");
     }
 
+    #[tokio::test]
+    async fn test_method_not_allowed_lists_allowed_methods() {
+        let server = Server::new()
+            .get("/hello", hello_handler)
+            .post("/hello", echo_handler);
+
+        let client = server.test();
+        let response = client.delete("/hello").send().await;
+
+        response.assert_status(crate::response::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.header("Allow").unwrap(), "GET, HEAD, OPTIONS, POST");
+    }
+
+    #[tokio::test]
+    async fn test_head_runs_get_handler_without_body() {
+        let server = Server::new()
+            .get("/hello", hello_handler);
+
+        let client = server.test();
+        let response = client.head("/hello").send().await;
+
+        response.assert_ok().assert_body_eq("");
+        assert_eq!(response.header("Content-Length").unwrap(), "13");
+    }
+
+    #[tokio::test]
+    async fn test_options_is_answered_automatically() {
+        let server = Server::new()
+            .get("/hello", hello_handler);
+
+        let client = server.test();
+        let response = client.options("/hello").send().await;
+
+        response.assert_status(crate::response::StatusCode::NoContent);
+        assert_eq!(response.header("Allow").unwrap(), "GET, HEAD, OPTIONS");
+        client.options("/nonexistent").send().await.assert_not_found();
+    }
+
     #[tokio::test]
     async fn test_not_found() {
         let server = Server::new();
@@ -428,6 +498,86 @@ mod tests {
         response.assert_not_found();
     }
 
+    #[tokio::test]
+    async fn test_fallback_serves_unmatched_paths() {
+        let server = Server::new()
+            .get("/hello", hello_handler)
+            .fallback(|_req: Request, res: Response| async move { res.text("index.html") });
+
+        let client = server.test();
+        client.get("/app/settings").send().await.assert_ok().assert_body_eq("index.html");
+        client.get("/hello").send().await.assert_body_eq("Hello, World!");
+    }
+
+    #[tokio::test]
+    async fn test_not_found_handler_keeps_404() {
+        let server = Server::new()
+            .not_found(|req: Request, res: Response| async move {
+                res.text(format!("nothing at {}", req.uri.path))
+            });
+
+        let client = server.test();
+        client
+            .get("/missing")
+            .send()
+            .await
+            .assert_not_found()
+            .assert_body_eq("nothing at /missing");
+    }
+
+    #[tokio::test]
+    async fn test_error_handler_renders_every_error() {
+        fn render(err: crate::Error, req: &Request) -> Response {
+            let status = err.into_response().status;
+            Response::new(status, format!("custom error at {}", req.uri.path))
+        }
+        async fn failing(_req: Request, _res: Response) -> Response {
+            crate::Error::Forbidden("no".into()).into_response()
+        }
+        // What a macro route expands to when an extractor fails
+        async fn extracting(mut req: Request, mut res: Response) -> Response {
+            match <crate::Json<serde_json::Value> as crate::FromRequest>::from_request(&mut req, &mut res).await {
+                Ok(_) => res,
+                Err(err) => err.into_response(),
+            }
+        }
+        fn deny(req: &mut Request, _res: &mut Response) -> Flow {
+            if req.uri.path == "/guarded" {
+                Flow::Stop(crate::Error::Unauthorized("token".into()).into_response())
+            } else {
+                Flow::Continue
+            }
+        }
+
+        let server = Server::new()
+            .middleware(deny)
+            .get("/forbidden", failing)
+            .post("/json", extracting)
+            .get("/guarded", hello_handler)
+            .error_handler(render);
+
+        let client = server.test();
+        client
+            .get("/forbidden")
+            .send()
+            .await
+            .assert_forbidden()
+            .assert_body_eq("custom error at /forbidden");
+        client
+            .post("/json")
+            .body("not json")
+            .send()
+            .await
+            .assert_bad_request()
+            .assert_body_eq("custom error at /json");
+        client.get("/guarded").send().await.assert_unauthorized();
+        client.get("/missing").send().await.assert_not_found().assert_body_eq("custom error at /missing");
+
+        let response = client.delete("/forbidden").send().await;
+        response.assert_body_eq("custom error at /forbidden");
+        assert_eq!(response.header("Allow").unwrap(), "GET, HEAD, OPTIONS");
+    }
+
     #[tokio::test]
     async fn test_with_headers() {
         let server = Server::new()
@@ -456,6 +606,41 @@ mod tests {
             .assert_body_eq("Hello, John!");
     }
 
+    #[tokio::test]
+    async fn test_route_params_are_percent_decoded() {
+        let server = Server::new()
+            .get("/hello/:name", param_handler);
+
+        let client = server.test();
+        client.get("/hello/John%20Doe").send().await.assert_body_eq("Hello, John Doe!");
+        client.get("/hello/%E9").send().await.assert_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_url_for_uses_the_serving_servers_names() {
+        async fn link(_req: Request, _res: Response) -> Response {
+            let url = crate::url_for("profile", [("id", 7)]).unwrap();
+            Response::new(crate::response::StatusCode::Ok, url)
+        }
+
+        // The same name on two servers in one process
+        let users = Server::new().named_route("profile", "GET", "/users/:id", link);
+        let admins = Server::new().prefix("/admin").named_route("profile", "GET", "/people/:id", link);
+        assert_eq!(admins.url_for("profile", [("id", 1)]).unwrap(), "/admin/people/1");
+
+        users.test().get("/users/1").send().await.assert_body_eq("/users/7");
+        admins.test().get("/admin/people/1").send().await.assert_body_eq("/admin/people/7");
+    }
+
+    #[test]
+    #[should_panic(expected = "route name 'profile' is used by both '/users/:id' and '/people/:id'")]
+    fn test_named_route_rejects_a_name_for_another_path() {
+        let _ = Server::new()
+            .named_route("profile", "GET", "/users/:id", hello_handler)
+            .named_route("profile", "PUT", "/users/:id/", hello_handler)
+            .named_route("profile", "GET", "/people/:id", hello_handler);
+    }
+
     #[tokio::test]
     async fn test_with_middleware() {
         fn add_header(req: &mut Request, res: &mut Response) -> Flow {
@@ -496,4 +681,161 @@ mod tests {
             .assert_ok()
             .assert_body_eq("Hello, Alice!");
     }
+
+    #[tokio::test]
+    async fn test_repeated_query_params() {
+        use crate::FromRequest;
+
+        #[derive(serde::Deserialize)]
+        struct Filter {
+            tag: Vec<String>,
+            page: u32,
+        }
+
+        async fn tags(mut req: Request, mut res: Response) -> Response {
+            let all = req.query_all("tag").join(",");
+            match crate::Query::<Filter>::from_request(&mut req, &mut res).await {
+                Ok(crate::Query(filter)) => {
+                    res.set_body(format!("{} | {} | page {}", all, filter.tag.join(","), filter.page).into_bytes());
+                    res
+                }
+                Err(err) => err.into_response(),
+            }
+        }
+
+        let client = Server::new().get("/tags", tags).test();
+        client
+            .get("/tags?tag=a&tag[]=b")
+            .query("tag", "c d")
+            .query("page", "2")
+            .send()
+            .await
+            .assert_ok()
+            .assert_body_eq("a,b,c d | a,b,c d | page 2");
+
+        let response = client.get("/tags?tag=a&page=two").send().await;
+        response.assert_status(crate::StatusCode::BadRequest);
+        assert!(response.text().contains("`page`"), "{}", response.text());
+    }
+
+    struct StampPlugin {
+        name: &'static str,
+        priority: i32,
+    }
+
+    #[async_trait::async_trait]
+    impl Plugin for StampPlugin {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        async fn on_response(&self, _req: &Request, res: &mut Response) -> PluginResult<()> {
+            let trail = res.headers.get("X-Stamp").cloned().unwrap_or_default();
+            res.headers.insert("X-Stamp".to_string(), format!("{}{}", trail, self.name));
+            Ok(())
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[tokio::test]
+    async fn test_on_response_runs_in_priority_order() {
+        let client = Server::new()
+            .get("/hello", hello_handler)
+            .test()
+            .with_plugin(Arc::new(StampPlugin { name: "b", priority: 10 }))
+            .with_plugin(Arc::new(StampPlugin { name: "a", priority: 100 }));
+
+        let response = client.get("/hello").send().await;
+
+        response
+            .assert_ok()
+            .assert_body_eq("Hello, World!")
+            .assert_header_eq("X-Stamp", "ab");
+    }
+
+    #[tokio::test]
+    async fn test_on_response_runs_on_not_found_and_short_circuit() {
+        fn block(_req: &mut Request, _res: &mut Response) -> Flow {
+            Flow::Stop(Response::new(crate::StatusCode::Forbidden, b"blocked"))
+        }
+
+        let client = Server::new()
+            .test()
+            .with_plugin(Arc::new(StampPlugin { name: "a", priority: 0 }));
+        client
+            .get("/missing")
+            .send()
+            .await
+            .assert_not_found()
+            .assert_header_eq("X-Stamp", "a");
+
+        let client = Server::new()
+            .middleware(block)
+            .get("/hello", hello_handler)
+            .test()
+            .with_plugin(Arc::new(StampPlugin { name: "a", priority: 0 }));
+        client
+            .get("/hello")
+            .send()
+            .await
+            .assert_forbidden()
+            .assert_header_eq("X-Stamp", "a");
+    }
+
+    /// Stamps responses to requests carrying `X-Registered-Probe`, so other tests are unaffected
+    struct RegisteredPlugin;
+
+    #[async_trait::async_trait]
+    impl Plugin for RegisteredPlugin {
+        fn name(&self) -> &'static str {
+            "test-registered"
+        }
+
+        async fn on_request(&self, req: &mut Request, _res: &mut Response) -> PluginResult<Option<Response>> {
+            if req.header("X-Registered-Probe") == Some("block") {
+                return Ok(Some(Response::new(crate::response::StatusCode::Forbidden, b"blocked by plugin")));
+            }
+            Ok(None)
+        }
+
+        async fn on_response(&self, req: &Request, res: &mut Response) -> PluginResult<()> {
+            if req.header("X-Registered-Probe").is_some() {
+                res.headers.insert("X-Registered".to_string(), "seen".to_string());
+            }
+            Ok(())
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_plugins_run_like_on_the_server() {
+        crate::register_plugin_async(Arc::new(RegisteredPlugin)).await.unwrap();
+        let client = Server::new().get("/hello", hello_handler).test();
+
+        client
+            .get("/hello")
+            .header("X-Registered-Probe", "pass")
+            .send()
+            .await
+            .assert_ok()
+            .assert_header_eq("X-Registered", "seen");
+        client
+            .get("/hello")
+            .header("X-Registered-Probe", "block")
+            .send()
+            .await
+            .assert_forbidden()
+            .assert_body_eq("blocked by plugin")
+            .assert_header_eq("X-Registered", "seen");
+    }
 }
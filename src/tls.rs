@@ -0,0 +1,222 @@
+use crate::config::TlsConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio_rustls::server::TlsStream;
+
+/// How often certificate files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// TLS termination for `Server::listen`, with certificates that can be swapped live
+///
+/// New handshakes pick up the latest certificate; connections already
+/// established keep the one they negotiated.
+pub(crate) struct TlsAcceptor {
+    cert: PathBuf,
+    key: PathBuf,
+    alpn: Vec<Vec<u8>>,
+    current: RwLock<Arc<rustls::ServerConfig>>,
+}
+
+impl TlsAcceptor {
+    /// Load the PEM cert chain and key named in `config`
+    pub(crate) fn load(config: &TlsConfig) -> std::io::Result<Self> {
+        // h2 is only offered when this build can speak it
+        let mut alpn = Vec::new();
+        if cfg!(feature = "http2") {
+            alpn.push(b"h2".to_vec());
+        }
+        alpn.push(b"http/1.1".to_vec());
+
+        let server_config = server_config(&config.cert, &config.key, &alpn)?;
+        Ok(Self {
+            cert: config.cert.clone(),
+            key: config.key.clone(),
+            alpn,
+            current: RwLock::new(Arc::new(server_config)),
+        })
+    }
+
+    pub(crate) async fn accept<S: crate::server::Io>(&self, socket: S) -> std::io::Result<TlsStream<S>> {
+        let config = Arc::clone(&self.current.read().unwrap());
+        tokio_rustls::TlsAcceptor::from(config).accept(socket).await
+    }
+
+    /// Re-read the certificate files; on failure the previous certificate stays in use
+    pub(crate) fn reload(&self) -> std::io::Result<()> {
+        let server_config = server_config(&self.cert, &self.key, &self.alpn)?;
+        *self.current.write().unwrap() = Arc::new(server_config);
+        Ok(())
+    }
+
+    /// Reload on SIGHUP or whenever the cert or key file changes on disk
+    pub(crate) async fn watch(self: Arc<Self>) {
+        #[cfg(unix)]
+        let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        let mut last_modified = self.modified();
+
+        loop {
+            #[cfg(unix)]
+            let hangup = async {
+                match sighup.as_mut() {
+                    Some(sighup) => {
+                        sighup.recv().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<()>();
+
+            let reason = tokio::select! {
+                _ = hangup => "SIGHUP",
+                _ = interval.tick() => {
+                    let modified = self.modified();
+                    if modified == last_modified {
+                        continue;
+                    }
+                    last_modified = modified;
+                    "certificate change"
+                }
+            };
+
+            match self.reload() {
+                Ok(()) => println!("[TLS] Reloaded certificate after {}", reason),
+                Err(e) => eprintln!("[TLS] Keeping previous certificate, reload failed: {}", e),
+            }
+        }
+    }
+
+    fn modified(&self) -> [Option<SystemTime>; 2] {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        [modified(&self.cert), modified(&self.key)]
+    }
+}
+
+fn server_config(
+    cert: &Path,
+    key: &Path,
+    alpn: &[Vec<u8>],
+) -> std::io::Result<rustls::ServerConfig> {
+    let invalid = |what: &str, path: &Path, e: &dyn std::fmt::Display| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} {}: {}", what, path.display(), e),
+        )
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid("invalid certificate", cert, &e))?;
+    if certs.is_empty() {
+        return Err(invalid("invalid certificate", cert, &"no certificates found"));
+    }
+    let key_der = PrivateKeyDer::from_pem_file(key).map_err(|e| invalid("invalid private key", key, &e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key_der))
+        .map_err(|e| invalid("unusable certificate", cert, &e))?;
+    config.alpn_protocols = alpn.to_vec();
+    Ok(config)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Self-signed `localhost` certificate written to `dir`
+    pub(crate) fn self_signed(dir: &std::path::Path) -> (TlsConfig, CertificateDer<'static>) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let config = TlsConfig {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+        };
+        std::fs::write(&config.cert, certified.cert.pem()).unwrap();
+        std::fs::write(&config.key, certified.key_pair.serialize_pem()).unwrap();
+        (config, certified.cert.der().clone())
+    }
+
+    /// Client that trusts only `cert`
+    pub(crate) fn connector(cert: CertificateDer<'static>, alpn: &[&[u8]]) -> tokio_rustls::TlsConnector {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        tokio_rustls::TlsConnector::from(Arc::new(config))
+    }
+
+    /// Handshake `acceptor` against a client trusting `cert`; returns the client side
+    async fn handshake(
+        acceptor: Arc<TlsAcceptor>,
+        cert: CertificateDer<'static>,
+        alpn: &[&[u8]],
+    ) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            if let Ok(mut tls) = acceptor.accept(socket).await {
+                let mut buf = [0u8; 4];
+                let _ = tls.read(&mut buf).await;
+            }
+        });
+
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
+        let mut tls = connector(cert, alpn).connect(name, socket).await?;
+        tls.write_all(b"ping").await?;
+        Ok(tls)
+    }
+
+    #[tokio::test]
+    async fn negotiates_alpn() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, cert) = self_signed(dir.path());
+        let acceptor = Arc::new(TlsAcceptor::load(&config).unwrap());
+
+        let tls = handshake(Arc::clone(&acceptor), cert.clone(), &[b"h2", b"http/1.1"]).await.unwrap();
+        let expected: &[u8] = if cfg!(feature = "http2") { b"h2" } else { b"http/1.1" };
+        assert_eq!(tls.get_ref().1.alpn_protocol(), Some(expected));
+
+        let tls = handshake(acceptor, cert, &[b"http/1.1"]).await.unwrap();
+        assert_eq!(tls.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, old_cert) = self_signed(dir.path());
+        let acceptor = Arc::new(TlsAcceptor::load(&config).unwrap());
+
+        let (_, new_cert) = self_signed(dir.path());
+        // Files changed, but nothing reloaded yet
+        assert!(handshake(Arc::clone(&acceptor), new_cert.clone(), &[]).await.is_err());
+
+        acceptor.reload().unwrap();
+        assert!(handshake(Arc::clone(&acceptor), new_cert, &[]).await.is_ok());
+        assert!(handshake(acceptor, old_cert, &[]).await.is_err());
+    }
+
+    #[test]
+    fn broken_reload_keeps_previous_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (config, _) = self_signed(dir.path());
+        let acceptor = TlsAcceptor::load(&config).unwrap();
+
+        std::fs::write(&config.key, "not a key").unwrap();
+        assert!(acceptor.reload().is_err());
+        assert!(TlsAcceptor::load(&config).is_err());
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{FromRequest, Request, Response, Error, Result};
+use crate::{BodyStream, FromRequest, Request, Response, Error, Result};
 use bytes::Bytes;
-use multer::Multipart;
+use multer::{Constraints, Multipart, SizeLimit};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tokio::fs::File;
@@ -101,33 +101,51 @@ impl FromRequest for FormData {
         // Get boundary from Content-Type header
         let content_type = req.headers
             .get("content-type")
-            .or_else(|| req.headers.get("Content-Type"))
-            .and_then(|v| v.first())
+            .cloned()
             .ok_or_else(|| Error::BadRequest("Missing Content-Type header".into()))?;
         
+        let body = req.take_body_stream();
+        Self::parse(body, &content_type, Constraints::new()).await
+    }
+}
+
+impl FormData {
+    /// Parse a streamed multipart body, rejecting any field larger than
+    /// `config.max_file_size` with 413 as soon as it crosses the limit
+    pub async fn from_stream(body: BodyStream, content_type: &str, config: &UploadConfig) -> Result<Self> {
+        let mut size_limit = SizeLimit::new().per_field(config.max_file_size as u64);
+        if let Some(max_body_size) = config.max_body_size {
+            // Checked before reading, so a client waiting on `100 Continue` never sends the body
+            if body.content_length().is_some_and(|len| len > max_body_size as u64) {
+                return Err(Error::PayloadTooLarge(format!(
+                    "Request body exceeds {} bytes",
+                    max_body_size
+                )));
+            }
+            size_limit = size_limit.whole_stream(max_body_size as u64);
+        }
+        Self::parse(body, content_type, Constraints::new().size_limit(size_limit)).await
+    }
+    
+    async fn parse(body: BodyStream, content_type: &str, constraints: Constraints) -> Result<Self> {
         let boundary = multer::parse_boundary(content_type)
             .map_err(|e| Error::BadRequest(format!("Invalid multipart boundary: {}", e)))?;
         
-        // Create stream from body bytes
-        use futures_util::stream::{self, StreamExt};
-        let body = std::mem::take(&mut req.body);
-        let body_stream = stream::once(async move { Ok::<_, multer::Error>(Bytes::from(body)) });
-        
-        let mut multipart = Multipart::new(body_stream, boundary);
+        let mut multipart = Multipart::with_constraints(body, boundary, constraints);
         
         let mut files: HashMap<String, Vec<UploadedFile>> = HashMap::new();
         let mut fields: HashMap<String, Vec<String>> = HashMap::new();
         
         // Parse fields
         while let Some(field) = multipart.next_field().await
-            .map_err(|e| Error::BadRequest(format!("Multipart parse error: {}", e)))? 
+            .map_err(|e| multipart_error("Multipart parse error", e))? 
         {
             let name = field.name().map(|s| s.to_string()).unwrap_or_else(|| "unknown".to_string());
             let filename = field.file_name().map(|s| s.to_string());
             let content_type = field.content_type().map(|m| m.to_string());
             
             let data = field.bytes().await
-                .map_err(|e| Error::BadRequest(format!("Failed to read field data: {}", e)))?;
+                .map_err(|e| multipart_error("Failed to read field data", e))?;
             
             if filename.is_some() {
                 // It's a file
@@ -150,12 +168,24 @@ impl FromRequest for FormData {
     }
 }
 
+fn multipart_error(context: &str, err: multer::Error) -> Error {
+    match err {
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            Error::PayloadTooLarge(err.to_string())
+        }
+        _ => Error::BadRequest(format!("{}: {}", context, err)),
+    }
+}
+
 /// File upload configuration
 #[derive(Debug, Clone)]
 pub struct UploadConfig {
     /// Maximum file size in bytes (default: 10MB)
     pub max_file_size: usize,
     
+    /// Maximum size of the whole request body in bytes (None = no limit)
+    pub max_body_size: Option<usize>,
+    
     /// Allowed file extensions (None = allow all)
     pub allowed_extensions: Option<Vec<String>>,
     
@@ -170,6 +200,7 @@ impl Default for UploadConfig {
     fn default() -> Self {
         Self {
             max_file_size: 10 * 1024 * 1024, // 10MB
+            max_body_size: None,
             allowed_extensions: None,
             allowed_mime_types: None,
             upload_dir: PathBuf::from("uploads"),
@@ -248,6 +279,7 @@ mod tests {
     fn test_upload_config_validation() {
         let config = UploadConfig {
             max_file_size: 1024,
+            max_body_size: None,
             allowed_extensions: Some(vec!["jpg".to_string(), "png".to_string()]),
             allowed_mime_types: Some(vec!["image/".to_string()]),
             upload_dir: PathBuf::from("uploads"),
@@ -294,4 +326,37 @@ mod tests {
         assert_eq!(form.field("name"), Some("John"));
         assert_eq!(form.fields_for("tags").map(|v| v.len()), Some(2));
     }
+    
+    #[tokio::test]
+    async fn test_form_data_from_stream_limits_fields() {
+        let body = b"--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\n0123456789\r\n--XyZ--\r\n";
+        let content_type = "multipart/form-data; boundary=XyZ";
+        
+        let config = UploadConfig { max_file_size: 64, ..Default::default() };
+        let form = FormData::from_stream(BodyStream::buffered(body.to_vec()), content_type, &config)
+            .await
+            .unwrap();
+        assert_eq!(form.file("file").map(|f| f.size), Some(10));
+        
+        let config = UploadConfig { max_file_size: 4, ..Default::default() };
+        let err = FormData::from_stream(BodyStream::buffered(body.to_vec()), content_type, &config)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PayloadTooLarge(_)));
+    }
+    
+    #[tokio::test]
+    async fn test_form_data_from_stream_rejects_declared_oversized_body_unread() {
+        let (_tx, mut body) = BodyStream::channel();
+        body = body.with_content_length(Some(1024));
+        let mut first_read = body.notify_first_read();
+        
+        let config = UploadConfig { max_body_size: Some(512), ..Default::default() };
+        let err = FormData::from_stream(body, "multipart/form-data; boundary=XyZ", &config)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PayloadTooLarge(_)));
+        // Dropped without ever being read
+        assert!(first_read.try_recv().is_err());
+    }
 }
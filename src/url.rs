@@ -0,0 +1,273 @@
+//! Named routes and reverse URL generation
+
+use std::fmt;
+use std::sync::Arc;
+
+use ahash::AHashMap;
+
+use crate::perfect_hash_router::{RoutePattern, Segment};
+
+/// Route names of one router, name -> path
+pub(crate) type RouteNames = Arc<AHashMap<String, String>>;
+
+tokio::task_local! {
+    /// Names of the router serving the current request
+    static SERVING_NAMES: RouteNames;
+}
+
+/// Why `url_for` could not build a URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// No route has this name
+    UnknownRoute(String),
+    /// The route has a param no value was given for
+    MissingParam { route: String, param: String },
+    /// A value was given for a param the route does not have
+    UnknownParam { route: String, param: String },
+    /// The value is empty, a `.`/`..` segment, or does not satisfy the param's constraint
+    InvalidParam { route: String, param: String, value: String },
+    /// The route's path is not a valid pattern, such as a bad constraint regex
+    InvalidRoute { route: String, reason: String },
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrlError::UnknownRoute(name) => write!(f, "No route named '{}'", name),
+            UrlError::MissingParam { route, param } => {
+                write!(f, "Route '{}' needs a value for '{}'", route, param)
+            }
+            UrlError::UnknownParam { route, param } => {
+                write!(f, "Route '{}' has no param '{}'", route, param)
+            }
+            UrlError::InvalidParam { route, param, value } => {
+                write!(f, "Value '{}' does not satisfy the constraint on '{}' in route '{}'", value, param, route)
+            }
+            UrlError::InvalidRoute { route, reason } => {
+                write!(f, "Route '{}' has an invalid path: {}", route, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+impl From<UrlError> for crate::Error {
+    fn from(err: UrlError) -> Self {
+        crate::Error::Internal(err.to_string())
+    }
+}
+
+/// Run `fut` with `names` visible to `url_for`
+pub(crate) async fn with_names<F: std::future::Future>(names: RouteNames, fut: F) -> F::Output {
+    SERVING_NAMES.scope(names, fut).await
+}
+
+fn lookup(name: &str) -> Option<String> {
+    let serving = SERVING_NAMES.try_with(|names| names.get(name).cloned()).ok().flatten();
+    serving.or_else(|| {
+        crate::ROUTES
+            .iter()
+            .find(|route| route.name == Some(name))
+            .map(|route| route.path.to_string())
+    })
+}
+
+/// Build the URL of the route named `name`, filling in its params
+///
+/// Param values are percent-encoded, so `("name", "a/b c")` fills
+/// `/files/:name` as `/files/a%2Fb%20c`. A catch-all value is a path: its
+/// `/` separate segments and `%XX` escapes already in it are kept. Every
+/// param needs a non-empty value, every value needs a param, and values
+/// must satisfy the param's constraint. Values that are, or whose catch-all
+/// segments are, `.` or `..` are refused, since path canonicalization would
+/// resolve them to another route.
+///
+/// Names come from the router serving the current request, then from the
+/// `#[get(.., name = "..")]` routes in the binary. Outside a handler (or in
+/// a task it spawned), use `Server::url_for`/`Router::url_for` for names
+/// given with `named_route`.
+///
+/// ```ignore
+/// #[get("/users/:id", name = "user_show")]
+/// async fn show(Path(id): Path<u64>) -> String { ... }
+///
+/// assert_eq!(url_for("user_show", [("id", 42)])?, "/users/42");
+/// ```
+pub fn url_for<K, V>(name: &str, params: impl IntoIterator<Item = (K, V)>) -> Result<String, UrlError>
+where
+    K: AsRef<str>,
+    V: fmt::Display,
+{
+    let path = lookup(name).ok_or_else(|| UrlError::UnknownRoute(name.to_string()))?;
+    fill(name, &path, params)
+}
+
+/// `url_for` on the route `name` whose path is `path`
+pub(crate) fn fill<K, V>(name: &str, path: &str, params: impl IntoIterator<Item = (K, V)>) -> Result<String, UrlError>
+where
+    K: AsRef<str>,
+    V: fmt::Display,
+{
+    let params: Vec<(K, String)> = params.into_iter().map(|(key, value)| (key, value.to_string())).collect();
+    build(name, path, &params)
+}
+
+fn build<K: AsRef<str>, V: AsRef<str>>(name: &str, path: &str, params: &[(K, V)]) -> Result<String, UrlError> {
+    let pattern = RoutePattern::try_parse(path).map_err(|reason| UrlError::InvalidRoute {
+        route: name.to_string(),
+        reason,
+    })?;
+    let mut used = vec![false; params.len()];
+    let mut value_of = |param: &str| {
+        let index = params.iter().position(|(key, _)| key.as_ref() == param).ok_or_else(|| {
+            UrlError::MissingParam {
+                route: name.to_string(),
+                param: param.to_string(),
+            }
+        })?;
+        used[index] = true;
+        Ok(params[index].1.as_ref())
+    };
+
+    let mut url = String::with_capacity(path.len());
+    for segment in &pattern.segments {
+        url.push('/');
+        match segment {
+            Segment::Static(text) => encode_into(&mut url, text, false),
+            Segment::Param { name: param, constraint } => {
+                let value = value_of(param)?;
+                if value.is_empty()
+                    || value == "."
+                    || value == ".."
+                    || constraint.as_ref().is_some_and(|c| !c.accepts(value))
+                {
+                    return Err(UrlError::InvalidParam {
+                        route: name.to_string(),
+                        param: param.clone(),
+                        value: value.to_string(),
+                    });
+                }
+                encode_into(&mut url, value, false);
+            }
+            Segment::CatchAll { name: param } => {
+                let value = value_of(param)?.trim_matches('/');
+                if value.is_empty() || value.split('/').any(is_dot_segment) {
+                    return Err(UrlError::InvalidParam {
+                        route: name.to_string(),
+                        param: param.clone(),
+                        value: value.to_string(),
+                    });
+                }
+                encode_into(&mut url, value, true);
+            }
+        }
+    }
+    if url.is_empty() {
+        url.push('/');
+    }
+
+    if let Some(index) = used.iter().position(|used| !used) {
+        return Err(UrlError::UnknownParam {
+            route: name.to_string(),
+            param: params[index].0.as_ref().to_string(),
+        });
+    }
+    Ok(url)
+}
+
+/// `.` or `..` in a catch-all value, whose `%2e` escapes canonicalization decodes
+fn is_dot_segment(segment: &str) -> bool {
+    let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+    segment == "." || segment == ".."
+}
+
+/// Percent-encode `value` as path segment text
+///
+/// With `as_path`, `/` and existing `%XX` escapes are left as they are.
+fn encode_into(url: &mut String, value: &str, as_path: bool) {
+    let bytes = value.as_bytes();
+    for (i, &byte) in bytes.iter().enumerate() {
+        let keep = byte.is_ascii_alphanumeric()
+            || b"-._~!$&'()*+,;=:@".contains(&byte)
+            || (as_path && byte == b'/')
+            || (as_path
+                && byte == b'%'
+                && bytes.get(i + 1..i + 3).is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)));
+        if keep {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_and_encodes_params() {
+        let params = [("id", "42"), ("name", "a/b c")];
+        assert_eq!(build("r", "/users/:id/files/:name", &params).unwrap(), "/users/42/files/a%2Fb%20c");
+        assert_eq!(build("r", "/", &[] as &[(&str, &str)]).unwrap(), "/");
+        assert_eq!(build("r", "/caf\u{e9}/:q", &[("q", "50%")]).unwrap(), "/caf%C3%A9/50%25");
+
+        let rest = [("rest", "css/site app.css")];
+        assert_eq!(build("r", "/static/*rest", &rest).unwrap(), "/static/css/site%20app.css");
+        let rest = [("rest", "a%2Fb/c")];
+        assert_eq!(build("r", "/static/*rest", &rest).unwrap(), "/static/a%2Fb/c");
+    }
+
+    #[test]
+    fn rejects_missing_unknown_and_invalid_params() {
+        assert_eq!(
+            build("user_show", "/users/:id", &[] as &[(&str, &str)]),
+            Err(UrlError::MissingParam { route: "user_show".into(), param: "id".into() })
+        );
+        assert_eq!(
+            build("user_show", "/users/:id", &[("id", "1"), ("page", "2")]),
+            Err(UrlError::UnknownParam { route: "user_show".into(), param: "page".into() })
+        );
+        assert!(matches!(
+            build("user_show", "/users/:id<u64>", &[("id", "ada")]),
+            Err(UrlError::InvalidParam { .. })
+        ));
+        assert!(matches!(build("r", "/static/*rest", &[("rest", "/")]), Err(UrlError::InvalidParam { .. })));
+        assert!(matches!(build("r", "/users/:id<[0-9>", &[("id", "1")]), Err(UrlError::InvalidRoute { .. })));
+    }
+
+    #[test]
+    fn rejects_dot_segments() {
+        for value in [".", ".."] {
+            assert!(
+                matches!(build("file", "/files/:name", &[("name", value)]), Err(UrlError::InvalidParam { .. })),
+                "{value}"
+            );
+        }
+        assert_eq!(build("file", "/files/:name", &[("name", "...")]).unwrap(), "/files/...");
+        // Escaped, so it stays the literal text
+        assert_eq!(build("file", "/files/:name", &[("name", "%2e%2e")]).unwrap(), "/files/%252e%252e");
+
+        for value in ["a/../../b", "./a", "a/.", "a/%2E%2E/b"] {
+            assert!(
+                matches!(build("r", "/static/*rest", &[("rest", value)]), Err(UrlError::InvalidParam { .. })),
+                "{value}"
+            );
+        }
+        assert_eq!(build("r", "/static/*rest", &[("rest", "a/.well-known/b")]).unwrap(), "/static/a/.well-known/b");
+    }
+
+    #[tokio::test]
+    async fn looks_up_names_of_the_serving_router() {
+        let names: RouteNames = Arc::new([("url_tests_user_show".to_string(), "/users/:id".to_string())].into_iter().collect());
+        let url = with_names(names, async { url_for("url_tests_user_show", [("id", 7)]) }).await;
+        assert_eq!(url.unwrap(), "/users/7");
+
+        // Only while that router serves the request
+        assert_eq!(
+            url_for("url_tests_user_show", [("id", 7)]),
+            Err(UrlError::UnknownRoute("url_tests_user_show".into()))
+        );
+    }
+}
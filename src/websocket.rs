@@ -25,7 +25,6 @@
 use crate::{Request, Response, StatusCode};
 use std::pin::Pin;
 use std::future::Future;
-use tokio::net::TcpStream;
 
 use tokio_tungstenite::{
     tungstenite::{
@@ -79,12 +78,13 @@ impl From<Message> for WsMessage {
 
 /// WebSocket connection
 pub struct WebSocket {
-    stream: WebSocketStream<TcpStream>,
+    stream: WebSocketStream<Box<dyn crate::server::Io>>,
 }
 
 impl WebSocket {
-    /// Create a new WebSocket from a TCP stream
-    pub(crate) async fn new(stream: TcpStream) -> Self {
+    /// Create a new WebSocket from an upgraded connection (TCP or TLS)
+    pub(crate) async fn new(stream: impl crate::server::Io) -> Self {
+        let stream: Box<dyn crate::server::Io> = Box::new(stream);
         Self {
             stream: WebSocketStream::from_raw_socket(stream, Role::Server, None).await,
         }
@@ -162,7 +162,7 @@ pub fn websocket_upgrade(req: &Request) -> Option<Response> {
     let key = req.header("Sec-WebSocket-Key")?;
     let accept_key = generate_accept_key(key);
 
-    let mut response = Response::new(StatusCode::Custom(101, "Switching Protocols".into()), b"");
+    let mut response = Response::new(StatusCode::SWITCHING_PROTOCOLS, b"");
     response.headers.insert("Upgrade".to_string(), "websocket".to_string());
     response.headers.insert("Connection".to_string(), "Upgrade".to_string());
     response.headers.insert("Sec-WebSocket-Accept".to_string(), accept_key);